@@ -0,0 +1,37 @@
+//! A small criterion-style runner: warms the routine up, times a fixed number of samples
+//! and prints the median with the fastest and slowest, plus throughput when given a size.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const WARM_UP_RUNS: usize = 2;
+const SAMPLES: usize = 10;
+
+/// Times `routine` and prints a report line under `name`. With `bytes`, also reports how
+/// many MiB per second one run of `routine` moves. Returns the median run time.
+pub fn bench<T>(name: &str, bytes: Option<u64>, mut routine: impl FnMut() -> T) -> Duration {
+    for _ in 0..WARM_UP_RUNS {
+        black_box(routine());
+    }
+    let mut times: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(routine());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    let median = times[SAMPLES / 2];
+    let mut report = format!(
+        "{name:<40} time: [{:>9.3?} {:>9.3?} {:>9.3?}]",
+        times[0],
+        median,
+        times[SAMPLES - 1]
+    );
+    if let Some(bytes) = bytes {
+        let mib_per_second = bytes as f64 / (1024.0 * 1024.0) / median.as_secs_f64();
+        report += &format!("  thrpt: {mib_per_second:.0} MiB/s");
+    }
+    println!("{report}");
+    median
+}
@@ -0,0 +1,49 @@
+//! Throughput of `HttpClient` fetching a large body from a local server at several read
+//! buffer sizes. Run with `cargo bench --bench http_read`.
+
+mod harness;
+
+use capynet::http::HttpClient;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+const BODY_SIZE: usize = 32 * 1024 * 1024;
+const READ_BUFFER_SIZES: [usize; 4] = [512, 4 * 1024, 16 * 1024, 64 * 1024];
+
+/// Serves `response` to every connection, after reading the request head.
+fn serve(listener: TcpListener, response: Vec<u8>) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => request.extend_from_slice(&buffer[..read]),
+            }
+        }
+        let _ = stream.write_all(&response);
+    }
+}
+
+fn main() {
+    let body: Vec<u8> = (0..BODY_SIZE).map(|i| (i % 251) as u8).collect();
+    let mut response =
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {BODY_SIZE}\r\n\r\n").into_bytes();
+    response.extend_from_slice(&body);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || serve(listener, response));
+
+    for read_buffer_size in READ_BUFFER_SIZES {
+        let client = HttpClient::new().with_read_buffer_size(read_buffer_size);
+        // The buffer size must only change speed, never what is read
+        assert_eq!(client.fetch(&url).unwrap().body(), body);
+        harness::bench(
+            &format!("fetch 32 MiB, {read_buffer_size} byte reads"),
+            Some(BODY_SIZE as u64),
+            || client.fetch(&url).unwrap(),
+        );
+    }
+}
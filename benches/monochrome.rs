@@ -0,0 +1,37 @@
+//! Time to render a page of small text with the anti-aliased supersampling rasterizer and
+//! with the monochrome one. Run with `cargo bench --bench monochrome`.
+
+mod harness;
+
+use capynet::font::{self, ParseOptions};
+use capynet::rasterizer::{MonochromeRasterizer, Rasterizer, SupersampleRasterizer};
+use capynet::renderer::{self, TextOptions};
+use std::sync::Arc;
+
+const FONT_PATH: &str = "assets/fonts/arial.ttf";
+const WIDTH: usize = 800;
+const HEIGHT: usize = 600;
+const PARAGRAPH: &str = "The quick brown fox jumps over the lazy dog. Pack my box with \
+    five dozen liquor jugs! How vexingly quick daft zebras jump; 0123456789 @&%$. ";
+
+fn main() {
+    let font = font::parse_from_file(FONT_PATH, ParseOptions::default()).unwrap();
+    let page = PARAGRAPH.repeat(30);
+    let rasterizers: [(&str, Arc<dyn Rasterizer>); 2] = [
+        ("supersample", Arc::new(SupersampleRasterizer)),
+        ("monochrome", Arc::new(MonochromeRasterizer)),
+    ];
+    let mut bitmap = vec![0; WIDTH * HEIGHT * 4];
+    for (name, rasterizer) in rasterizers {
+        let options = TextOptions {
+            size: 12.0,
+            max_width: WIDTH,
+            rasterizer,
+            ..TextOptions::default()
+        };
+        harness::bench(&format!("render 12px page, {name}"), None, || {
+            bitmap.fill(255);
+            renderer::render_text(&mut bitmap, &page, 0, 0, WIDTH, &font, &options).unwrap()
+        });
+    }
+}
@@ -0,0 +1,46 @@
+//! Segment counts and fill times for flattened glyph outlines with and without
+//! `rasterizer::simplify`, at several pixel sizes. Run with `cargo bench --bench simplify`.
+
+mod harness;
+
+use capynet::font::{self, ParseOptions};
+use capynet::rasterizer::{self, Rasterizer, Segment, SupersampleRasterizer};
+
+const FONT_PATH: &str = "assets/fonts/arial.ttf";
+const CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789@&%$";
+const SIZES: [f32; 3] = [8.0, 16.0, 96.0];
+// The tolerance the renderer simplifies with under `simplify_outlines`
+const TOLERANCE: f32 = 0.2;
+
+fn fill_all(outlines: &[Vec<Segment>]) {
+    for outline in outlines {
+        SupersampleRasterizer.fill(outline, &rasterizer::bounding_box(outline));
+    }
+}
+
+fn main() {
+    let font = font::parse_from_file(FONT_PATH, ParseOptions::default()).unwrap();
+    for size in SIZES {
+        let flattened: Vec<Vec<Segment>> = CHARS
+            .chars()
+            .filter_map(|char| font.outline_at_size(font.glyph_index(char)?, size))
+            .map(|contours| rasterizer::flatten(&contours))
+            .collect();
+        let simplified: Vec<Vec<Segment>> = flattened
+            .iter()
+            .map(|outline| rasterizer::simplify(outline, TOLERANCE))
+            .collect();
+        let count = |outlines: &[Vec<Segment>]| outlines.iter().map(Vec::len).sum::<usize>();
+        println!(
+            "{size}px: {} segments flattened, {} simplified",
+            count(&flattened),
+            count(&simplified)
+        );
+        harness::bench(&format!("fill {size}px, flattened"), None, || {
+            fill_all(&flattened)
+        });
+        harness::bench(&format!("fill {size}px, simplified"), None, || {
+            fill_all(&simplified)
+        });
+    }
+}
@@ -0,0 +1,307 @@
+use crate::url::Url;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    // Cookies set without a Domain attribute are only sent back to the exact host that set them
+    host_only: bool,
+    path: String,
+    expires: Option<SystemTime>,
+    secure: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `url`. Returns `None` for malformed
+    /// cookies and for cookies whose Domain attribute doesn't cover the setting host.
+    pub fn parse(url: &Url, set_cookie: &str, now: SystemTime) -> Option<Cookie> {
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: url.host().to_string(),
+            host_only: true,
+            path: default_path(url),
+            expires: None,
+            secure: false,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    if !domain_matches(url.host(), &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+            } else if key.eq_ignore_ascii_case("path") {
+                if value.starts_with('/') {
+                    cookie.path = value.to_string();
+                }
+            } else if key.eq_ignore_ascii_case("expires") {
+                if let Some(expires) = parse_http_date(value) {
+                    cookie.expires = Some(expires);
+                }
+            } else if key.eq_ignore_ascii_case("max-age") {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    max_age = Some(seconds);
+                }
+            } else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            }
+        }
+
+        // Max-Age takes precedence over Expires; zero or negative means "expire now"
+        if let Some(seconds) = max_age {
+            cookie.expires = Some(match u64::try_from(seconds) {
+                Ok(seconds) if seconds > 0 => now + Duration::from_secs(seconds),
+                _ => UNIX_EPOCH,
+            });
+        }
+        Some(cookie)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let domain_ok = if self.host_only {
+            url.host() == self.domain
+        } else {
+            domain_matches(url.host(), &self.domain)
+        };
+        domain_ok
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Stores cookies received via `Set-Cookie` and picks the ones to send back with a request.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn store(&mut self, url: &Url, set_cookie: &str) {
+        let now = SystemTime::now();
+        let Some(cookie) = Cookie::parse(url, set_cookie, now) else {
+            return;
+        };
+
+        // A cookie replaces any earlier one with the same name, domain and path. An already
+        // expired cookie is how servers delete one, so it is never stored.
+        self.cookies.retain(|existing| {
+            existing.name != cookie.name
+                || existing.domain != cookie.domain
+                || existing.path != cookie.path
+        });
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Builds the `Cookie` header value for a request to `url`, dropping expired cookies.
+    pub fn cookie_header(&mut self, url: &Url) -> Option<String> {
+        let now = SystemTime::now();
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut cookies: Vec<&Cookie> = self.cookies.iter().filter(|c| c.matches(url)).collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        // Cookies with longer paths are listed first
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || request_path.as_bytes().get(cookie_path.len()) == Some(&b'/')))
+}
+
+/// The directory of the request path, used when a cookie has no Path attribute.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(index) => url.path()[..index].to_string(),
+    }
+}
+
+/// Parses an HTTP date such as `Sun, 06 Nov 1994 08:49:37 GMT`, also accepting the
+/// dash-separated `06-Nov-1994` form that many servers use in cookies.
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let date = date.split_once(',').map_or(date, |(_, rest)| rest);
+    let mut parts = date.split([' ', '-']).filter(|part| !part.is_empty());
+
+    let day = parts.next()?.parse::<u64>().ok()?;
+    let month = match parts.next()?.to_ascii_lowercase().as_str() {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "aug" => 8,
+        "sep" => 9,
+        "oct" => 10,
+        "nov" => 11,
+        "dec" => 12,
+        _ => return None,
+    };
+    let mut year = parts.next()?.parse::<u64>().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    let mut time = parts.next()?.split(':');
+    let hours = time.next()?.parse::<u64>().ok()?;
+    let minutes = time.next()?.parse::<u64>().ok()?;
+    let seconds = time.next()?.parse::<u64>().ok()?;
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day);
+    let seconds = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Howard Hinnant's days_from_civil, restricted to dates after 1970
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn domain_path_and_secure_limit_where_cookies_are_sent() {
+        let mut jar = CookieJar::new();
+        let origin = url("https://www.example.com/account/login");
+        jar.store(&origin, "host=1");
+        jar.store(&origin, "shared=2; Domain=.example.com; Path=/");
+        jar.store(&origin, "secret=3; Path=/account; Secure");
+
+        assert_eq!(
+            jar.cookie_header(&url("https://www.example.com/account/settings")),
+            Some(String::from("host=1; secret=3; shared=2"))
+        );
+        // Domain cookies reach subdomains, host-only cookies don't
+        assert_eq!(
+            jar.cookie_header(&url("https://api.example.com/")),
+            Some(String::from("shared=2"))
+        );
+        // Secure cookies stay off plain HTTP, and /accounts isn't under /account
+        assert_eq!(
+            jar.cookie_header(&url("http://www.example.com/accounts")),
+            Some(String::from("shared=2"))
+        );
+        assert_eq!(jar.cookie_header(&url("https://example.org/")), None);
+    }
+
+    #[test]
+    fn domain_not_covering_the_host_is_rejected() {
+        let origin = url("http://www.example.com/");
+        assert!(Cookie::parse(&origin, "a=1; Domain=other.com", SystemTime::now()).is_none());
+        assert!(Cookie::parse(&origin, "a=1; Domain=ample.com", SystemTime::now()).is_none());
+    }
+
+    #[test]
+    fn expired_cookies_delete_and_are_dropped() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        jar.store(&origin, "session=abc");
+        jar.store(&origin, "session=; Max-Age=0");
+        assert!(jar.cookies().is_empty());
+
+        jar.store(&origin, "old=1; Expires=Thu, 01-Jan-1971 00:00:00 GMT");
+        assert_eq!(jar.cookie_header(&origin), None);
+    }
+
+    #[test]
+    fn http_dates_parse_in_both_forms() {
+        let expected = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(expected)
+        );
+        assert_eq!(parse_http_date("06 Foo 1994 08:49:37 GMT"), None);
+    }
+}
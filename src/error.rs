@@ -5,7 +5,7 @@ pub struct CapyError {
     error_impl: Box<ErrorImpl>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorCode {
     Cancelled,
     Unknown,
@@ -27,7 +27,10 @@ pub enum ErrorCode {
 
 impl std::error::Error for CapyError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.error_impl.source.as_deref()
+        self.error_impl
+            .source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
     }
 }
 
@@ -54,16 +57,41 @@ impl Display for ErrorCode {
     }
 }
 
+impl ErrorCode {
+    /// The HTTP status closest to this code, following the gRPC status mapping. This is the
+    /// inverse of `HttpResponse::error_for_status` for the statuses it handles.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCode::Cancelled => 499,
+            ErrorCode::Unknown => 500,
+            ErrorCode::InvalidArgument => 400,
+            ErrorCode::DeadlineExceeded => 504,
+            ErrorCode::NotFound => 404,
+            ErrorCode::AlreadyExists => 409,
+            ErrorCode::PermissionDenied => 403,
+            ErrorCode::ResourceExhausted => 429,
+            ErrorCode::FailedPrecondition => 400,
+            ErrorCode::Aborted => 409,
+            ErrorCode::OutOfRange => 400,
+            ErrorCode::Unimplemented => 501,
+            ErrorCode::Internal => 500,
+            ErrorCode::Unavailable => 503,
+            ErrorCode::DataLoss => 500,
+            ErrorCode::Unauthenticated => 401,
+        }
+    }
+}
+
 impl Display for CapyError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         // FIXME: Print the entire error chain (the source field)
-        write!(
-            f,
-            "[{}] {}\n",
-            self.error_impl.code, self.error_impl.message
-        )?;
+        write!(f, "[{}] ", self.error_impl.code)?;
+        for context in self.context_chain() {
+            write!(f, "{}: ", context)?;
+        }
+        writeln!(f, "{}", self.error_impl.message)?;
         if let Some(source) = &self.error_impl.source {
-            write!(f, "   Caused by: {}\n", source)?;
+            writeln!(f, "   Caused by: {}", source)?;
         }
         Ok(())
     }
@@ -75,6 +103,7 @@ impl CapyError {
             error_impl: Box::new(ErrorImpl {
                 code,
                 message: message.to_string(),
+                contexts: Vec::new(),
                 source: None,
             }),
         }
@@ -83,35 +112,122 @@ impl CapyError {
     pub fn with_source(
         code: ErrorCode,
         message: &'static str,
-        source: Box<dyn std::error::Error + 'static>,
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
     ) -> Self {
         Self {
             error_impl: Box::new(ErrorImpl {
                 code,
                 message: message.to_string(),
+                contexts: Vec::new(),
                 source: Some(source),
             }),
         }
     }
 
+    pub fn code(&self) -> ErrorCode {
+        self.error_impl.code
+    }
+
     pub fn with_context(mut self, extra_context: &str) -> Self {
-        self.error_impl.message = format!("{}: {}", extra_context, self.error_impl.message);
+        self.error_impl.contexts.push(extra_context.to_string());
         self
     }
+
+    pub fn to_http_status(&self) -> u16 {
+        self.error_impl.code.http_status()
+    }
+
+    /// The error as a JSON problem body, `{"code": "NOT_FOUND", "message": "..."}`, for
+    /// reporting it over HTTP. The message includes the contexts but not the source error.
+    pub fn to_problem_json(&self) -> String {
+        let mut message = String::new();
+        for context in self.context_chain() {
+            message.push_str(context);
+            message.push_str(": ");
+        }
+        message.push_str(&self.error_impl.message);
+        format!(
+            "{{\"code\": \"{}\", \"message\": \"{}\"}}",
+            self.error_impl.code,
+            escape_json(&message)
+        )
+    }
+
+    /// The contexts added with `with_context`, outermost (most recently added) first.
+    pub fn context_chain(&self) -> Vec<&str> {
+        self.error_impl
+            .contexts
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char.is_control() => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+            char => escaped.push(char),
+        }
+    }
+    escaped
 }
 
 #[derive(Debug)]
 struct ErrorImpl {
     code: ErrorCode,
     message: String,
-    source: Option<Box<dyn std::error::Error + 'static>>,
+    // In the order they were added, so the innermost context comes first
+    contexts: Vec<String>,
+    // Send + Sync so errors can be handed across threads
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
 }
 
 impl From<std::io::Error> for CapyError {
     fn from(err: std::io::Error) -> CapyError {
+        use std::io::ErrorKind;
+        let (code, message) = match err.kind() {
+            ErrorKind::TimedOut => (ErrorCode::DeadlineExceeded, "operation timed out"),
+            ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::AddrNotAvailable => (ErrorCode::Unavailable, "connection failed"),
+            ErrorKind::NotFound => (ErrorCode::NotFound, "file not found"),
+            ErrorKind::PermissionDenied => (ErrorCode::PermissionDenied, "permission denied"),
+            ErrorKind::AlreadyExists => (ErrorCode::AlreadyExists, "file already exists"),
+            ErrorKind::UnexpectedEof => (ErrorCode::DataLoss, "unexpected end of file"),
+            _ => (ErrorCode::Unknown, "I/O error"),
+        };
+        CapyError::with_source(code, message, Box::new(err))
+    }
+}
+
+impl From<std::str::Utf8Error> for CapyError {
+    fn from(err: std::str::Utf8Error) -> CapyError {
+        CapyError::with_source(ErrorCode::DataLoss, "invalid UTF-8", Box::new(err))
+    }
+}
+
+impl From<std::string::FromUtf8Error> for CapyError {
+    fn from(err: std::string::FromUtf8Error) -> CapyError {
+        CapyError::with_source(ErrorCode::DataLoss, "invalid UTF-8", Box::new(err))
+    }
+}
+
+impl From<std::num::TryFromIntError> for CapyError {
+    fn from(err: std::num::TryFromIntError) -> CapyError {
         CapyError::with_source(
-            ErrorCode::Unknown,
-            "FIXME: define a real IOError => CapyError mapping",
+            ErrorCode::OutOfRange,
+            "integer out of range for its type",
             Box::new(err),
         )
     }
@@ -129,3 +245,95 @@ where
         self.map_err(|e| e.into().with_context(context))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contexts_are_kept_as_separate_frames() {
+        let err = CapyError::new(ErrorCode::NotFound, "table not found")
+            .with_context("cmap")
+            .with_context("parsing font")
+            .with_context("arial.ttf");
+        assert_eq!(err.context_chain(), ["arial.ttf", "parsing font", "cmap"]);
+        assert_eq!(
+            err.to_string(),
+            "[NOT_FOUND] arial.ttf: parsing font: cmap: table not found\n"
+        );
+    }
+
+    #[test]
+    fn error_context_converts_and_adds_a_frame() {
+        let result: Result<(), std::io::Error> = Err(std::io::ErrorKind::NotFound.into());
+        let err = result.error_context("opening font").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::NotFound);
+        assert_eq!(err.context_chain(), ["opening font"]);
+    }
+
+    #[test]
+    fn each_code_maps_to_an_http_status() {
+        let cases = [
+            (ErrorCode::Cancelled, 499),
+            (ErrorCode::Unknown, 500),
+            (ErrorCode::InvalidArgument, 400),
+            (ErrorCode::DeadlineExceeded, 504),
+            (ErrorCode::NotFound, 404),
+            (ErrorCode::AlreadyExists, 409),
+            (ErrorCode::PermissionDenied, 403),
+            (ErrorCode::ResourceExhausted, 429),
+            (ErrorCode::FailedPrecondition, 400),
+            (ErrorCode::Aborted, 409),
+            (ErrorCode::OutOfRange, 400),
+            (ErrorCode::Unimplemented, 501),
+            (ErrorCode::Internal, 500),
+            (ErrorCode::Unavailable, 503),
+            (ErrorCode::DataLoss, 500),
+            (ErrorCode::Unauthenticated, 401),
+        ];
+        for (code, status) in cases {
+            assert_eq!(code.http_status(), status, "{code}");
+            assert_eq!(CapyError::new(code, "failed").to_http_status(), status);
+        }
+    }
+
+    #[test]
+    fn problem_json_carries_the_code_and_escaped_message() {
+        let err = CapyError::new(ErrorCode::NotFound, "no \"page\" here").with_context("/a\tb");
+        assert_eq!(
+            err.to_problem_json(),
+            "{\"code\": \"NOT_FOUND\", \"message\": \"/a\\tb: no \\\"page\\\" here\"}"
+        );
+    }
+
+    #[test]
+    fn common_error_sources_convert_to_the_expected_code() {
+        let invalid = vec![0xC3, 0x28];
+        let err: CapyError = std::str::from_utf8(&invalid).unwrap_err().into();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        let err: CapyError = String::from_utf8(invalid).unwrap_err().into();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        let err: CapyError = u16::try_from(70_000u32).unwrap_err().into();
+        assert_eq!(err.code(), ErrorCode::OutOfRange);
+        assert!(std::error::Error::source(&err).is_some());
+
+        let cases = [
+            (std::io::ErrorKind::TimedOut, ErrorCode::DeadlineExceeded),
+            (
+                std::io::ErrorKind::ConnectionRefused,
+                ErrorCode::Unavailable,
+            ),
+            (std::io::ErrorKind::NotFound, ErrorCode::NotFound),
+            (
+                std::io::ErrorKind::PermissionDenied,
+                ErrorCode::PermissionDenied,
+            ),
+            (std::io::ErrorKind::UnexpectedEof, ErrorCode::DataLoss),
+            (std::io::ErrorKind::Other, ErrorCode::Unknown),
+        ];
+        for (kind, code) in cases {
+            let err: CapyError = std::io::Error::from(kind).into();
+            assert_eq!(err.code(), code, "{kind:?}");
+        }
+    }
+}
@@ -1,18 +1,165 @@
+// Table structs mirror the on-disk layout, so some parsed fields and tags are never read
+#![allow(dead_code)]
+
 use crate::error::{CapyError, ErrorCode};
-use core::num;
-use std::io::Read;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Debug)]
 pub struct Font {
+    data: FontData,
     font_directory_table: FontDirectoryTable,
     cmap_table: CmapTable,
     head_table: HeadTable,
     hhea_table: HheaTable,
     maxp_table: MaxpTable,
+    hmtx_table: HmtxTable,
+    loca_table: LocaTable,
     glyf_table: GlyfTable,
+    // Tables nothing needs to lay out text are parsed on first use
+    kern_table: LazyTable<KernTable>,
+    gpos_table: LazyTable<GposTable>,
+    vmtx_table: LazyTable<VmtxTable>,
+    vorg_table: LazyTable<VorgTable>,
+    os2_table: LazyTable<Os2Table>,
+    post_table: LazyTable<PostTable>,
+    meta_table: LazyTable<MetaTable>,
+    gasp_table: LazyTable<GaspTable>,
+    bitmap_location_table: Option<BitmapLocationTable>,
+    name_table: LazyTable<NameTable>,
+    // What the font was parsed with, for the tables parsed later
+    options: ParseOptions,
+    // Decoded outlines in font units, shared by every size they're drawn at
+    outline_cache: Mutex<OutlineCache>,
     // Other required tables can be added here as needed
 }
 
+/// The font bytes, kept around so tables can be handed out verbatim.
+enum FontData {
+    // The whole font file
+    File(Vec<u8>),
+    // Only the tables that were parsed, keyed by tag, for fonts loaded from a reader
+    Tables(HashMap<u32, Vec<u8>>),
+    // The whole font file, mapped into memory
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl std::fmt::Debug for FontData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontData::File(bytes) => write!(f, "FontData({} bytes)", bytes.len()),
+            FontData::Tables(tables) => write!(f, "FontData({} tables)", tables.len()),
+            #[cfg(feature = "mmap")]
+            FontData::Mapped(mmap) => write!(f, "FontData({} bytes mapped)", mmap.len()),
+        }
+    }
+}
+
+/// The kind of font file, from the scalar type at the start of the offset subtable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SfntVersion {
+    // 0x00010000
+    TrueType,
+    // 'true', used by older Apple fonts
+    AppleTrueType,
+    // 'OTTO', OpenType with CFF outlines
+    OpenTypeCff,
+    // 'typ1', an old Apple wrapper around a PostScript Type 1 font
+    PostScriptType1,
+    // 'ttcf', a TrueType collection holding several fonts
+    Collection,
+}
+
+impl SfntVersion {
+    fn from_scalar_type(scalar_type: u32) -> Option<SfntVersion> {
+        match &scalar_type.to_be_bytes() {
+            [0x00, 0x01, 0x00, 0x00] => Some(SfntVersion::TrueType),
+            b"true" => Some(SfntVersion::AppleTrueType),
+            b"OTTO" => Some(SfntVersion::OpenTypeCff),
+            b"typ1" => Some(SfntVersion::PostScriptType1),
+            b"ttcf" => Some(SfntVersion::Collection),
+            _ => None,
+        }
+    }
+}
+
+/// How picky font parsing is. The default is lenient, accepting the minor spec violations that
+/// fonts in the wild often have, as browsers do.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ParseOptions {
+    // Reject fonts that break the spec in ways the parser could otherwise work around: font
+    // directory search fields that don't match the table count, tables out of tag order or
+    // not 4-byte aligned, a head table with the wrong magic number, and counts that overrun
+    // their table, which lenient parsing clamps to what fits. Every table and glyph is parsed
+    // up front, so one that is malformed fails the load instead of counting as missing.
+    pub strict: bool,
+    // Reject fonts where a table's checksum doesn't match the font directory
+    pub verify_checksums: bool,
+}
+
+/// Least recently used glyph outlines, keyed by glyph id. Glyphs without an outline are cached
+/// too, so they aren't looked up again.
+#[derive(Debug)]
+struct OutlineCache {
+    capacity: usize,
+    // Outline and the tick it was last used at
+    entries: HashMap<u16, (Option<Vec<Contour>>, u64)>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+/// How often a cache had what was asked of it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl OutlineCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn get(&mut self, glyph_id: u16) -> Option<Option<Vec<Contour>>> {
+        self.tick += 1;
+        match self.entries.get_mut(&glyph_id) {
+            Some((outline, last_used)) => {
+                *last_used = self.tick;
+                self.stats.hits += 1;
+                Some(outline.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, glyph_id: u16, outline: Option<Vec<Contour>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, &(_, last_used))| last_used)
+                .map(|(&glyph_id, _)| glyph_id);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(glyph_id, (outline, self.tick));
+    }
+}
+
 #[derive(Debug)]
 struct OffsetSubtable {
     scalar_type: u32,
@@ -30,6 +177,14 @@ struct TableDirectorySubtable {
     length: u32,
 }
 
+/// A font directory entry, as returned by `Font::table_info`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableInfo {
+    pub offset: u32,
+    pub length: u32,
+    pub checksum: u32,
+}
+
 #[derive(Debug)]
 struct FontDirectoryTable {
     offset_subtable: OffsetSubtable,
@@ -59,6 +214,55 @@ struct CmapFormatFourTable {
     id_delta: Vec<u16>,
     id_range_offset: Vec<u16>,
     glyph_id_array: Vec<u16>,
+    // Whether search_range, entry_selector and range_shift match seg_count_x2; real fonts
+    // often get them wrong, and lookups then fall back to a plain binary search
+    search_fields_valid: bool,
+}
+
+/// Glyphs for Unicode variation sequences: a base character followed by a variation selector.
+#[derive(Debug)]
+struct CmapFormatFourteenTable {
+    // Sorted by selector
+    selectors: Vec<VariationSelectorRecord>,
+}
+
+#[derive(Debug)]
+struct VariationSelectorRecord {
+    selector: u32,
+    // Sequences drawn with the base character's usual glyph, as the first base character and
+    // how many follow it
+    default_ranges: Vec<(u32, u8)>,
+    // Sequences with a glyph of their own, sorted by base character
+    mappings: Vec<(u32, u16)>,
+}
+
+/// What a format 14 subtable says about a variation sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VariationGlyph {
+    // Use the glyph the rest of the cmap gives the base character
+    Default,
+    Glyph(u16),
+}
+
+impl CmapFormatFourteenTable {
+    fn lookup(&self, base: u32, selector: u32) -> Option<VariationGlyph> {
+        let index = self
+            .selectors
+            .binary_search_by_key(&selector, |record| record.selector)
+            .ok()?;
+        let record = &self.selectors[index];
+        if let Ok(index) = record
+            .mappings
+            .binary_search_by_key(&base, |&(base, _)| base)
+        {
+            return Some(VariationGlyph::Glyph(record.mappings[index].1));
+        }
+        record
+            .default_ranges
+            .iter()
+            .any(|&(start, additional)| (start..=start + additional as u32).contains(&base))
+            .then_some(VariationGlyph::Default)
+    }
 }
 
 #[derive(Debug)]
@@ -68,13 +272,241 @@ struct CmapEncodingSubtable {
     offset: u32,
 }
 
+#[derive(Debug)]
+enum CmapSubtable {
+    FormatZero(CmapFormatZeroTable),
+    FormatFour(CmapFormatFourTable),
+    FormatFourteen(CmapFormatFourteenTable),
+    // Formats we can't decode yet are kept so the encoding records still line up
+    Unsupported(u16),
+}
+
+impl CmapSubtable {
+    fn format(&self) -> u16 {
+        match self {
+            CmapSubtable::FormatZero(_) => 0,
+            CmapSubtable::FormatFour(_) => 4,
+            CmapSubtable::FormatFourteen(_) => 14,
+            CmapSubtable::Unsupported(format) => *format,
+        }
+    }
+
+    /// Whether the subtable maps single characters, unlike format 14 which only covers
+    /// variation sequences.
+    fn is_character_map(&self) -> bool {
+        matches!(
+            self,
+            CmapSubtable::FormatZero(_) | CmapSubtable::FormatFour(_)
+        )
+    }
+
+    fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        match self {
+            CmapSubtable::FormatZero(table) => table.glyph_index(code_point),
+            CmapSubtable::FormatFour(table) => table.glyph_index(code_point),
+            CmapSubtable::FormatFourteen(_) | CmapSubtable::Unsupported(_) => None,
+        }
+    }
+}
+
+/// Identifies the cmap subtable used for character lookups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CmapEncoding {
+    pub platform_id: u16,
+    pub platform_specific_id: u16,
+    pub format: u16,
+}
+
+impl CmapEncoding {
+    pub fn platform_encoding(&self) -> CmapPlatformEncoding {
+        CmapPlatformEncoding::from_ids(self.platform_id, self.platform_specific_id)
+    }
+}
+
+/// What a cmap subtable's (platform ID, encoding ID) pair says its character codes are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmapPlatformEncoding {
+    // Unicode platform (0) limited to the Basic Multilingual Plane: encodings 0 to 3
+    UnicodeBmp,
+    // Unicode platform (0), encodings 4 and 6
+    UnicodeFull,
+    // Windows (3, 0). Symbol fonts map their glyphs into the private use area at U+F020.
+    WindowsSymbol,
+    // Windows (3, 1), the BMP
+    WindowsUcs2,
+    // Windows (3, 10), all of Unicode
+    WindowsUcs4,
+    // Macintosh (1, 0)
+    MacRoman,
+    Other {
+        platform_id: u16,
+        platform_specific_id: u16,
+    },
+}
+
+impl CmapPlatformEncoding {
+    pub fn from_ids(platform_id: u16, platform_specific_id: u16) -> Self {
+        match (platform_id, platform_specific_id) {
+            (0, 0..=3) => CmapPlatformEncoding::UnicodeBmp,
+            (0, 4 | 6) => CmapPlatformEncoding::UnicodeFull,
+            (3, 0) => CmapPlatformEncoding::WindowsSymbol,
+            (3, 1) => CmapPlatformEncoding::WindowsUcs2,
+            (3, 10) => CmapPlatformEncoding::WindowsUcs4,
+            (1, 0) => CmapPlatformEncoding::MacRoman,
+            _ => CmapPlatformEncoding::Other {
+                platform_id,
+                platform_specific_id,
+            },
+        }
+    }
+
+    /// How well subtables with this encoding serve Unicode lookups; lower is better. Subtables
+    /// covering all of Unicode beat BMP-only ones, and Windows beats the Unicode platform the
+    /// way browsers prefer. Symbol and Mac Roman codes only agree with Unicode for some
+    /// characters, so they come last.
+    fn unicode_rank(&self) -> u8 {
+        match self {
+            CmapPlatformEncoding::WindowsUcs4 => 0,
+            CmapPlatformEncoding::UnicodeFull => 1,
+            CmapPlatformEncoding::WindowsUcs2 => 2,
+            CmapPlatformEncoding::UnicodeBmp => 3,
+            CmapPlatformEncoding::WindowsSymbol => 4,
+            CmapPlatformEncoding::MacRoman => 5,
+            CmapPlatformEncoding::Other { .. } => 6,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CmapTable {
     version: u16,
     num_subtables: u16,
     encoding_subtables: Vec<CmapEncodingSubtable>,
-    format_zero_table: Option<CmapFormatZeroTable>,
-    format_four_table: Option<CmapFormatFourTable>,
+    // One per encoding subtable, in the same order
+    subtables: Vec<CmapSubtable>,
+    // Index of the subtable used for lookups
+    preferred: Option<usize>,
+}
+
+impl CmapTable {
+    /// The subtable lookups go through, or `None` if none can be decoded.
+    fn active_subtable(&self) -> Option<&CmapSubtable> {
+        self.subtables.get(self.preferred?)
+    }
+
+    fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        self.active_subtable()?.glyph_index(code_point)
+    }
+
+    fn variation_subtable(&self) -> Option<&CmapFormatFourteenTable> {
+        self.subtables.iter().find_map(|subtable| match subtable {
+            CmapSubtable::FormatFourteen(table) => Some(table),
+            _ => None,
+        })
+    }
+
+    fn encodings(&self) -> Vec<CmapEncoding> {
+        self.encoding_subtables
+            .iter()
+            .zip(&self.subtables)
+            .map(|(encoding, subtable)| CmapEncoding {
+                platform_id: encoding.platform_id,
+                platform_specific_id: encoding.platform_specific_id,
+                format: subtable.format(),
+            })
+            .collect()
+    }
+
+    fn preferred_encoding(&self) -> Option<CmapEncoding> {
+        let encoding = &self.encoding_subtables[self.preferred?];
+        Some(CmapEncoding {
+            platform_id: encoding.platform_id,
+            platform_specific_id: encoding.platform_specific_id,
+            format: self.active_subtable()?.format(),
+        })
+    }
+}
+
+/// Picks the subtable to look Unicode text up in, by `CmapPlatformEncoding::unicode_rank`
+/// among the subtables we can decode. Ties go to the first one listed.
+fn preferred_cmap_subtable(
+    encoding_subtables: &[CmapEncodingSubtable],
+    subtables: &[CmapSubtable],
+) -> Option<usize> {
+    (0..subtables.len())
+        .filter(|&index| subtables[index].is_character_map())
+        .min_by_key(|&index| {
+            let encoding = &encoding_subtables[index];
+            CmapPlatformEncoding::from_ids(encoding.platform_id, encoding.platform_specific_id)
+                .unicode_rank()
+        })
+}
+
+impl CmapFormatZeroTable {
+    fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        let glyph_id = *self.glyph_index_array.get(code_point as usize)?;
+        (glyph_id != 0).then_some(glyph_id as u16)
+    }
+}
+
+impl CmapFormatFourTable {
+    fn glyph_index(&self, code_point: u32) -> Option<u16> {
+        let code_point = u16::try_from(code_point).ok()?;
+        let segment = self.find_segment(code_point)?;
+        let start_code = self.start_code[segment];
+        if code_point < start_code {
+            return None;
+        }
+
+        let id_delta = self.id_delta[segment];
+        let id_range_offset = self.id_range_offset[segment];
+        let glyph_id = if id_range_offset == 0 {
+            code_point.wrapping_add(id_delta)
+        } else {
+            // id_range_offset is a byte offset from its own slot in the id_range_offset array,
+            // so rebase it onto glyph_id_array, which immediately follows that array.
+            let seg_count = self.end_code.len();
+            let index = (id_range_offset / 2) as usize + (code_point - start_code) as usize;
+            let index = index.checked_sub(seg_count - segment)?;
+            match *self.glyph_id_array.get(index)? {
+                0 => 0,
+                glyph_id => glyph_id.wrapping_add(id_delta),
+            }
+        };
+        (glyph_id != 0).then_some(glyph_id)
+    }
+
+    /// Finds the first segment whose end code is >= `code_point` using the binary search
+    /// parameters precomputed in the subtable header, if they are valid.
+    fn find_segment(&self, code_point: u16) -> Option<usize> {
+        if !self.search_fields_valid {
+            let index = self.end_code.partition_point(|&end| end < code_point);
+            return (index < self.end_code.len()).then_some(index);
+        }
+        let search_range = (self.search_range / 2) as usize;
+        let range_shift = (self.range_shift / 2) as usize;
+        if search_range == 0 {
+            return None;
+        }
+
+        let mut index = 0;
+        if code_point > *self.end_code.get(search_range - 1)? {
+            index = range_shift;
+        }
+        let mut step = search_range;
+        for _ in 0..self.entry_selector {
+            step /= 2;
+            if step == 0 {
+                break;
+            }
+            if code_point > *self.end_code.get(index + step - 1)? {
+                index += step;
+            }
+        }
+
+        let end_code = *self.end_code.get(index)?;
+        (code_point <= end_code).then_some(index)
+    }
 }
 
 #[derive(Debug)]
@@ -135,6 +567,256 @@ struct MaxpTable {
     max_component_depth: u16,
 }
 
+#[derive(Debug)]
+struct LongHorMetric {
+    advance_width: u16,
+    left_side_bearing: i16,
+}
+
+#[derive(Debug)]
+struct HmtxTable {
+    h_metrics: Vec<LongHorMetric>,
+    // Glyphs past the end of h_metrics share its last advance width and only store a bearing
+    left_side_bearings: Vec<i16>,
+}
+
+#[derive(Debug)]
+struct VheaTable {
+    version: u32,
+    vert_typo_ascender: i16,
+    vert_typo_descender: i16,
+    vert_typo_line_gap: i16,
+    advance_height_max: u16,
+    min_top_side_bearing: i16,
+    min_bottom_side_bearing: i16,
+    y_max_extent: i16,
+    caret_slope_rise: i16,
+    caret_slope_run: i16,
+    caret_offset: i16,
+    reserved: [i16; 4],
+    metric_data_format: i16,
+    num_of_long_ver_metrics: u16,
+}
+
+#[derive(Debug)]
+struct LongVerMetric {
+    advance_height: u16,
+    top_side_bearing: i16,
+}
+
+#[derive(Debug)]
+struct VmtxTable {
+    v_metrics: Vec<LongVerMetric>,
+    // Glyphs past the end of v_metrics share its last advance height and only store a bearing
+    top_side_bearings: Vec<i16>,
+}
+
+#[derive(Debug)]
+struct VorgTable {
+    default_vert_origin_y: i16,
+    // (glyph id, vertOriginY) for glyphs whose origin differs from the default, sorted by id
+    vert_origin_y_metrics: Vec<(u16, i16)>,
+}
+
+/// The parts of the OS/2 table we use.
+#[derive(Debug)]
+struct Os2Table {
+    // Heights of lowercase and capital letters, added in version 2
+    sx_height: Option<i16>,
+    s_cap_height: Option<i16>,
+}
+
+/// The fixed header of the post table; glyph names that follow it aren't parsed.
+#[derive(Debug)]
+struct PostTable {
+    version: u32,
+    italic_angle: u32,
+    // Top of the underline relative to the baseline, negative below it
+    underline_position: i16,
+    underline_thickness: i16,
+    is_fixed_pitch: u32,
+}
+
+#[derive(Debug)]
+struct MetaTable {
+    // ScriptLangTags from the dlng data map: what the font was designed for
+    design_languages: Vec<String>,
+    // ScriptLangTags from the slng data map: what the font can render
+    supported_languages: Vec<String>,
+}
+
+#[derive(Debug)]
+struct NameTable {
+    // Records whose encoding we can decode; the others are dropped
+    records: Vec<NameRecord>,
+}
+
+#[derive(Debug)]
+struct NameRecord {
+    platform_id: u16,
+    language_id: u16,
+    name_id: u16,
+    string: String,
+}
+
+#[derive(Debug)]
+struct GaspTable {
+    // (rangeMaxPPEM, rangeGaspBehavior), sorted by ppem; each covers sizes up to its max
+    ranges: Vec<(u16, u16)>,
+}
+
+/// How the font recommends rendering text at a given size, from the gasp table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaspFlags {
+    // Snap outlines to the pixel grid
+    pub gridfit: bool,
+    // Anti-alias with grayscale coverage
+    pub grayscale: bool,
+    // Grid-fit the way ClearType does (version 1 tables only)
+    pub symmetric_gridfit: bool,
+    // Anti-alias along both axes (version 1 tables only)
+    pub symmetric_smoothing: bool,
+}
+
+impl GaspFlags {
+    const GRIDFIT: u16 = 0x0001;
+    const DOGRAY: u16 = 0x0002;
+    const SYMMETRIC_GRIDFIT: u16 = 0x0004;
+    const SYMMETRIC_SMOOTHING: u16 = 0x0008;
+
+    // What to do when the font gives no advice: grid-fit and smooth at every size
+    const DEFAULT: GaspFlags = GaspFlags {
+        gridfit: true,
+        grayscale: true,
+        symmetric_gridfit: false,
+        symmetric_smoothing: false,
+    };
+
+    fn from_behavior(behavior: u16) -> Self {
+        Self {
+            gridfit: behavior & Self::GRIDFIT != 0,
+            grayscale: behavior & Self::DOGRAY != 0,
+            symmetric_gridfit: behavior & Self::SYMMETRIC_GRIDFIT != 0,
+            symmetric_smoothing: behavior & Self::SYMMETRIC_SMOOTHING != 0,
+        }
+    }
+}
+
+/// Placement of an underline or strikethrough in font units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecorationMetrics {
+    // Top edge of the line relative to the baseline, positive above it
+    pub position: i16,
+    pub thickness: i16,
+}
+
+/// Where each glyph's embedded bitmap sits in the EBDT or CBDT table, for every strike.
+#[derive(Debug)]
+struct BitmapLocationTable {
+    // CBLC/CBDT, whose glyphs are color PNG images, rather than EBLC/EBDT
+    color: bool,
+    strikes: Vec<BitmapStrike>,
+}
+
+/// The bitmaps drawn for one size.
+#[derive(Debug)]
+struct BitmapStrike {
+    ppem: u16,
+    // Bits per pixel of EBDT images: 1, 2, 4 or 8
+    bit_depth: u8,
+    ranges: Vec<BitmapGlyphRange>,
+}
+
+/// Glyphs described by one index subtable, which share an image format.
+#[derive(Debug)]
+struct BitmapGlyphRange {
+    image_format: u16,
+    // Metrics shared by every glyph, for index formats that store them in EBLC
+    metrics: Option<BitmapMetrics>,
+    // (glyph id, start, end) of each glyph's image in the data table, sorted by glyph id
+    glyphs: Vec<(u16, usize, usize)>,
+}
+
+/// Horizontal metrics of a bitmap glyph in pixels.
+#[derive(Debug, Clone, Copy)]
+struct BitmapMetrics {
+    height: u8,
+    width: u8,
+    // Left edge relative to the pen and top edge above the baseline
+    bearing_x: i8,
+    bearing_y: i8,
+}
+
+/// How a bitmap glyph's image is stored after its metrics.
+enum BitmapEncoding {
+    // Each row starts on a byte boundary
+    ByteAligned,
+    // Rows follow each other without padding
+    BitAligned,
+    // A length-prefixed PNG image, in CBDT
+    Png,
+}
+
+impl BitmapLocationTable {
+    /// The exact strike for `ppem`. Color fonts usually have a single large strike meant to be
+    /// scaled, so they fall back to the smallest strike above `ppem`, or the largest one.
+    fn strike(&self, ppem: u16) -> Option<&BitmapStrike> {
+        if let Some(strike) = self.strikes.iter().find(|strike| strike.ppem == ppem) {
+            return Some(strike);
+        }
+        if !self.color {
+            return None;
+        }
+        self.strikes
+            .iter()
+            .filter(|strike| strike.ppem > ppem)
+            .min_by_key(|strike| strike.ppem)
+            .or_else(|| self.strikes.iter().max_by_key(|strike| strike.ppem))
+    }
+}
+
+impl BitmapStrike {
+    fn find(&self, glyph_id: u16) -> Option<(&BitmapGlyphRange, usize, usize)> {
+        self.ranges.iter().find_map(|range| {
+            let index = range
+                .glyphs
+                .binary_search_by_key(&glyph_id, |&(glyph_id, _, _)| glyph_id)
+                .ok()?;
+            let (_, start, end) = range.glyphs[index];
+            Some((range, start, end))
+        })
+    }
+}
+
+/// An embedded bitmap glyph from the EBDT or CBDT table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapGlyph {
+    // Pixels per em of the strike the bitmap was drawn for, which may differ from the size asked
+    // for with color fonts
+    pub ppem: u16,
+    pub width: usize,
+    pub height: usize,
+    // Left edge relative to the pen and top edge above the baseline, in pixels at `ppem`
+    pub left: i32,
+    pub top: i32,
+    pub pixels: BitmapPixels,
+}
+
+/// Pixels of a `BitmapGlyph`, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitmapPixels {
+    // One byte per pixel, 255 where the glyph is fully inked
+    Coverage(Vec<u8>),
+    // Four bytes per pixel with straight alpha
+    Rgba(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct LocaTable {
+    // num_glyphs + 1 offsets into the glyf table; glyph i spans offsets[i]..offsets[i + 1]
+    offsets: Vec<u32>,
+}
+
 #[derive(Debug)]
 struct GlyfSubtable {
     number_of_contours: i16,
@@ -148,517 +830,4954 @@ struct GlyfSubtable {
     flags: Vec<u8>,
     x_coordinates: Vec<i16>,
     y_coordinates: Vec<i16>,
+    // Glyphs a composite glyph (number_of_contours < 0) is built from
+    components: Vec<GlyphComponent>,
 }
 
+/// One glyph placed inside a composite glyph.
 #[derive(Debug)]
-struct GlyfTable {
-    glyphs: Vec<GlyfSubtable>,
+struct GlyphComponent {
+    glyph_index: u16,
+    flags: u16,
+    // An x/y offset, or point numbers to align when ARGS_ARE_XY_VALUES is clear
+    arg1: i32,
+    arg2: i32,
+    // 2x2 matrix as [xscale, scale01, scale10, yscale]
+    transform: [f32; 4],
 }
 
-enum TableTag {
-    Dsig = 1146308935,
-    Gdef = 1195656518,
-    Gpos = 1196445523,
-    Gsub = 1196643650,
-    Jstf = 1246975046,
-    Ltsh = 1280594760,
-    Os2 = 1330851634,
-    Pclt = 1346587732,
-    Vdmx = 1447316824,
-    Cmap = 1668112752,
-    Cvt = 1668707360,
-    Fpgm = 1718642541,
-    Gasp = 1734439792,
-    Glyf = 1735162214,
-    Hdmx = 1751412088,
-    Head = 1751474532,
-    Hhea = 1751672161,
-    Hmtx = 1752003704,
-    Kern = 1801810542,
-    Loca = 1819239265,
-    Maxp = 1835104368,
-    Meta = 1835365473,
-    Name = 1851878757,
-    Post = 1886352244,
-    Prep = 1886545264,
-}
+impl GlyphComponent {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const USE_MY_METRICS: u16 = 0x0200;
+    const SCALED_COMPONENT_OFFSET: u16 = 0x0800;
+    const UNSCALED_COMPONENT_OFFSET: u16 = 0x1000;
 
-pub fn parse_from_file(filepath: &str) -> Result<Font, CapyError> {
-    let buffer = read_file_to_byte_buffer(filepath)?;
+    fn has_flag(&self, flag: u16) -> bool {
+        self.flags & flag != 0
+    }
 
-    let mut parser = ByteParser::new(&buffer);
+    fn apply_transform(&self, x: f32, y: f32) -> (f32, f32) {
+        let [xscale, scale01, scale10, yscale] = self.transform;
+        (xscale * x + scale10 * y, scale01 * x + yscale * y)
+    }
+}
 
-    let font_directory_table = parse_font_directory_table(&mut parser)?;
-    let cmap_table = parse_cmap_table(&mut parser, &font_directory_table)?;
-    let head_table = parse_head_table(&mut parser, &font_directory_table)?;
-    let hhea_table = parse_hhea_table(&mut parser, &font_directory_table)?;
-    let maxp_table = parse_maxp_table(&mut parser, &font_directory_table)?;
-    let glyf_table = parse_glyf_table(&mut parser, &font_directory_table, maxp_table.num_glyphs)?;
+impl GlyfSubtable {
+    fn empty() -> Self {
+        Self {
+            number_of_contours: 0,
+            x_min: 0,
+            y_min: 0,
+            x_max: 0,
+            y_max: 0,
+            end_pts_of_contours: Vec::new(),
+            instruction_length: 0,
+            instructions: Vec::new(),
+            flags: Vec::new(),
+            x_coordinates: Vec::new(),
+            y_coordinates: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+}
 
-    Ok(Font {
-        font_directory_table,
-        cmap_table,
-        head_table,
-        hhea_table,
-        maxp_table,
-        glyf_table,
-    })
+/// Glyph outlines are by far the largest part of a font, so each glyph is only parsed the first
+/// time it's needed. Loading a font to read its metrics never touches the outlines.
+#[derive(Debug)]
+struct GlyfTable {
+    // One slot per glyph, holding the error once parsed if the glyph's data is malformed
+    glyphs: Vec<OnceLock<Result<GlyfSubtable, CapyError>>>,
 }
 
-struct ByteParser<'a> {
-    buffer: &'a [u8],
-    offset: usize,
+/// A table that is parsed the first time it's used, so that loading a font to read a few
+/// tables, say to list family names, doesn't pay for the rest.
+#[derive(Debug)]
+struct LazyTable<T> {
+    // Parses the table, or returns `None` if the font doesn't have it
+    parse: fn(&Font) -> Result<Option<T>, CapyError>,
+    table: OnceLock<Result<Option<T>, CapyError>>,
 }
 
-impl<'a> ByteParser<'a> {
-    const U8_SIZE: usize = 1;
-    const U32_SIZE: usize = 4;
-    const U16_SIZE: usize = 2;
-    const I16_SIZE: usize = 2;
+impl<T> LazyTable<T> {
+    fn new(parse: fn(&Font) -> Result<Option<T>, CapyError>) -> Self {
+        Self {
+            parse,
+            table: OnceLock::new(),
+        }
+    }
 
-    fn new(buffer: &'a [u8]) -> Self {
-        Self { buffer, offset: 0 }
+    /// The table, parsing it on first use. A malformed table counts as missing.
+    fn get<'a>(&'a self, font: &Font) -> Option<&'a T> {
+        self.table
+            .get_or_init(|| (self.parse)(font))
+            .as_ref()
+            .ok()?
+            .as_ref()
     }
 
-    fn set_offset(&mut self, offset: usize) -> Result<(), CapyError> {
-        if offset > self.buffer.len() {
-            return Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "failed to slice buffer for tag",
-            ));
+    /// Parses the table if it hasn't been yet, returning the error if it is malformed.
+    fn validate(&self, font: &Font) -> Result<(), CapyError> {
+        if let Some(Ok(_)) = self.table.get() {
+            return Ok(());
         }
-
-        self.offset = offset;
+        // A stored error can't be handed out, so parse again for a fresh one
+        let table = (self.parse)(font)?;
+        let _ = self.table.set(Ok(table));
         Ok(())
     }
+}
 
-    fn read_u8_array_256(&mut self) -> Result<[u8; 256], CapyError> {
-        if self.offset + Self::U8_SIZE * 256 <= self.buffer.len() {
-            let bytes = &self.buffer[self.offset..self.offset + Self::U8_SIZE * 256];
-            self.offset += Self::U8_SIZE * 256;
-            Ok(bytes.try_into().unwrap())
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for u8 array",
-            ))
-        }
+#[derive(Debug)]
+struct KernTable {
+    // Horizontal kerning adjustments keyed by (left glyph, right glyph), in font units
+    pairs: HashMap<(u16, u16), i16>,
+    // Format 2 subtables, which are too large to expand into pairs
+    class_subtables: Vec<KernClassSubtable>,
+    // Coverage fields of the subtables that were skipped: vertical, minimum, cross-stream and
+    // variation kerning, or formats other than 0 and 2
+    skipped_subtables: Vec<u16>,
+}
+
+/// A format 2 kern subtable, which looks kerning values up in a two-dimensional array by the
+/// classes of the left and right glyphs.
+#[derive(Debug)]
+struct KernClassSubtable {
+    // Classes are byte offsets of a row, from the start of the subtable
+    left_classes: KernClassTable,
+    // Classes are byte offsets of a value within a row
+    right_classes: KernClassTable,
+    // Where the array starts, relative to the start of the subtable
+    array_offset: u16,
+    values: Vec<i16>,
+}
+
+#[derive(Debug)]
+struct KernClassTable {
+    first_glyph: u16,
+    classes: Vec<u16>,
+}
+
+#[derive(Debug)]
+struct GposTable {
+    // Pair adjustment subtables of the lookups used by the 'kern' feature, in lookup order
+    lookups: Vec<Vec<PairAdjustment>>,
+}
+
+#[derive(Debug)]
+struct RangeRecord {
+    start_glyph_id: u16,
+    end_glyph_id: u16,
+    value: u16,
+}
+
+impl RangeRecord {
+    fn contains(&self, glyph_id: u16) -> bool {
+        self.start_glyph_id <= glyph_id && glyph_id <= self.end_glyph_id
     }
+}
 
-    fn read_be_i16_array_4(&mut self) -> Result<[i16; 4], CapyError> {
-        if self.offset + Self::I16_SIZE * 4 <= self.buffer.len() {
-            let mut array = [0; 4];
-            for i in 0..4 {
-                let bytes = &self.buffer[self.offset..self.offset + Self::I16_SIZE];
-                self.offset += Self::I16_SIZE;
-                array[i] = i16::from_be_bytes(bytes.try_into().unwrap());
-            }
-            Ok(array)
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for i16 array",
-            ))
+#[derive(Debug)]
+enum Coverage {
+    Glyphs(Vec<u16>),
+    // value is the coverage index of start_glyph_id
+    Ranges(Vec<RangeRecord>),
+}
+
+#[derive(Debug)]
+enum ClassDef {
+    Glyphs {
+        start_glyph_id: u16,
+        classes: Vec<u16>,
+    },
+    // value is the class of every glyph in the range
+    Ranges(Vec<RangeRecord>),
+}
+
+#[derive(Debug)]
+enum PairAdjustment {
+    // Format 1: for each covered first glyph, its (second glyph, x advance) pairs sorted by glyph
+    Pairs {
+        coverage: Coverage,
+        pair_sets: Vec<Vec<(u16, i16)>>,
+    },
+    // Format 2: x advances indexed by class1 * class2_count + class2
+    Classes {
+        coverage: Coverage,
+        class_def1: ClassDef,
+        class_def2: ClassDef,
+        class2_count: u16,
+        x_advances: Vec<i16>,
+    },
+}
+
+impl Coverage {
+    fn index(&self, glyph_id: u16) -> Option<usize> {
+        match self {
+            Coverage::Glyphs(glyphs) => glyphs.binary_search(&glyph_id).ok(),
+            Coverage::Ranges(ranges) => ranges
+                .iter()
+                .find(|range| range.contains(glyph_id))
+                .map(|range| range.value as usize + (glyph_id - range.start_glyph_id) as usize),
         }
     }
 
-    fn read_be_u8(&mut self) -> Result<u8, CapyError> {
-        if self.offset + Self::U8_SIZE <= self.buffer.len() {
-            let byte = self.buffer[self.offset];
-            self.offset += Self::U8_SIZE;
-            Ok(byte)
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for u8",
-            ))
+    fn glyphs(&self) -> Vec<u16> {
+        match self {
+            Coverage::Glyphs(glyphs) => glyphs.clone(),
+            Coverage::Ranges(ranges) => ranges
+                .iter()
+                .flat_map(|range| range.start_glyph_id..=range.end_glyph_id)
+                .collect(),
         }
     }
+}
 
-    fn read_be_u32(&mut self) -> Result<u32, CapyError> {
-        if self.offset + Self::U32_SIZE <= self.buffer.len() {
-            let bytes = &self.buffer[self.offset..self.offset + Self::U32_SIZE];
-            self.offset += Self::U32_SIZE;
-            Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for u32",
-            ))
+impl ClassDef {
+    fn class(&self, glyph_id: u16) -> u16 {
+        // Glyphs not listed belong to class 0
+        match self {
+            ClassDef::Glyphs {
+                start_glyph_id,
+                classes,
+            } => glyph_id
+                .checked_sub(*start_glyph_id)
+                .and_then(|index| classes.get(index as usize))
+                .copied()
+                .unwrap_or(0),
+            ClassDef::Ranges(ranges) => ranges
+                .iter()
+                .find(|range| range.contains(glyph_id))
+                .map_or(0, |range| range.value),
         }
     }
+}
 
-    fn read_be_u16(&mut self) -> Result<u16, CapyError> {
-        if self.offset + Self::U16_SIZE <= self.buffer.len() {
-            let bytes = &self.buffer[self.offset..self.offset + Self::U16_SIZE];
-            self.offset += Self::U16_SIZE;
-            Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for u16",
-            ))
+impl PairAdjustment {
+    fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        match self {
+            PairAdjustment::Pairs {
+                coverage,
+                pair_sets,
+            } => {
+                let pair_set = pair_sets.get(coverage.index(left)?)?;
+                let index = pair_set
+                    .binary_search_by_key(&right, |&(second, _)| second)
+                    .ok()?;
+                Some(pair_set[index].1)
+            }
+            PairAdjustment::Classes {
+                coverage,
+                class_def1,
+                class_def2,
+                class2_count,
+                x_advances,
+            } => {
+                coverage.index(left)?;
+                let class1 = class_def1.class(left) as usize;
+                let class2 = class_def2.class(right) as usize;
+                if class2 >= *class2_count as usize {
+                    return None;
+                }
+                x_advances
+                    .get(class1 * *class2_count as usize + class2)
+                    .copied()
+            }
         }
     }
 
-    fn read_be_i16(&mut self) -> Result<i16, CapyError> {
-        if self.offset + Self::U16_SIZE <= self.buffer.len() {
-            let bytes = &self.buffer[self.offset..self.offset + Self::U16_SIZE];
-            self.offset += Self::U16_SIZE;
-            Ok(i16::from_be_bytes(bytes.try_into().unwrap()))
-        } else {
-            Err(CapyError::new(
-                ErrorCode::OutOfRange,
-                "Buffer too small for i16",
-            ))
+    /// Adds every pair this subtable gives a non-zero adjustment to `pairs`.
+    fn collect_pairs(&self, num_glyphs: u16, pairs: &mut BTreeSet<(u16, u16)>) {
+        match self {
+            PairAdjustment::Pairs {
+                coverage,
+                pair_sets,
+            } => {
+                for left in coverage.glyphs() {
+                    let Some(pair_set) =
+                        coverage.index(left).and_then(|index| pair_sets.get(index))
+                    else {
+                        continue;
+                    };
+                    pairs.extend(
+                        pair_set
+                            .iter()
+                            .filter(|&&(_, value)| value != 0)
+                            .map(|&(right, _)| (left, right)),
+                    );
+                }
+            }
+            PairAdjustment::Classes {
+                coverage,
+                class_def1,
+                class_def2,
+                class2_count,
+                x_advances,
+            } => {
+                // Any glyph can be in a second class, so sort them all into their classes once
+                let class2_count = *class2_count as usize;
+                let mut glyphs_by_class2 = vec![Vec::new(); class2_count];
+                for right in 0..num_glyphs {
+                    if let Some(glyphs) = glyphs_by_class2.get_mut(class_def2.class(right) as usize)
+                    {
+                        glyphs.push(right);
+                    }
+                }
+                for left in coverage.glyphs() {
+                    let class1 = class_def1.class(left) as usize;
+                    for (class2, rights) in glyphs_by_class2.iter().enumerate() {
+                        let value = x_advances.get(class1 * class2_count + class2);
+                        if value.is_some_and(|&value| value != 0) {
+                            pairs.extend(rights.iter().map(|&right| (left, right)));
+                        }
+                    }
+                }
+            }
         }
     }
 }
 
-fn parse_font_directory_table(parser: &mut ByteParser) -> Result<FontDirectoryTable, CapyError> {
-    let offset_subtable = parse_offset_table(parser)?;
-    let table_directory_subtables =
-        parse_table_directory_subtables(parser, offset_subtable.num_tables)?;
-    Ok(FontDirectoryTable {
-        offset_subtable,
-        table_directory_subtables,
-    })
+impl KernTable {
+    fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        // Every subtable that has a value for the pair adds to it
+        let mut kerning = self.pairs.get(&(left, right)).copied();
+        for subtable in &self.class_subtables {
+            if let Some(value) = subtable.kerning(left, right) {
+                kerning = Some(kerning.unwrap_or(0i16).saturating_add(value));
+            }
+        }
+        kerning
+    }
 }
 
-fn parse_offset_table(parser: &mut ByteParser) -> Result<OffsetSubtable, CapyError> {
-    Ok(OffsetSubtable {
-        scalar_type: parser.read_be_u32()?,
-        num_tables: parser.read_be_u16()?,
-        search_range: parser.read_be_u16()?,
-        entry_selector: parser.read_be_u16()?,
-        range_shift: parser.read_be_u16()?,
-    })
+impl KernClassSubtable {
+    fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        let offset =
+            self.left_classes.class(left) as usize + self.right_classes.class(right) as usize;
+        // Glyphs outside the class tables get class 0, which points before the array
+        let index = offset.checked_sub(self.array_offset as usize)? / 2;
+        self.values.get(index).copied()
+    }
+
+    /// Adds every pair this subtable gives a non-zero adjustment to `pairs`.
+    fn collect_pairs(&self, pairs: &mut BTreeSet<(u16, u16)>) {
+        for left in self.left_classes.glyphs() {
+            pairs.extend(
+                self.right_classes
+                    .glyphs()
+                    .filter(|&right| self.kerning(left, right).is_some_and(|value| value != 0))
+                    .map(|right| (left, right)),
+            );
+        }
+    }
 }
 
-fn parse_table_directory_subtables(
-    parser: &mut ByteParser,
-    num_tables: u16,
-) -> Result<Vec<TableDirectorySubtable>, CapyError> {
-    let mut subtables = Vec::new();
-    for _ in 0..num_tables {
-        subtables.push(TableDirectorySubtable {
-            tag: parser.read_be_u32()?,
-            check_sum: parser.read_be_u32()?,
-            offset: parser.read_be_u32()?,
-            length: parser.read_be_u32()?,
-        });
+impl KernClassTable {
+    fn class(&self, glyph_id: u16) -> u16 {
+        glyph_id
+            .checked_sub(self.first_glyph)
+            .and_then(|index| self.classes.get(index as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn glyphs(&self) -> impl Iterator<Item = u16> {
+        let end = self.first_glyph as usize + self.classes.len();
+        self.first_glyph..end.min(u16::MAX as usize) as u16
     }
-    Ok(subtables)
 }
 
-fn parse_cmap_table(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<CmapTable, CapyError> {
-    let cmap_offset = lookup_offset_for_tag(TableTag::Cmap, font_directory_table)?;
-    parser.set_offset(cmap_offset)?;
-    let version = parser.read_be_u16()?;
-    let num_subtables = parser.read_be_u16()?;
-    let encoding_subtables = parse_cmap_encoding_subtables(parser, num_subtables)?;
-    let mut format_zero_table = None;
-    let mut format_four_table = None;
-    for table in encoding_subtables.iter() {
-        parser.set_offset(cmap_offset + table.offset as usize)?;
-        let format = parser.read_be_u16()?;
-        match format {
-            0 => {
-                let tmp_table = parse_cmap_format_zero(parser, format)?;
-                format_zero_table = Some(tmp_table);
+impl GposTable {
+    fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        // Within a lookup the first matching subtable wins; separate lookups accumulate
+        let mut kerning = None;
+        for lookup in &self.lookups {
+            if let Some(value) = lookup
+                .iter()
+                .find_map(|subtable| subtable.kerning(left, right))
+            {
+                kerning = Some(kerning.unwrap_or(0i16).saturating_add(value));
             }
-            4 => {
-                let tmp_table = parse_cmap_format_four(parser, format)?;
-                format_four_table = Some(tmp_table);
-            }
-            _ => todo!("Implement other cmap formats"),
         }
+        kerning
     }
-    Ok(CmapTable {
-        version,
-        num_subtables,
-        encoding_subtables,
-        format_zero_table,
-        format_four_table,
-    })
 }
 
-fn parse_cmap_format_zero(
-    parser: &mut ByteParser,
-    format: u16,
-) -> Result<CmapFormatZeroTable, CapyError> {
-    Ok(CmapFormatZeroTable {
-        format,
-        length: parser.read_be_u16()?,
-        language: parser.read_be_u16()?,
-        glyph_index_array: parser.read_u8_array_256()?,
-    })
+/// A point of a glyph outline in font units (y-up).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+    pub on_curve: bool,
 }
 
-fn parse_cmap_format_four(
-    parser: &mut ByteParser,
-    format: u16,
-) -> Result<CmapFormatFourTable, CapyError> {
-    let seg_count_x2 = parser.read_be_u16()?;
-    let seg_count = seg_count_x2 / 2;
-    let search_range = parser.read_be_u16()?;
-    let entry_selector = parser.read_be_u16()?;
-    let range_shift = parser.read_be_u16()?;
-    let mut end_code = Vec::new();
-    for _ in 0..seg_count {
-        end_code.push(parser.read_be_u16()?);
-    }
-    let reserved_pad = parser.read_be_u16()?;
-    let mut start_code = Vec::new();
-    for _ in 0..seg_count {
-        start_code.push(parser.read_be_u16()?);
-    }
-    let mut id_delta = Vec::new();
-    for _ in 0..seg_count {
-        id_delta.push(parser.read_be_u16()?);
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    pub points: Vec<Point>,
+}
+
+impl Contour {
+    /// Twice the area enclosed by the contour's points, negative when they run clockwise.
+    fn signed_area(&self) -> i64 {
+        let points = &self.points;
+        (0..points.len())
+            .map(|i| {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64
+            })
+            .sum()
     }
-    let mut id_range_offset = Vec::new();
-    for _ in 0..seg_count {
-        id_range_offset.push(parser.read_be_u16()?);
+
+    /// Whether `point` lies inside the polygon through the contour's points, off-curve points
+    /// included, by the even-odd rule.
+    fn contains(&self, point: Point) -> bool {
+        let points = &self.points;
+        let mut inside = false;
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            if (a.y > point.y) == (b.y > point.y) {
+                continue;
+            }
+            // Where the edge crosses the horizontal line through `point`
+            let t = (point.y as f64 - a.y as f64) / (b.y as f64 - a.y as f64);
+            if (point.x as f64) < a.x as f64 + t * (b.x as f64 - a.x as f64) {
+                inside = !inside;
+            }
+        }
+        inside
     }
-    let mut glyph_id_array = Vec::new();
-    for _ in 0..seg_count {
-        glyph_id_array.push(parser.read_be_u16()?);
+
+    /// Whether `other` sits entirely inside this contour. Contours that merely overlap don't
+    /// count, so overlapping shapes aren't mistaken for holes.
+    fn encloses(&self, other: &Contour) -> bool {
+        !other.points.is_empty() && other.points.iter().all(|&point| self.contains(point))
     }
-    Ok(CmapFormatFourTable {
-        format,
-        length: parser.read_be_u16()?,
-        language: parser.read_be_u16()?,
-        seg_count_x2,
-        search_range,
-        entry_selector,
-        range_shift,
-        end_code,
-        reserved_pad,
-        start_code,
-        id_delta,
-        id_range_offset,
-        glyph_id_array,
-    })
 }
 
-fn parse_cmap_encoding_subtables(
-    parser: &mut ByteParser,
-    num_subtables: u16,
-) -> Result<Vec<CmapEncodingSubtable>, CapyError> {
-    let mut tables = Vec::new();
-    for _ in 0..num_subtables {
-        tables.push(CmapEncodingSubtable {
-            platform_id: parser.read_be_u16()?,
-            platform_specific_id: parser.read_be_u16()?,
-            offset: parser.read_be_u32()?,
-        });
+/// Reverses contours so that outer contours run clockwise and holes counter-clockwise, as
+/// TrueType expects. A contour's role comes from how many other contours enclose it: an even
+/// count makes it an outer contour, an odd count a hole. Without this, a font whose holes wind
+/// the same way as their outer contour has them filled in by the non-zero rule.
+pub fn normalize_winding(contours: &mut [Contour]) {
+    let depths: Vec<usize> = contours
+        .iter()
+        .enumerate()
+        .map(|(i, contour)| {
+            contours
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| j != i && other.encloses(contour))
+                .count()
+        })
+        .collect();
+    for (contour, depth) in contours.iter_mut().zip(depths) {
+        let area = contour.signed_area();
+        let is_hole = depth % 2 == 1;
+        if area != 0 && (area > 0) != is_hole {
+            contour.points.reverse();
+        }
     }
-    Ok(tables)
 }
 
-fn parse_head_table(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<HeadTable, CapyError> {
-    let head_offset = lookup_offset_for_tag(TableTag::Head, font_directory_table)?;
-    parser.set_offset(head_offset)?;
-    Ok(HeadTable {
-        version: parser.read_be_u32()?,
-        font_revision: parser.read_be_u32()?,
-        check_sum_adjustment: parser.read_be_u32()?,
-        magic_number: parser.read_be_u32()?,
-        flags: parser.read_be_u16()?,
-        units_per_em: parser.read_be_u16()?,
-        created: parser.read_be_u32()?,
-        modified: parser.read_be_u32()?,
-        x_min: parser.read_be_i16()?,
-        y_min: parser.read_be_i16()?,
-        x_max: parser.read_be_i16()?,
-        y_max: parser.read_be_i16()?,
-        mac_style: parser.read_be_u16()?,
-        lowest_rec_ppem: parser.read_be_u16()?,
-        font_direction_hint: parser.read_be_i16()?,
-        index_to_loc_format: parser.read_be_i16()?,
-        glyph_data_format: parser.read_be_i16()?,
-    })
+/// A point of a glyph outline scaled to pixels, relative to the pen position on the baseline
+/// (y-down, so points above the baseline have negative y).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointF32 {
+    pub x: f32,
+    pub y: f32,
+    pub on_curve: bool,
 }
 
-fn parse_hhea_table(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<HheaTable, CapyError> {
-    let hhea_offset = lookup_offset_for_tag(TableTag::Hhea, font_directory_table)?;
-    parser.set_offset(hhea_offset)?;
-    Ok(HheaTable {
-        version: parser.read_be_u32()?,
-        ascent: parser.read_be_i16()?,
-        descent: parser.read_be_i16()?,
-        line_gap: parser.read_be_i16()?,
-        advance_width_max: parser.read_be_u16()?,
-        min_left_side_bearing: parser.read_be_i16()?,
-        min_right_side_bearing: parser.read_be_i16()?,
-        x_max_extent: parser.read_be_i16()?,
-        caret_slope_rise: parser.read_be_i16()?,
-        caret_slope_run: parser.read_be_i16()?,
-        caret_offset: parser.read_be_i16()?,
-        reserved: parser.read_be_i16_array_4()?,
-        metric_data_format: parser.read_be_i16()?,
-        number_of_hmetrics: parser.read_be_u16()?,
-    })
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourF32 {
+    pub points: Vec<PointF32>,
 }
 
-fn parse_maxp_table(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<MaxpTable, CapyError> {
-    let maxp_offset = lookup_offset_for_tag(TableTag::Maxp, font_directory_table)?;
-    parser.set_offset(maxp_offset)?;
-    Ok(MaxpTable {
-        version: parser.read_be_u32()?,
-        num_glyphs: parser.read_be_u16()?,
-        max_points: parser.read_be_u16()?,
-        max_contours: parser.read_be_u16()?,
-        max_composite_points: parser.read_be_u16()?,
-        max_composite_contours: parser.read_be_u16()?,
-        max_zones: parser.read_be_u16()?,
-        max_twilight_points: parser.read_be_u16()?,
-        max_storage: parser.read_be_u16()?,
-        max_function_defs: parser.read_be_u16()?,
-        max_instruction_defs: parser.read_be_u16()?,
-        max_stack_elements: parser.read_be_u16()?,
-        max_size_of_instructions: parser.read_be_u16()?,
-        max_component_elements: parser.read_be_u16()?,
-        max_component_depth: parser.read_be_u16()?,
-    })
+#[derive(Clone, Copy)]
+enum TableTag {
+    Cbdt = 1128416340,
+    Cblc = 1128418371,
+    Dsig = 1146308935,
+    Ebdt = 1161970772,
+    Eblc = 1161972803,
+    Gdef = 1195656518,
+    Gpos = 1196445523,
+    Gsub = 1196643650,
+    Jstf = 1246975046,
+    Ltsh = 1280594760,
+    Os2 = 1330851634,
+    Pclt = 1346587732,
+    Vdmx = 1447316824,
+    Vorg = 1448038983,
+    Cmap = 1668112752,
+    Cvt = 1668707360,
+    Fpgm = 1718642541,
+    Gasp = 1734439792,
+    Glyf = 1735162214,
+    Hdmx = 1751412088,
+    Head = 1751474532,
+    Hhea = 1751672161,
+    Hmtx = 1752003704,
+    Kern = 1801810542,
+    Loca = 1819239265,
+    Maxp = 1835104368,
+    Meta = 1835365473,
+    Name = 1851878757,
+    Post = 1886352244,
+    Prep = 1886545264,
+    Vhea = 1986553185,
+    Vmtx = 1986884728,
 }
 
-fn parse_glyf_table(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-    num_glyphs: u16,
-) -> Result<GlyfTable, CapyError> {
-    let mut glyphs = Vec::new();
-    for _ in 0..num_glyphs {
-        let glyph = parse_glyph_subtable(parser, font_directory_table)?;
-        if glyph.number_of_contours < 0 {
-            println!("Reached compound glyph");
-            break;
+// Tables read by `Font::from_reader`; everything else is left on disk
+const PARSED_TABLES: [TableTag; 22] = [
+    TableTag::Cmap,
+    TableTag::Head,
+    TableTag::Hhea,
+    TableTag::Maxp,
+    TableTag::Hmtx,
+    TableTag::Loca,
+    TableTag::Glyf,
+    TableTag::Kern,
+    TableTag::Gpos,
+    TableTag::Vhea,
+    TableTag::Vmtx,
+    TableTag::Post,
+    TableTag::Meta,
+    TableTag::Gasp,
+    TableTag::Dsig,
+    TableTag::Eblc,
+    TableTag::Ebdt,
+    TableTag::Cblc,
+    TableTag::Cbdt,
+    TableTag::Name,
+    TableTag::Vorg,
+    TableTag::Os2,
+];
+
+// name IDs
+const NAME_FAMILY: u16 = 1;
+
+// name platform IDs
+const PLATFORM_UNICODE: u16 = 0;
+const PLATFORM_MACINTOSH: u16 = 1;
+const PLATFORM_WINDOWS: u16 = 3;
+
+// Mac Roman code points 0x80 to 0xFF; the lower half is ASCII
+const MAC_ROMAN: [char; 128] = [
+    '\u{00C4}', '\u{00C5}', '\u{00C7}', '\u{00C9}', '\u{00D1}', '\u{00D6}', '\u{00DC}', '\u{00E1}',
+    '\u{00E0}', '\u{00E2}', '\u{00E4}', '\u{00E3}', '\u{00E5}', '\u{00E7}', '\u{00E9}', '\u{00E8}',
+    '\u{00EA}', '\u{00EB}', '\u{00ED}', '\u{00EC}', '\u{00EE}', '\u{00EF}', '\u{00F1}', '\u{00F3}',
+    '\u{00F2}', '\u{00F4}', '\u{00F6}', '\u{00F5}', '\u{00FA}', '\u{00F9}', '\u{00FB}', '\u{00FC}',
+    '\u{2020}', '\u{00B0}', '\u{00A2}', '\u{00A3}', '\u{00A7}', '\u{2022}', '\u{00B6}', '\u{00DF}',
+    '\u{00AE}', '\u{00A9}', '\u{2122}', '\u{00B4}', '\u{00A8}', '\u{2260}', '\u{00C6}', '\u{00D8}',
+    '\u{221E}', '\u{00B1}', '\u{2264}', '\u{2265}', '\u{00A5}', '\u{00B5}', '\u{2202}', '\u{2211}',
+    '\u{220F}', '\u{03C0}', '\u{222B}', '\u{00AA}', '\u{00BA}', '\u{03A9}', '\u{00E6}', '\u{00F8}',
+    '\u{00BF}', '\u{00A1}', '\u{00AC}', '\u{221A}', '\u{0192}', '\u{2248}', '\u{2206}', '\u{00AB}',
+    '\u{00BB}', '\u{2026}', '\u{00A0}', '\u{00C0}', '\u{00C3}', '\u{00D5}', '\u{0152}', '\u{0153}',
+    '\u{2013}', '\u{2014}', '\u{201C}', '\u{201D}', '\u{2018}', '\u{2019}', '\u{00F7}', '\u{25CA}',
+    '\u{00FF}', '\u{0178}', '\u{2044}', '\u{20AC}', '\u{2039}', '\u{203A}', '\u{FB01}', '\u{FB02}',
+    '\u{2021}', '\u{00B7}', '\u{201A}', '\u{201E}', '\u{2030}', '\u{00C2}', '\u{00CA}', '\u{00C1}',
+    '\u{00CB}', '\u{00C8}', '\u{00CD}', '\u{00CE}', '\u{00CF}', '\u{00CC}', '\u{00D3}', '\u{00D4}',
+    '\u{F8FF}', '\u{00D2}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{0131}', '\u{02C6}', '\u{02DC}',
+    '\u{00AF}', '\u{02D8}', '\u{02D9}', '\u{02DA}', '\u{00B8}', '\u{02DD}', '\u{02DB}', '\u{02C7}',
+];
+
+// Sizes of the offset subtable that starts a font file, and of each table directory entry
+// following it
+const OFFSET_SUBTABLE_SIZE: usize = 12;
+const TABLE_DIRECTORY_ENTRY_SIZE: usize = 16;
+
+// Enough for the glyphs of a typical page in a Latin script
+const DEFAULT_OUTLINE_CACHE_CAPACITY: usize = 512;
+
+// Deepest nesting of composite glyphs we follow
+const MAX_COMPONENT_DEPTH: usize = 16;
+
+// Fixed value of head.magicNumber
+const HEAD_MAGIC_NUMBER: u32 = 0x5F0F_3CF5;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+pub fn parse_from_file(filepath: &str, options: ParseOptions) -> Result<Font, CapyError> {
+    parse_from_bytes(read_file_to_byte_buffer(filepath)?, options)
+}
+
+/// Parses a font file held in memory, checking it as thoroughly as `options` asks.
+pub fn parse_from_bytes(buffer: Vec<u8>, options: ParseOptions) -> Result<Font, CapyError> {
+    let mut parser = ByteParser::new(&buffer);
+    let font_directory_table = parse_font_directory_table(&mut parser, buffer.len())?;
+    // Every table parser seeks within the whole file
+    let mut font = parse_tables(font_directory_table, options, |_| {
+        Ok(ByteParser::new(&buffer))
+    })?;
+    font.data = FontData::File(buffer);
+    font.check(options)?;
+    Ok(font)
+}
+
+/// Like `parse_from_file`, but maps the file into memory so only the pages parsing touches are
+/// ever read.
+#[cfg(feature = "mmap")]
+pub fn parse_from_mmap(filepath: &str, options: ParseOptions) -> Result<Font, CapyError> {
+    let file = std::fs::File::open(filepath)?;
+    // SAFETY: The mapping is read-only. Modifying or truncating the font file while it is in
+    // use is undefined behavior, which we accept just like every other mmap-based font loader.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+    let mut parser = ByteParser::new(&mmap);
+    let font_directory_table = parse_font_directory_table(&mut parser, mmap.len())?;
+    let mut font = parse_tables(font_directory_table, options, |_| {
+        Ok(ByteParser::new(&mmap))
+    })?;
+    font.data = FontData::Mapped(mmap);
+    font.check(options)?;
+    Ok(font)
+}
+
+impl Font {
+    /// Parses a font by seeking to each table it needs instead of reading the whole file, which
+    /// keeps memory down for large fonts. `raw_table` only returns the tables that were parsed.
+    pub fn from_reader<R: Read + Seek>(
+        mut reader: R,
+        options: ParseOptions,
+    ) -> Result<Font, CapyError> {
+        let file_length = reader.seek(SeekFrom::End(0))? as usize;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut directory = vec![0; OFFSET_SUBTABLE_SIZE.min(file_length)];
+        reader.read_exact(&mut directory)?;
+        // Rejects non-font data before sizing the directory by its table count
+        let num_tables =
+            parse_offset_table(&mut ByteParser::new(&directory), file_length)?.num_tables as usize;
+        directory.resize(
+            OFFSET_SUBTABLE_SIZE + num_tables * TABLE_DIRECTORY_ENTRY_SIZE,
+            0,
+        );
+        reader.read_exact(&mut directory[OFFSET_SUBTABLE_SIZE..])?;
+        let font_directory_table =
+            parse_font_directory_table(&mut ByteParser::new(&directory), file_length)?;
+
+        let mut tables = HashMap::new();
+        for table_dir in &font_directory_table.table_directory_subtables {
+            if !PARSED_TABLES.iter().any(|&tag| tag as u32 == table_dir.tag) {
+                continue;
+            }
+            let mut table = vec![0; table_dir.length as usize];
+            reader.seek(SeekFrom::Start(table_dir.offset as u64))?;
+            reader.read_exact(&mut table)?;
+            tables.insert(table_dir.tag, table);
         }
-        glyphs.push(glyph);
+
+        let table_offsets: HashMap<u32, usize> = font_directory_table
+            .table_directory_subtables
+            .iter()
+            .map(|dir| (dir.tag, dir.offset as usize))
+            .collect();
+        let mut font = parse_tables(font_directory_table, options, |tag| {
+            let tag = tag as u32;
+            match (tables.get(&tag), table_offsets.get(&tag)) {
+                (Some(table), Some(&offset)) => Ok(ByteParser::with_base(table, offset)),
+                _ => Err(CapyError::new(
+                    ErrorCode::NotFound,
+                    "table not found in FontDirectoryTable",
+                )),
+            }
+        })?;
+        font.data = FontData::Tables(tables);
+        font.check(options)?;
+        Ok(font)
     }
-    Ok(GlyfTable { glyphs })
 }
 
-fn parse_glyph_subtable(
-    parser: &mut ByteParser,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<GlyfSubtable, CapyError> {
-    let glyf_offset = lookup_offset_for_tag(TableTag::Glyf, font_directory_table)?;
-    parser.set_offset(glyf_offset)?;
+/// Parses every supported table, getting a parser positioned anywhere over each table's bytes
+/// from `table_parser`. The returned font has no data yet; the caller fills it in.
+fn parse_tables<'a>(
+    font_directory_table: FontDirectoryTable,
+    options: ParseOptions,
+    table_parser: impl Fn(TableTag) -> Result<ByteParser<'a>, CapyError>,
+) -> Result<Font, CapyError> {
+    let fdt = &font_directory_table;
+    let cmap_table = parse_cmap_table(&mut table_parser(TableTag::Cmap)?, fdt)?;
+    let head_table = parse_head_table(&mut table_parser(TableTag::Head)?, fdt)?;
+    let hhea_table = parse_hhea_table(&mut table_parser(TableTag::Hhea)?, fdt)?;
+    let maxp_table = parse_maxp_table(&mut table_parser(TableTag::Maxp)?, fdt)?;
+    let hmtx_table = parse_hmtx_table(
+        &mut table_parser(TableTag::Hmtx)?,
+        fdt,
+        hhea_table.number_of_hmetrics,
+        maxp_table.num_glyphs,
+        options,
+    )?;
+    // Prefer color bitmaps, as renderers generally do
+    let bitmap_location_table = match [TableTag::Cblc, TableTag::Eblc]
+        .into_iter()
+        .find(|&tag| has_table(tag, fdt))
+    {
+        Some(tag) => Some(parse_bitmap_location_table(
+            &mut table_parser(tag)?,
+            fdt,
+            matches!(tag, TableTag::Cblc),
+        )?),
+        None => None,
+    };
+    // Fonts with only embedded bitmaps, such as color emoji fonts, have no outlines at all
+    let has_outlines = has_table(TableTag::Loca, fdt) || has_table(TableTag::Glyf, fdt);
+    let (loca_table, glyf_table) = match (has_outlines, &bitmap_location_table) {
+        (false, Some(_)) => {
+            let loca_table = LocaTable {
+                offsets: vec![0; maxp_table.num_glyphs as usize + 1],
+            };
+            let glyf_table = GlyfTable {
+                glyphs: (0..maxp_table.num_glyphs)
+                    .map(|_| OnceLock::new())
+                    .collect(),
+            };
+            (loca_table, glyf_table)
+        }
+        _ => {
+            let loca_table = parse_loca_table(
+                &mut table_parser(TableTag::Loca)?,
+                fdt,
+                &head_table,
+                maxp_table.num_glyphs,
+            )?;
+            let glyf_table = parse_glyf_table(fdt, &loca_table)?;
+            (loca_table, glyf_table)
+        }
+    };
+    Ok(Font {
+        data: FontData::Tables(HashMap::new()),
+        font_directory_table,
+        cmap_table,
+        head_table,
+        hhea_table,
+        maxp_table,
+        hmtx_table,
+        loca_table,
+        glyf_table,
+        kern_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Kern, |parser, fdt| {
+                parse_kern_table(parser, fdt, font.options)
+            })
+        }),
+        gpos_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Gpos, parse_gpos_table)
+        }),
+        // Vertical metrics are optional and mostly found in CJK fonts
+        vmtx_table: LazyTable::new(|font| {
+            let Some(vhea_table) = font.parse_optional_table(TableTag::Vhea, parse_vhea_table)?
+            else {
+                return Ok(None);
+            };
+            font.parse_optional_table(TableTag::Vmtx, |parser, fdt| {
+                parse_vmtx_table(
+                    parser,
+                    fdt,
+                    vhea_table.num_of_long_ver_metrics,
+                    font.maxp_table.num_glyphs,
+                )
+            })
+        }),
+        vorg_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Vorg, parse_vorg_table)
+        }),
+        os2_table: LazyTable::new(|font| font.parse_optional_table(TableTag::Os2, parse_os2_table)),
+        post_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Post, parse_post_table)
+        }),
+        meta_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Meta, parse_meta_table)
+        }),
+        gasp_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Gasp, parse_gasp_table)
+        }),
+        bitmap_location_table,
+        name_table: LazyTable::new(|font| {
+            font.parse_optional_table(TableTag::Name, parse_name_table)
+        }),
+        options,
+        outline_cache: Mutex::new(OutlineCache::new(DEFAULT_OUTLINE_CACHE_CAPACITY)),
+    })
+}
 
-    let number_of_contours = parser.read_be_i16()?;
-    let x_min = parser.read_be_i16()?;
-    let y_min = parser.read_be_i16()?;
-    let x_max = parser.read_be_i16()?;
-    let y_max = parser.read_be_i16()?;
+impl Font {
+    /// Maps a character to its glyph id through the cmap table, or `None` if the font lacks it.
+    pub fn glyph_index(&self, char: char) -> Option<u16> {
+        self.cmap_table.glyph_index(char as u32)
+    }
 
-    let mut end_pts_of_contours = Vec::new();
-    for i in 0..number_of_contours {
-        end_pts_of_contours.push(parser.read_be_u16()?);
+    pub fn sfnt_version(&self) -> SfntVersion {
+        let scalar_type = self.font_directory_table.offset_subtable.scalar_type;
+        // Only TrueType flavors make it through parsing
+        SfntVersion::from_scalar_type(scalar_type).unwrap_or(SfntVersion::TrueType)
     }
 
-    let instruction_length = parser.read_be_u16()?;
-    let mut instructions = Vec::new();
-    for _ in 0..instruction_length {
-        instructions.push(parser.read_be_u8()?);
+    /// The glyph for `base` followed by the variation selector `selector` (U+FE00 to U+FE0F, or
+    /// U+E0100 to U+E01EF), such as the text or emoji presentation of a symbol. Returns `None`
+    /// if the font doesn't support that sequence.
+    pub fn glyph_for_variation(&self, base: char, selector: char) -> Option<u16> {
+        let table = self.cmap_table.variation_subtable()?;
+        match table.lookup(base as u32, selector as u32)? {
+            VariationGlyph::Default => self.glyph_index(base),
+            VariationGlyph::Glyph(glyph_id) => Some(glyph_id),
+        }
     }
 
-    let num_points = end_pts_of_contours[number_of_contours as usize - 1] + 1;
-    let mut flags = Vec::new();
-    let mut i = 0;
-    while i < num_points {
-        let flag = parser.read_be_u8()?;
-        flags.push(flag);
-        if flag & 0x08 != 0 {
-            let repeat_count = parser.read_be_u8()?;
-            for _ in 0..repeat_count {
-                flags.push(flag);
-                i += 1;
+    /// The cmap subtable `glyph_index` uses, or `None` if the font has none we can decode.
+    pub fn cmap_encoding(&self) -> Option<CmapEncoding> {
+        self.cmap_table.preferred_encoding()
+    }
+
+    /// Every cmap subtable in the order the font lists them, including ones we can't decode.
+    pub fn cmap_encodings(&self) -> Vec<CmapEncoding> {
+        self.cmap_table.encodings()
+    }
+
+    /// The characters `glyph_index` maps to a glyph, as sorted inclusive ranges. Only the
+    /// Basic Multilingual Plane is checked, since the subtable formats we decode can't reach
+    /// beyond it.
+    pub fn coverage(&self) -> Vec<(char, char)> {
+        let mut ranges: Vec<(char, char)> = Vec::new();
+        for char in (0..=0xFFFF).filter_map(char::from_u32) {
+            if self.glyph_index(char).is_none() {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if *end as u32 + 1 == char as u32 => *end = char,
+                _ => ranges.push((char, char)),
             }
         }
-        i += 1;
+        ranges
     }
 
-    let mut x_coordinates = Vec::new();
-    let mut y_coordinates = Vec::new();
-    let mut x = 0;
-    let mut y = 0;
+    /// A human-readable report on the font for debugging: metrics, tables, cmap subtables,
+    /// kerning sources and which characters are covered.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        if let Some(family_name) = self.family_name() {
+            summary.push_str(&format!("Family: {}\n", family_name));
+        }
+        summary.push_str(&format!(
+            "{:?} font, {} glyphs, {} units per em\n",
+            self.sfnt_version(),
+            self.num_glyphs(),
+            self.units_per_em()
+        ));
+        summary.push_str(&format!(
+            "Ascent {}, descent {}, line gap {}\n",
+            self.ascent(),
+            self.descent(),
+            self.line_gap()
+        ));
+        let tags = self.table_tags();
+        let tag_names: Vec<String> = tags
+            .iter()
+            .map(|tag| String::from_utf8_lossy(tag).trim_end().to_string())
+            .collect();
+        summary.push_str(&format!("Tables: {}\n", tag_names.join(" ")));
 
-    for flag in &flags {
-        if flag & 0x02 != 0 {
-            let dx = parser.read_be_u8()?;
-            x += if flag & 0x10 != 0 {
-                dx as i16
-            } else {
-                -(dx as i16)
+        let active = self.cmap_encoding();
+        let encodings: Vec<String> = self
+            .cmap_encodings()
+            .into_iter()
+            .map(|encoding| {
+                let marker = if Some(encoding) == active {
+                    " (active)"
+                } else {
+                    ""
+                };
+                format!(
+                    "({}, {}) format {}{}",
+                    encoding.platform_id, encoding.platform_specific_id, encoding.format, marker
+                )
+            })
+            .collect();
+        summary.push_str(&format!("Cmap subtables: {}\n", encodings.join(", ")));
+
+        let mut kerning_sources: Vec<String> = [(b"GPOS", "GPOS"), (b"kern", "kern table")]
+            .into_iter()
+            .filter(|(tag, _)| tags.contains(tag))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        // Name the kern subtables that don't contribute, by their coverage field
+        if let Some(kern_table) = self.kern_table.get(self) {
+            if !kern_table.skipped_subtables.is_empty() {
+                let coverages: Vec<String> = kern_table
+                    .skipped_subtables
+                    .iter()
+                    .map(|coverage| format!("0x{:04X}", coverage))
+                    .collect();
+                if let Some(kern) = kerning_sources.last_mut() {
+                    kern.push_str(&format!(
+                        " (skipped subtables with coverage {})",
+                        coverages.join(", ")
+                    ));
+                }
+            }
+        }
+        summary.push_str(&format!(
+            "Kerning: {}\n",
+            match kerning_sources.is_empty() {
+                true => String::from("none"),
+                false => kerning_sources.join(", "),
+            }
+        ));
+
+        let coverage = self.coverage();
+        let char_count: u32 = coverage
+            .iter()
+            .map(|&(start, end)| end as u32 - start as u32 + 1)
+            .sum();
+        summary.push_str(&format!(
+            "Coverage: {} characters in {} ranges\n",
+            char_count,
+            coverage.len()
+        ));
+        let ranges: Vec<String> = coverage
+            .iter()
+            .map(|&(start, end)| match start == end {
+                true => format!("U+{:04X}", start as u32),
+                false => format!("U+{:04X}-U+{:04X}", start as u32, end as u32),
+            })
+            .collect();
+        for line in ranges.chunks(6) {
+            summary.push_str(&format!("  {}\n", line.join(", ")));
+        }
+        summary
+    }
+
+    /// Tags of every table in the font directory, in directory order.
+    pub fn table_tags(&self) -> Vec<[u8; 4]> {
+        self.font_directory_table
+            .table_directory_subtables
+            .iter()
+            .map(|dir| dir.tag.to_be_bytes())
+            .collect()
+    }
+
+    /// Where a table sits in the font file and its checksum, as listed in the font directory.
+    pub fn table_info(&self, tag: [u8; 4]) -> Option<TableInfo> {
+        let tag = u32::from_be_bytes(tag);
+        self.font_directory_table
+            .table_directory_subtables
+            .iter()
+            .find(|dir| dir.tag == tag)
+            .map(|dir| TableInfo {
+                offset: dir.offset,
+                length: dir.length,
+                checksum: dir.check_sum,
+            })
+    }
+
+    /// Returns the bytes of any table in the font directory, parsed or not.
+    pub fn raw_table(&self, tag: [u8; 4]) -> Option<&[u8]> {
+        let tag = u32::from_be_bytes(tag);
+        let buffer: &[u8] = match &self.data {
+            FontData::File(buffer) => buffer,
+            #[cfg(feature = "mmap")]
+            FontData::Mapped(mmap) => mmap,
+            FontData::Tables(tables) => return tables.get(&tag).map(Vec::as_slice),
+        };
+        let table_dir = self
+            .font_directory_table
+            .table_directory_subtables
+            .iter()
+            .find(|dir| dir.tag == tag)?;
+        let start = table_dir.offset as usize;
+        let end = start.checked_add(table_dir.length as usize)?;
+        buffer.get(start..end)
+    }
+
+    /// Runs the whole-font checks `options` asks for, once every table is parsed.
+    fn check(&self, options: ParseOptions) -> Result<(), CapyError> {
+        if options.strict {
+            self.check_conformance()?;
+            self.validate()?;
+        }
+        if options.verify_checksums {
+            self.verify_checksums()?;
+        }
+        Ok(())
+    }
+
+    /// Checks the spec rules `ParseOptions::strict` enforces.
+    fn check_conformance(&self) -> Result<(), CapyError> {
+        let offset_subtable = &self.font_directory_table.offset_subtable;
+        let num_tables = offset_subtable.num_tables;
+        // The binary search fields are derived from the largest power of two <= num_tables
+        let entry_selector = num_tables.max(1).ilog2() as u16;
+        let search_range = (1u32 << entry_selector) * 16;
+        let range_shift = (num_tables as u32 * 16).saturating_sub(search_range);
+        if offset_subtable.search_range as u32 != search_range
+            || offset_subtable.entry_selector != entry_selector
+            || offset_subtable.range_shift as u32 != range_shift
+        {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "font directory search fields don't match the number of tables",
+            ));
+        }
+
+        let directory = &self.font_directory_table.table_directory_subtables;
+        if directory.windows(2).any(|pair| pair[0].tag >= pair[1].tag) {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "font directory tables are not sorted by tag",
+            ));
+        }
+        if directory.iter().any(|dir| dir.offset % 4 != 0) {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "font table is not aligned to 4 bytes",
+            ));
+        }
+        let invalid_format_4_search = self.cmap_table.subtables.iter().any(|subtable| {
+            matches!(subtable, CmapSubtable::FormatFour(table) if !table.search_fields_valid)
+        });
+        if invalid_format_4_search {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "cmap format 4 search fields don't match the segment count",
+            ));
+        }
+        if self.head_table.magic_number != HEAD_MAGIC_NUMBER {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "head table has the wrong magic number",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compares each table's checksum with the font directory. Tables whose bytes weren't kept
+    /// (see `from_reader`) are skipped.
+    fn verify_checksums(&self) -> Result<(), CapyError> {
+        for dir in &self.font_directory_table.table_directory_subtables {
+            let Some(table) = self.raw_table(dir.tag.to_be_bytes()) else {
+                continue;
             };
-        } else if flag & 0x10 == 0 {
-            x += parser.read_be_i16()?;
+            if table_checksum(dir.tag, table) != dir.check_sum {
+                return Err(CapyError::new(
+                    ErrorCode::DataLoss,
+                    "font table checksum doesn't match the font directory",
+                )
+                .with_context(&String::from_utf8_lossy(&dir.tag.to_be_bytes())));
+            }
         }
-        x_coordinates.push(x);
+        Ok(())
     }
 
-    for flag in &flags {
-        if flag & 0x04 != 0 {
-            let dy = parser.read_be_u8()?;
-            y += if flag & 0x20 != 0 {
-                dy as i16
-            } else {
-                -(dy as i16)
+    /// The raw DSIG table, holding the font's digital signatures, if it has one.
+    pub fn digital_signature(&self) -> Option<&[u8]> {
+        self.raw_table((TableTag::Dsig as u32).to_be_bytes())
+    }
+
+    /// Whether the font carries at least one digital signature. Some tools add a DSIG table
+    /// with no signatures as a placeholder, which doesn't count. Signatures aren't verified.
+    pub fn is_digitally_signed(&self) -> bool {
+        // DSIG starts with a u32 version followed by the u16 number of signatures
+        self.digital_signature()
+            .and_then(|dsig| dsig.get(4..6))
+            .is_some_and(|num_signatures| num_signatures != [0, 0])
+    }
+
+    /// Identifies the font by the contents of the tables we parse, so the same font loaded
+    /// from different paths or with different loaders hashes the same. The hash is FNV-1a,
+    /// which is stable across runs and Rust versions, unlike `DefaultHasher`.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        for tag in PARSED_TABLES {
+            let Some(table) = self.raw_table((tag as u32).to_be_bytes()) else {
+                continue;
             };
-        } else if flag & 0x20 == 0 {
-            y += parser.read_be_i16()?;
+            hash = fnv1a(hash, &(tag as u32).to_be_bytes());
+            hash = fnv1a(hash, &(table.len() as u64).to_be_bytes());
+            hash = fnv1a(hash, table);
         }
-        y_coordinates.push(y);
+        hash
     }
 
-    Ok(GlyfSubtable {
-        number_of_contours,
-        x_min,
-        y_min,
-        x_max,
-        y_max,
-        end_pts_of_contours,
-        instruction_length,
-        instructions,
-        flags,
-        x_coordinates,
-        y_coordinates,
-    })
-}
+    /// Number of glyphs in the font, from the maxp table. Glyph ids run from 0 to one less.
+    pub fn num_glyphs(&self) -> u16 {
+        self.maxp_table.num_glyphs
+    }
 
-fn read_file_to_byte_buffer(filepath: &str) -> Result<Vec<u8>, CapyError> {
-    let mut file = std::fs::File::open(filepath)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    Ok(buffer)
-}
+    pub fn units_per_em(&self) -> u16 {
+        self.head_table.units_per_em
+    }
 
-fn lookup_offset_for_tag(
-    tag: TableTag,
-    font_directory_table: &FontDirectoryTable,
-) -> Result<usize, CapyError> {
-    let desired_tag = tag as u32;
-    let table_dir = font_directory_table
-        .table_directory_subtables
-        .iter()
-        .find(|&dir| dir.tag == desired_tag)
-        .ok_or_else(|| {
-            CapyError::new(ErrorCode::NotFound, "table not found in FontDirectoryTable")
-        })?;
-    Ok(table_dir.offset as usize)
+    /// Distance from the baseline to the top of the line, in font units.
+    pub fn ascent(&self) -> i16 {
+        self.hhea_table.ascent
+    }
+
+    /// Height of lowercase letters such as 'x' above the baseline, in font units. Comes from the
+    /// OS/2 table, or is measured from the 'x' glyph for fonts that don't record it.
+    pub fn x_height(&self) -> Option<i16> {
+        let os2_value = self.os2_table.get(self).and_then(|table| table.sx_height);
+        os2_value
+            .filter(|&height| height > 0)
+            .or_else(|| self.measured_height('x'))
+    }
+
+    /// Height of capital letters such as 'H' above the baseline, in font units. Comes from the
+    /// OS/2 table, or is measured from the 'H' glyph for fonts that don't record it.
+    pub fn cap_height(&self) -> Option<i16> {
+        let os2_value = self
+            .os2_table
+            .get(self)
+            .and_then(|table| table.s_cap_height);
+        os2_value
+            .filter(|&height| height > 0)
+            .or_else(|| self.measured_height('H'))
+    }
+
+    // Top of a flat-topped letter's outline
+    fn measured_height(&self, char: char) -> Option<i16> {
+        let (_, _, _, y_max) = self.glyph_bounds(self.glyph_index(char)?)?;
+        Some(y_max)
+    }
+
+    /// Distance from the baseline to the bottom of the line in font units (usually negative).
+    pub fn descent(&self) -> i16 {
+        self.hhea_table.descent
+    }
+
+    pub fn line_gap(&self) -> i16 {
+        self.hhea_table.line_gap
+    }
+
+    /// Horizontal advance of a glyph in font units. A composite glyph with a USE_MY_METRICS
+    /// component takes that component's advance, so accented letters match their base letter.
+    pub fn advance_width(&self, glyph_id: u16) -> u16 {
+        self.advance_width_at_depth(glyph_id, 0)
+    }
+
+    /// Distance from a glyph's origin to the left edge of its outline, in font units, or `None`
+    /// for glyph ids past the end of the font's metrics.
+    pub fn left_side_bearing(&self, glyph_id: u16) -> Option<i16> {
+        let index = glyph_id as usize;
+        match self.hmtx_table.h_metrics.get(index) {
+            Some(metric) => Some(metric.left_side_bearing),
+            None => self
+                .hmtx_table
+                .left_side_bearings
+                .get(index - self.hmtx_table.h_metrics.len())
+                .copied(),
+        }
+    }
+
+    /// A glyph's bounding box as `(x_min, y_min, x_max, y_max)` in font units, or `None` for
+    /// glyphs without an outline.
+    pub fn glyph_bounds(&self, glyph_id: u16) -> Option<(i16, i16, i16, i16)> {
+        let glyph = self.glyph(glyph_id)?;
+        if glyph.number_of_contours == 0 {
+            return None;
+        }
+        Some((glyph.x_min, glyph.y_min, glyph.x_max, glyph.y_max))
+    }
+
+    /// The parsed glyph, parsing it on first use. Returns `None` for glyph ids past the end of
+    /// the font and for glyphs whose data is malformed, which `validate` reports.
+    fn glyph(&self, glyph_id: u16) -> Option<&GlyfSubtable> {
+        let slot = self.glyf_table.glyphs.get(glyph_id as usize)?;
+        slot.get_or_init(|| self.parse_glyph(glyph_id))
+            .as_ref()
+            .ok()
+    }
+
+    fn parse_glyph(&self, glyph_id: u16) -> Result<GlyfSubtable, CapyError> {
+        let glyf = self
+            .raw_table((TableTag::Glyf as u32).to_be_bytes())
+            .ok_or(CapyError::new(
+                ErrorCode::NotFound,
+                "font has no glyf table",
+            ))?;
+        let start = self.loca_table.offsets[glyph_id as usize] as usize;
+        let end = self.loca_table.offsets[glyph_id as usize + 1] as usize;
+        parse_glyph_in_range(glyf, start, end)
+            .map_err(|e| e.with_context(&format!("glyph {}", glyph_id)))
+    }
+
+    /// Parses every table and glyph that is otherwise only parsed when first used, failing on
+    /// the first that is malformed. Lenient parsing treats those as missing instead; strict
+    /// parsing runs this before returning the font.
+    pub fn validate(&self) -> Result<(), CapyError> {
+        self.kern_table.validate(self)?;
+        self.gpos_table.validate(self)?;
+        self.vmtx_table.validate(self)?;
+        self.vorg_table.validate(self)?;
+        self.os2_table.validate(self)?;
+        self.post_table.validate(self)?;
+        self.meta_table.validate(self)?;
+        self.gasp_table.validate(self)?;
+        self.name_table.validate(self)?;
+        for (glyph_id, slot) in self.glyf_table.glyphs.iter().enumerate() {
+            if let Some(Ok(_)) = slot.get() {
+                continue;
+            }
+            let glyph = self.parse_glyph(glyph_id as u16)?;
+            let _ = slot.set(Ok(glyph));
+        }
+        Ok(())
+    }
+
+    /// Parses `tag`'s table with `parse`, or returns `None` if the font doesn't have one.
+    fn parse_optional_table<T>(
+        &self,
+        tag: TableTag,
+        parse: impl FnOnce(&mut ByteParser, &FontDirectoryTable) -> Result<T, CapyError>,
+    ) -> Result<Option<T>, CapyError> {
+        let fdt = &self.font_directory_table;
+        if !has_table(tag, fdt) {
+            return Ok(None);
+        }
+        let mut parser = match &self.data {
+            FontData::File(buffer) => ByteParser::new(buffer),
+            #[cfg(feature = "mmap")]
+            FontData::Mapped(mmap) => ByteParser::new(mmap),
+            FontData::Tables(tables) => {
+                let (offset, _) = lookup_offset_for_tag(tag, fdt)?;
+                let table = tables.get(&(tag as u32)).ok_or(CapyError::new(
+                    ErrorCode::NotFound,
+                    "table not found in FontDirectoryTable",
+                ))?;
+                ByteParser::with_base(table, offset)
+            }
+        };
+        parse(&mut parser, fdt).map(Some)
+    }
+
+    fn advance_width_at_depth(&self, glyph_id: u16, depth: usize) -> u16 {
+        let metrics_component = self.glyph(glyph_id).and_then(|glyph| {
+            glyph
+                .components
+                .iter()
+                .find(|component| component.has_flag(GlyphComponent::USE_MY_METRICS))
+        });
+        if let Some(component) = metrics_component.filter(|_| depth < MAX_COMPONENT_DEPTH) {
+            return self.advance_width_at_depth(component.glyph_index, depth + 1);
+        }
+        let h_metrics = &self.hmtx_table.h_metrics;
+        h_metrics
+            .get(glyph_id as usize)
+            .or(h_metrics.last())
+            .map_or(0, |metric| metric.advance_width)
+    }
+
+    /// Vertical advance of a glyph in font units, or `None` if the font has no vertical metrics.
+    pub fn vertical_advance(&self, glyph_id: u16) -> Option<u16> {
+        let v_metrics = &self.vmtx_table.get(self)?.v_metrics;
+        let metric = v_metrics.get(glyph_id as usize).or(v_metrics.last())?;
+        Some(metric.advance_height)
+    }
+
+    /// Distance from the top of a glyph's vertical advance box down to the top of its outline,
+    /// in font units.
+    pub fn top_side_bearing(&self, glyph_id: u16) -> Option<i16> {
+        let vmtx_table = self.vmtx_table.get(self)?;
+        let index = glyph_id as usize;
+        match vmtx_table.v_metrics.get(index) {
+            Some(metric) => Some(metric.top_side_bearing),
+            None => vmtx_table
+                .top_side_bearings
+                .get(index - vmtx_table.v_metrics.len())
+                .copied(),
+        }
+    }
+
+    /// Height above the baseline of the vertical origin, the point the pen sits on in vertical
+    /// layout, in font units. Comes from the VORG table when the font has one, and is otherwise
+    /// derived from the glyph's top side bearing.
+    pub fn vertical_origin_y(&self, glyph_id: u16) -> Option<i32> {
+        if let Some(vorg_table) = self.vorg_table.get(self) {
+            let metrics = &vorg_table.vert_origin_y_metrics;
+            let origin = match metrics.binary_search_by_key(&glyph_id, |&(glyph_id, _)| glyph_id) {
+                Ok(index) => metrics[index].1,
+                Err(_) => vorg_table.default_vert_origin_y,
+            };
+            return Some(origin as i32);
+        }
+        let glyph = self.glyph(glyph_id)?;
+        let top_side_bearing = self.top_side_bearing(glyph_id)?;
+        Some(glyph.y_max as i32 + top_side_bearing as i32)
+    }
+
+    /// Script/language tags (such as `Latn` or `en-Latn`) the font was designed for, from the
+    /// meta table.
+    pub fn design_languages(&self) -> &[String] {
+        self.meta_table
+            .get(self)
+            .map_or(&[], |meta_table| &meta_table.design_languages)
+    }
+
+    /// Script/language tags the font can render, from the meta table.
+    pub fn supported_languages(&self) -> &[String] {
+        self.meta_table
+            .get(self)
+            .map_or(&[], |meta_table| &meta_table.supported_languages)
+    }
+
+    /// A string from the name table, such as `1` for the family name. Windows English names
+    /// are preferred, then any other Windows or Unicode name, then Mac Roman ones.
+    pub fn name(&self, name_id: u16) -> Option<&str> {
+        let rank = |record: &NameRecord| match (record.platform_id, record.language_id) {
+            (PLATFORM_WINDOWS, 0x0409) => 0,
+            (PLATFORM_WINDOWS, _) => 1,
+            (PLATFORM_UNICODE, _) => 2,
+            // Language 0 is English on the Mac platform
+            (_, 0) => 3,
+            _ => 4,
+        };
+        self.name_table
+            .get(self)?
+            .records
+            .iter()
+            .filter(|record| record.name_id == name_id)
+            .min_by_key(|record| rank(record))
+            .map(|record| record.string.as_str())
+    }
+
+    pub fn family_name(&self) -> Option<&str> {
+        self.name(NAME_FAMILY)
+    }
+
+    /// How the font recommends rendering text at `ppem` pixels per em: whether to grid-fit,
+    /// anti-alias, or both. Fonts without a gasp table get grid-fitting and smoothing.
+    pub fn gasp_behavior(&self, ppem: u16) -> GaspFlags {
+        self.gasp_table
+            .get(self)
+            .and_then(|gasp_table| {
+                gasp_table
+                    .ranges
+                    .iter()
+                    .find(|&&(max_ppem, _)| ppem <= max_ppem)
+            })
+            .map_or(GaspFlags::DEFAULT, |&(_, behavior)| {
+                GaspFlags::from_behavior(behavior)
+            })
+    }
+
+    /// Where to draw an underline, from the post table or derived from the descent if the font
+    /// has none.
+    pub fn underline_metrics(&self) -> DecorationMetrics {
+        match self.post_table.get(self) {
+            Some(post_table) if post_table.underline_thickness > 0 => DecorationMetrics {
+                position: post_table.underline_position,
+                thickness: post_table.underline_thickness,
+            },
+            _ => DecorationMetrics {
+                position: self.descent() / 2,
+                thickness: self.default_decoration_thickness(),
+            },
+        }
+    }
+
+    /// Where to draw a strikethrough: about half the x-height, with the underline's thickness.
+    pub fn strikethrough_metrics(&self) -> DecorationMetrics {
+        let thickness = self.underline_metrics().thickness;
+        DecorationMetrics {
+            position: (self.units_per_em() / 4) as i16 + thickness / 2,
+            thickness,
+        }
+    }
+
+    fn default_decoration_thickness(&self) -> i16 {
+        (self.units_per_em() / 20).max(1) as i16
+    }
+
+    /// Horizontal kerning between two glyphs in font units. GPOS pair positioning is consulted
+    /// first, then the legacy kern table.
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let gpos_kerning = self
+            .gpos_table
+            .get(self)
+            .and_then(|table| table.kerning(left, right));
+        gpos_kerning
+            .or_else(|| {
+                let kern_table = self.kern_table.get(self)?;
+                kern_table.kerning(left, right)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Every glyph pair with non-zero kerning as (left, right, value in font units), sorted by
+    /// pair. Values are the ones `kerning` returns, so GPOS overrides the kern table.
+    pub fn kern_pairs(&self) -> Vec<(u16, u16, i16)> {
+        let mut pairs = BTreeSet::new();
+        if let Some(gpos_table) = self.gpos_table.get(self) {
+            for subtable in gpos_table.lookups.iter().flatten() {
+                subtable.collect_pairs(self.maxp_table.num_glyphs, &mut pairs);
+            }
+        }
+        if let Some(kern_table) = self.kern_table.get(self) {
+            pairs.extend(kern_table.pairs.keys().copied());
+            for subtable in &kern_table.class_subtables {
+                subtable.collect_pairs(&mut pairs);
+            }
+        }
+        pairs
+            .into_iter()
+            .map(|(left, right)| (left, right, self.kerning(left, right)))
+            .filter(|&(_, _, value)| value != 0)
+            .collect()
+    }
+
+    /// Multiplier converting font units to pixels at the given pixel size (em height).
+    pub fn scale_factor(&self, pixel_size: f32) -> f32 {
+        pixel_size / self.head_table.units_per_em.max(1) as f32
+    }
+
+    /// Returns the outline of a glyph in font units, split into its contours. Composite glyphs
+    /// are assembled from their transformed components. Contour directions are normalized with
+    /// `normalize_winding`. Outlines are cached, see `with_outline_cache_capacity`.
+    pub fn outline(&self, glyph_id: u16) -> Option<Vec<Contour>> {
+        if let Some(outline) = self.lock_outline_cache().get(glyph_id) {
+            return outline;
+        }
+        let outline = self.outline_at_depth(glyph_id, 0).map(|mut contours| {
+            normalize_winding(&mut contours);
+            contours
+        });
+        self.lock_outline_cache().insert(glyph_id, outline.clone());
+        outline
+    }
+
+    /// Keeps the outlines of up to `capacity` glyphs, dropping the least recently used ones
+    /// first. Defaults to 512 glyphs; 0 turns the cache off.
+    pub fn with_outline_cache_capacity(self, capacity: usize) -> Self {
+        *self.lock_outline_cache() = OutlineCache::new(capacity);
+        self
+    }
+
+    pub fn outline_cache_stats(&self) -> CacheStats {
+        self.lock_outline_cache().stats
+    }
+
+    fn lock_outline_cache(&self) -> std::sync::MutexGuard<'_, OutlineCache> {
+        // The cache is never left half-updated by a panic, so keep using it
+        self.outline_cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn outline_at_depth(&self, glyph_id: u16, depth: usize) -> Option<Vec<Contour>> {
+        let glyph = self.glyph(glyph_id)?;
+        if glyph.number_of_contours < 0 {
+            return self.composite_outline(glyph, depth);
+        }
+
+        let mut contours = Vec::new();
+        let mut start = 0;
+        for &end in &glyph.end_pts_of_contours {
+            let end = end as usize;
+            if end < start || end >= glyph.flags.len() {
+                return None;
+            }
+            let points = (start..=end)
+                .map(|i| Point {
+                    x: glyph.x_coordinates[i],
+                    y: glyph.y_coordinates[i],
+                    on_curve: glyph.flags[i] & 0x01 != 0,
+                })
+                .collect();
+            contours.push(Contour { points });
+            start = end + 1;
+        }
+        Some(contours)
+    }
+
+    fn composite_outline(&self, glyph: &GlyfSubtable, depth: usize) -> Option<Vec<Contour>> {
+        // Composites referring back to themselves would otherwise recurse forever
+        if depth >= MAX_COMPONENT_DEPTH {
+            return None;
+        }
+        let mut contours: Vec<Contour> = Vec::new();
+        for component in &glyph.components {
+            let component_contours = self.outline_at_depth(component.glyph_index, depth + 1)?;
+            let transformed: Vec<Vec<(f32, f32, bool)>> = component_contours
+                .iter()
+                .map(|contour| {
+                    contour
+                        .points
+                        .iter()
+                        .map(|point| {
+                            let (x, y) = component.apply_transform(point.x as f32, point.y as f32);
+                            (x, y, point.on_curve)
+                        })
+                        .collect()
+                })
+                .collect();
+
+            let (dx, dy) = if component.has_flag(GlyphComponent::ARGS_ARE_XY_VALUES) {
+                let (x, y) = (component.arg1 as f32, component.arg2 as f32);
+                // Offsets are unscaled unless the font asks otherwise, as in Microsoft's rasterizer
+                let is_scaled = component.has_flag(GlyphComponent::SCALED_COMPONENT_OFFSET)
+                    && !component.has_flag(GlyphComponent::UNSCALED_COMPONENT_OFFSET);
+                match is_scaled {
+                    true => component.apply_transform(x, y),
+                    false => (x, y),
+                }
+            } else {
+                // Move the component so its point arg2 lands on point arg1 of the glyph so far
+                let anchor = contours
+                    .iter()
+                    .flat_map(|contour| &contour.points)
+                    .nth(component.arg1 as usize)?;
+                let (x, y, _) = *transformed.iter().flatten().nth(component.arg2 as usize)?;
+                (anchor.x as f32 - x, anchor.y as f32 - y)
+            };
+            contours.extend(transformed.into_iter().map(|points| {
+                Contour {
+                    points: points
+                        .into_iter()
+                        .map(|(x, y, on_curve)| Point {
+                            x: (x + dx).round() as i16,
+                            y: (y + dy).round() as i16,
+                            on_curve,
+                        })
+                        .collect(),
+                }
+            }));
+        }
+        Some(contours)
+    }
+
+    /// Pixel sizes (ppem) the font has embedded bitmaps for, from EBLC or CBLC.
+    pub fn bitmap_sizes(&self) -> Vec<u16> {
+        self.bitmap_location_table
+            .as_ref()
+            .map_or(Vec::new(), |table| {
+                table.strikes.iter().map(|strike| strike.ppem).collect()
+            })
+    }
+
+    /// The embedded bitmap for a glyph at `ppem` pixels per em, if the font has one. Color
+    /// fonts may return a bitmap from another size, to be scaled by `ppem / bitmap.ppem`.
+    pub fn bitmap_glyph(&self, glyph_id: u16, ppem: u16) -> Option<BitmapGlyph> {
+        let table = self.bitmap_location_table.as_ref()?;
+        let strike = table.strike(ppem)?;
+        let (range, start, end) = strike.find(glyph_id)?;
+        let data_tag = match table.color {
+            true => TableTag::Cbdt,
+            false => TableTag::Ebdt,
+        };
+        let data = self.raw_table((data_tag as u32).to_be_bytes())?;
+        decode_bitmap_glyph(data, strike, range, (start, end)).ok()
+    }
+
+    /// Returns the glyph outline scaled to `pixel_size` and flipped to the raster's y-down
+    /// orientation, relative to the pen position on the baseline.
+    pub fn outline_at_size(&self, glyph_id: u16, pixel_size: f32) -> Option<Vec<ContourF32>> {
+        let scale = self.scale_factor(pixel_size);
+        let contours = self
+            .outline(glyph_id)?
+            .into_iter()
+            .map(|contour| ContourF32 {
+                points: contour
+                    .points
+                    .iter()
+                    .map(|point| PointF32 {
+                        x: point.x as f32 * scale,
+                        y: -(point.y as f32) * scale,
+                        on_curve: point.on_curve,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Some(contours)
+    }
+}
+
+struct ByteParser<'a> {
+    buffer: &'a [u8],
+    // File offset of the first byte in `buffer`, so offsets stay absolute when only part of the
+    // file has been read
+    base: usize,
+    offset: usize,
+}
+
+impl<'a> ByteParser<'a> {
+    const U8_SIZE: usize = 1;
+    const U24_SIZE: usize = 3;
+    const U32_SIZE: usize = 4;
+    const U16_SIZE: usize = 2;
+    const I16_SIZE: usize = 2;
+
+    fn new(buffer: &'a [u8]) -> Self {
+        Self::with_base(buffer, 0)
+    }
+
+    fn with_base(buffer: &'a [u8], base: usize) -> Self {
+        Self {
+            buffer,
+            base,
+            offset: 0,
+        }
+    }
+
+    fn set_offset(&mut self, offset: usize) -> Result<(), CapyError> {
+        let offset = offset
+            .checked_sub(self.base)
+            .filter(|&offset| offset <= self.buffer.len())
+            .ok_or(CapyError::new(
+                ErrorCode::OutOfRange,
+                "failed to slice buffer for tag",
+            ))?;
+
+        self.offset = offset;
+        Ok(())
+    }
+
+    fn position(&self) -> usize {
+        self.base + self.offset
+    }
+
+    /// Advances past `n` bytes, e.g. reserved fields or padding.
+    fn skip(&mut self, n: usize) -> Result<(), CapyError> {
+        if n <= self.remaining() {
+            self.offset += n;
+            Ok(())
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small to skip",
+            ))
+        }
+    }
+
+    /// Number of bytes left between the current position and the end of the buffer.
+    fn remaining(&self) -> usize {
+        self.buffer.len().saturating_sub(self.offset)
+    }
+
+    /// Reads the next u16 without advancing past it.
+    fn peek_be_u16(&self) -> Result<u16, CapyError> {
+        if self.remaining() >= Self::U16_SIZE {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U16_SIZE];
+            Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u16",
+            ))
+        }
+    }
+
+    fn read_u8_array_256(&mut self) -> Result<[u8; 256], CapyError> {
+        if self.offset + Self::U8_SIZE * 256 <= self.buffer.len() {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U8_SIZE * 256];
+            self.offset += Self::U8_SIZE * 256;
+            Ok(bytes.try_into().unwrap())
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u8 array",
+            ))
+        }
+    }
+
+    fn read_be_i16_array_4(&mut self) -> Result<[i16; 4], CapyError> {
+        if self.offset + Self::I16_SIZE * 4 <= self.buffer.len() {
+            let mut array = [0; 4];
+            for value in array.iter_mut() {
+                let bytes = &self.buffer[self.offset..self.offset + Self::I16_SIZE];
+                self.offset += Self::I16_SIZE;
+                *value = i16::from_be_bytes(bytes.try_into().unwrap());
+            }
+            Ok(array)
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for i16 array",
+            ))
+        }
+    }
+
+    fn read_be_u8(&mut self) -> Result<u8, CapyError> {
+        if self.offset + Self::U8_SIZE <= self.buffer.len() {
+            let byte = self.buffer[self.offset];
+            self.offset += Self::U8_SIZE;
+            Ok(byte)
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u8",
+            ))
+        }
+    }
+
+    /// Reads a 24-bit value, as used for code points in cmap format 14.
+    fn read_be_u24(&mut self) -> Result<u32, CapyError> {
+        if self.offset + Self::U24_SIZE <= self.buffer.len() {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U24_SIZE];
+            self.offset += Self::U24_SIZE;
+            Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u24",
+            ))
+        }
+    }
+
+    fn read_be_u32(&mut self) -> Result<u32, CapyError> {
+        if self.offset + Self::U32_SIZE <= self.buffer.len() {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U32_SIZE];
+            self.offset += Self::U32_SIZE;
+            Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u32",
+            ))
+        }
+    }
+
+    fn read_be_u16(&mut self) -> Result<u16, CapyError> {
+        if self.offset + Self::U16_SIZE <= self.buffer.len() {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U16_SIZE];
+            self.offset += Self::U16_SIZE;
+            Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for u16",
+            ))
+        }
+    }
+
+    fn read_be_i16(&mut self) -> Result<i16, CapyError> {
+        if self.offset + Self::U16_SIZE <= self.buffer.len() {
+            let bytes = &self.buffer[self.offset..self.offset + Self::U16_SIZE];
+            self.offset += Self::U16_SIZE;
+            Ok(i16::from_be_bytes(bytes.try_into().unwrap()))
+        } else {
+            Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "Buffer too small for i16",
+            ))
+        }
+    }
+}
+
+fn parse_font_directory_table(
+    parser: &mut ByteParser,
+    file_length: usize,
+) -> Result<FontDirectoryTable, CapyError> {
+    let offset_subtable = parse_offset_table(parser, file_length)?;
+    let table_directory_subtables =
+        parse_table_directory_subtables(parser, offset_subtable.num_tables)?;
+    // Table lengths are only useful for bounding reads if the tables actually fit in the file
+    for table_dir in &table_directory_subtables {
+        let end = table_dir.offset as usize + table_dir.length as usize;
+        if end > file_length {
+            return Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "font directory entry extends past the end of the file",
+            ));
+        }
+    }
+    Ok(FontDirectoryTable {
+        offset_subtable,
+        table_directory_subtables,
+    })
+}
+
+/// Parses the offset subtable at the start of a font file, rejecting data that is obviously not
+/// a font we can use, such as a saved HTML error page, before anything trusts its table count.
+fn parse_offset_table(
+    parser: &mut ByteParser,
+    file_length: usize,
+) -> Result<OffsetSubtable, CapyError> {
+    if file_length < OFFSET_SUBTABLE_SIZE {
+        return Err(CapyError::new(
+            ErrorCode::InvalidArgument,
+            "file is too short to be a font",
+        ));
+    }
+    let offset_subtable = OffsetSubtable {
+        scalar_type: parser.read_be_u32()?,
+        num_tables: parser.read_be_u16()?,
+        search_range: parser.read_be_u16()?,
+        entry_selector: parser.read_be_u16()?,
+        range_shift: parser.read_be_u16()?,
+    };
+    // Fail early with a clear reason rather than on a missing glyf table later
+    match SfntVersion::from_scalar_type(offset_subtable.scalar_type) {
+        Some(SfntVersion::TrueType | SfntVersion::AppleTrueType) => {}
+        Some(SfntVersion::OpenTypeCff) => {
+            return Err(CapyError::new(
+                ErrorCode::Unimplemented,
+                "fonts with CFF outlines are not supported",
+            ))
+        }
+        Some(SfntVersion::PostScriptType1) => {
+            return Err(CapyError::new(
+                ErrorCode::Unimplemented,
+                "PostScript Type 1 fonts are not supported",
+            ))
+        }
+        Some(SfntVersion::Collection) => {
+            return Err(CapyError::new(
+                ErrorCode::Unimplemented,
+                "font collections are not supported",
+            ))
+        }
+        None => {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "not a TrueType or OpenType font file",
+            ))
+        }
+    }
+    let directory_size =
+        OFFSET_SUBTABLE_SIZE + offset_subtable.num_tables as usize * TABLE_DIRECTORY_ENTRY_SIZE;
+    if offset_subtable.num_tables == 0 || directory_size > file_length {
+        return Err(CapyError::new(
+            ErrorCode::InvalidArgument,
+            "font directory is empty or doesn't fit in the file",
+        ));
+    }
+    Ok(offset_subtable)
+}
+
+fn parse_table_directory_subtables(
+    parser: &mut ByteParser,
+    num_tables: u16,
+) -> Result<Vec<TableDirectorySubtable>, CapyError> {
+    let mut subtables = Vec::new();
+    for _ in 0..num_tables {
+        subtables.push(TableDirectorySubtable {
+            tag: parser.read_be_u32()?,
+            check_sum: parser.read_be_u32()?,
+            offset: parser.read_be_u32()?,
+            length: parser.read_be_u32()?,
+        });
+    }
+    Ok(subtables)
+}
+
+fn parse_cmap_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<CmapTable, CapyError> {
+    let (cmap_offset, cmap_length) = lookup_offset_for_tag(TableTag::Cmap, font_directory_table)?;
+    parser.set_offset(cmap_offset)?;
+    let version = parser.read_be_u16()?;
+    let num_subtables = parser.read_be_u16()?;
+    let encoding_subtables = parse_cmap_encoding_subtables(parser, num_subtables)?;
+    let mut subtables = Vec::new();
+    for table in encoding_subtables.iter() {
+        let subtable_offset = table.offset as usize;
+        if subtable_offset >= cmap_length {
+            return Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "cmap encoding subtable offset points outside the cmap table",
+            ));
+        }
+        parser.set_offset(cmap_offset + subtable_offset)?;
+        let subtable = match parser.peek_be_u16()? {
+            0 => CmapSubtable::FormatZero(parse_cmap_format_zero(parser)?),
+            4 => {
+                let tmp_table = parse_cmap_format_four(parser)?;
+                if tmp_table.length as usize > cmap_length - subtable_offset {
+                    return Err(CapyError::new(
+                        ErrorCode::OutOfRange,
+                        "cmap format 4 subtable extends past the end of the cmap table",
+                    ));
+                }
+                CmapSubtable::FormatFour(tmp_table)
+            }
+            14 => CmapSubtable::FormatFourteen(parse_cmap_format_fourteen(
+                parser,
+                cmap_length - subtable_offset,
+            )?),
+            format => CmapSubtable::Unsupported(format),
+        };
+        subtables.push(subtable);
+    }
+    let preferred = preferred_cmap_subtable(&encoding_subtables, &subtables);
+    Ok(CmapTable {
+        version,
+        num_subtables,
+        encoding_subtables,
+        subtables,
+        preferred,
+    })
+}
+
+fn parse_cmap_format_zero(parser: &mut ByteParser) -> Result<CmapFormatZeroTable, CapyError> {
+    Ok(CmapFormatZeroTable {
+        format: parser.read_be_u16()?,
+        length: parser.read_be_u16()?,
+        language: parser.read_be_u16()?,
+        glyph_index_array: parser.read_u8_array_256()?,
+    })
+}
+
+/// Parses a format 14 subtable, which may take up at most `max_length` bytes.
+fn parse_cmap_format_fourteen(
+    parser: &mut ByteParser,
+    max_length: usize,
+) -> Result<CmapFormatFourteenTable, CapyError> {
+    let subtable_offset = parser.position();
+    let _format = parser.read_be_u16()?;
+    let length = parser.read_be_u32()? as usize;
+    if length > max_length {
+        return Err(CapyError::new(
+            ErrorCode::OutOfRange,
+            "cmap format 14 subtable extends past the end of the cmap table",
+        ));
+    }
+    let num_records = parser.read_be_u32()?;
+    let mut offsets = Vec::new();
+    for _ in 0..num_records {
+        let selector = parser.read_be_u24()?;
+        let default_offset = parser.read_be_u32()? as usize;
+        let non_default_offset = parser.read_be_u32()? as usize;
+        offsets.push((selector, default_offset, non_default_offset));
+    }
+
+    let mut selectors = Vec::new();
+    for (selector, default_offset, non_default_offset) in offsets {
+        // Offsets are from the start of the subtable; zero means the table is absent
+        let mut default_ranges = Vec::new();
+        if default_offset != 0 {
+            parser.set_offset(subtable_offset + default_offset)?;
+            for _ in 0..parser.read_be_u32()? {
+                default_ranges.push((parser.read_be_u24()?, parser.read_be_u8()?));
+            }
+        }
+        let mut mappings = Vec::new();
+        if non_default_offset != 0 {
+            parser.set_offset(subtable_offset + non_default_offset)?;
+            for _ in 0..parser.read_be_u32()? {
+                mappings.push((parser.read_be_u24()?, parser.read_be_u16()?));
+            }
+        }
+        selectors.push(VariationSelectorRecord {
+            selector,
+            default_ranges,
+            mappings,
+        });
+    }
+    Ok(CmapFormatFourteenTable { selectors })
+}
+
+fn parse_cmap_format_four(parser: &mut ByteParser) -> Result<CmapFormatFourTable, CapyError> {
+    // format, length, language, seg_count_x2, search_range, entry_selector, range_shift
+    const HEADER_SIZE: usize = 14;
+
+    let format = parser.read_be_u16()?;
+    let length = parser.read_be_u16()?;
+    let language = parser.read_be_u16()?;
+    let seg_count_x2 = parser.read_be_u16()?;
+    let seg_count = seg_count_x2 / 2;
+    if seg_count_x2 % 2 != 0 || seg_count == 0 {
+        return Err(CapyError::new(
+            ErrorCode::OutOfRange,
+            "cmap format 4 subtable has an invalid segment count",
+        ));
+    }
+
+    // The four segment arrays plus reserved_pad follow the header; whatever remains of the
+    // subtable is the glyph id array.
+    let arrays_size = 4 * seg_count_x2 as usize + 2;
+    let glyph_id_array_size = (length as usize)
+        .checked_sub(HEADER_SIZE + arrays_size)
+        .ok_or(CapyError::new(
+            ErrorCode::OutOfRange,
+            "cmap format 4 subtable is too short for its segment count",
+        ))?;
+
+    let search_range = parser.read_be_u16()?;
+    let entry_selector = parser.read_be_u16()?;
+    let range_shift = parser.read_be_u16()?;
+    // The search fields are derived from the largest power of two <= seg_count
+    let expected_entry_selector = seg_count.ilog2() as u16;
+    let expected_search_range = 2 << expected_entry_selector;
+    let search_fields_valid = search_range == expected_search_range
+        && entry_selector == expected_entry_selector
+        && range_shift == seg_count_x2 - expected_search_range;
+    let mut end_code = Vec::new();
+    for _ in 0..seg_count {
+        end_code.push(parser.read_be_u16()?);
+    }
+    let reserved_pad = parser.read_be_u16()?;
+    let mut start_code = Vec::new();
+    for _ in 0..seg_count {
+        start_code.push(parser.read_be_u16()?);
+    }
+    let mut id_delta = Vec::new();
+    for _ in 0..seg_count {
+        id_delta.push(parser.read_be_u16()?);
+    }
+    let mut id_range_offset = Vec::new();
+    for _ in 0..seg_count {
+        id_range_offset.push(parser.read_be_u16()?);
+    }
+    let mut glyph_id_array = Vec::new();
+    for _ in 0..glyph_id_array_size / 2 {
+        glyph_id_array.push(parser.read_be_u16()?);
+    }
+
+    // Every id_range_offset must point into the glyph id array for its whole segment
+    for segment in 0..seg_count as usize {
+        if start_code[segment] > end_code[segment] {
+            return Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "cmap format 4 segment starts after it ends",
+            ));
+        }
+        if id_range_offset[segment] == 0 {
+            continue;
+        }
+        let first = (id_range_offset[segment] / 2) as usize;
+        let last = first + (end_code[segment] - start_code[segment]) as usize;
+        let slots_before_array = seg_count as usize - segment;
+        if first < slots_before_array || last - slots_before_array >= glyph_id_array.len() {
+            // The spec's 0xFFFF sentinel segment is sometimes given a bogus offset; it never maps
+            // to a real glyph, so tolerate it.
+            if end_code[segment] == 0xFFFF && start_code[segment] == 0xFFFF {
+                continue;
+            }
+            return Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "cmap format 4 id_range_offset points outside the subtable",
+            ));
+        }
+    }
+
+    Ok(CmapFormatFourTable {
+        format,
+        length,
+        language,
+        seg_count_x2,
+        search_range,
+        entry_selector,
+        range_shift,
+        end_code,
+        reserved_pad,
+        start_code,
+        id_delta,
+        id_range_offset,
+        glyph_id_array,
+        search_fields_valid,
+    })
+}
+
+fn parse_cmap_encoding_subtables(
+    parser: &mut ByteParser,
+    num_subtables: u16,
+) -> Result<Vec<CmapEncodingSubtable>, CapyError> {
+    let mut tables = Vec::new();
+    for _ in 0..num_subtables {
+        tables.push(CmapEncodingSubtable {
+            platform_id: parser.read_be_u16()?,
+            platform_specific_id: parser.read_be_u16()?,
+            offset: parser.read_be_u32()?,
+        });
+    }
+    Ok(tables)
+}
+
+fn parse_head_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<HeadTable, CapyError> {
+    let (head_offset, _) = lookup_offset_for_tag(TableTag::Head, font_directory_table)?;
+    parser.set_offset(head_offset)?;
+    Ok(HeadTable {
+        version: parser.read_be_u32()?,
+        font_revision: parser.read_be_u32()?,
+        check_sum_adjustment: parser.read_be_u32()?,
+        magic_number: parser.read_be_u32()?,
+        flags: parser.read_be_u16()?,
+        units_per_em: parser.read_be_u16()?,
+        created: parser.read_be_u32()?,
+        modified: parser.read_be_u32()?,
+        x_min: parser.read_be_i16()?,
+        y_min: parser.read_be_i16()?,
+        x_max: parser.read_be_i16()?,
+        y_max: parser.read_be_i16()?,
+        mac_style: parser.read_be_u16()?,
+        lowest_rec_ppem: parser.read_be_u16()?,
+        font_direction_hint: parser.read_be_i16()?,
+        index_to_loc_format: parser.read_be_i16()?,
+        glyph_data_format: parser.read_be_i16()?,
+    })
+}
+
+fn parse_hhea_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<HheaTable, CapyError> {
+    let (hhea_offset, _) = lookup_offset_for_tag(TableTag::Hhea, font_directory_table)?;
+    parser.set_offset(hhea_offset)?;
+    Ok(HheaTable {
+        version: parser.read_be_u32()?,
+        ascent: parser.read_be_i16()?,
+        descent: parser.read_be_i16()?,
+        line_gap: parser.read_be_i16()?,
+        advance_width_max: parser.read_be_u16()?,
+        min_left_side_bearing: parser.read_be_i16()?,
+        min_right_side_bearing: parser.read_be_i16()?,
+        x_max_extent: parser.read_be_i16()?,
+        caret_slope_rise: parser.read_be_i16()?,
+        caret_slope_run: parser.read_be_i16()?,
+        caret_offset: parser.read_be_i16()?,
+        reserved: parser.read_be_i16_array_4()?,
+        metric_data_format: parser.read_be_i16()?,
+        number_of_hmetrics: parser.read_be_u16()?,
+    })
+}
+
+fn parse_maxp_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<MaxpTable, CapyError> {
+    let (maxp_offset, _) = lookup_offset_for_tag(TableTag::Maxp, font_directory_table)?;
+    parser.set_offset(maxp_offset)?;
+    Ok(MaxpTable {
+        version: parser.read_be_u32()?,
+        num_glyphs: parser.read_be_u16()?,
+        max_points: parser.read_be_u16()?,
+        max_contours: parser.read_be_u16()?,
+        max_composite_points: parser.read_be_u16()?,
+        max_composite_contours: parser.read_be_u16()?,
+        max_zones: parser.read_be_u16()?,
+        max_twilight_points: parser.read_be_u16()?,
+        max_storage: parser.read_be_u16()?,
+        max_function_defs: parser.read_be_u16()?,
+        max_instruction_defs: parser.read_be_u16()?,
+        max_stack_elements: parser.read_be_u16()?,
+        max_size_of_instructions: parser.read_be_u16()?,
+        max_component_elements: parser.read_be_u16()?,
+        max_component_depth: parser.read_be_u16()?,
+    })
+}
+
+fn parse_hmtx_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+    number_of_hmetrics: u16,
+    num_glyphs: u16,
+    options: ParseOptions,
+) -> Result<HmtxTable, CapyError> {
+    let (hmtx_offset, hmtx_length) = lookup_offset_for_tag(TableTag::Hmtx, font_directory_table)?;
+    if number_of_hmetrics == 0 && num_glyphs > 0 {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "hhea declares no horizontal metrics",
+        ));
+    }
+    if number_of_hmetrics > num_glyphs && options.strict {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "hhea declares more horizontal metrics than there are glyphs",
+        ));
+    }
+    // Metrics past the last glyph belong to nothing, so don't read into the next table for them
+    let mut number_of_hmetrics = number_of_hmetrics.min(num_glyphs);
+    if number_of_hmetrics as usize * 4 > hmtx_length {
+        if options.strict || hmtx_length < 4 {
+            return Err(CapyError::new(
+                ErrorCode::DataLoss,
+                "hmtx table is too short for its metrics",
+            ));
+        }
+        // The glyphs left without a metric share the last one, as if they had been declared so
+        number_of_hmetrics = (hmtx_length / 4) as u16;
+    }
+    let h_metrics_length = number_of_hmetrics as usize * 4;
+    // Some fonts leave off trailing bearings; the glyphs missing one report none
+    let num_left_side_bearings =
+        ((num_glyphs - number_of_hmetrics) as usize).min((hmtx_length - h_metrics_length) / 2);
+    parser.set_offset(hmtx_offset)?;
+    let mut h_metrics = Vec::with_capacity(number_of_hmetrics as usize);
+    for _ in 0..number_of_hmetrics {
+        h_metrics.push(LongHorMetric {
+            advance_width: parser.read_be_u16()?,
+            left_side_bearing: parser.read_be_i16()?,
+        });
+    }
+    let mut left_side_bearings = Vec::with_capacity(num_left_side_bearings);
+    for _ in 0..num_left_side_bearings {
+        left_side_bearings.push(parser.read_be_i16()?);
+    }
+    Ok(HmtxTable {
+        h_metrics,
+        left_side_bearings,
+    })
+}
+
+fn parse_vhea_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<VheaTable, CapyError> {
+    let (vhea_offset, _) = lookup_offset_for_tag(TableTag::Vhea, font_directory_table)?;
+    parser.set_offset(vhea_offset)?;
+    Ok(VheaTable {
+        version: parser.read_be_u32()?,
+        vert_typo_ascender: parser.read_be_i16()?,
+        vert_typo_descender: parser.read_be_i16()?,
+        vert_typo_line_gap: parser.read_be_i16()?,
+        advance_height_max: parser.read_be_u16()?,
+        min_top_side_bearing: parser.read_be_i16()?,
+        min_bottom_side_bearing: parser.read_be_i16()?,
+        y_max_extent: parser.read_be_i16()?,
+        caret_slope_rise: parser.read_be_i16()?,
+        caret_slope_run: parser.read_be_i16()?,
+        caret_offset: parser.read_be_i16()?,
+        reserved: parser.read_be_i16_array_4()?,
+        metric_data_format: parser.read_be_i16()?,
+        num_of_long_ver_metrics: parser.read_be_u16()?,
+    })
+}
+
+fn parse_vorg_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<VorgTable, CapyError> {
+    let (vorg_offset, _) = lookup_offset_for_tag(TableTag::Vorg, font_directory_table)?;
+    parser.set_offset(vorg_offset)?;
+    let _major_version = parser.read_be_u16()?;
+    let _minor_version = parser.read_be_u16()?;
+    let default_vert_origin_y = parser.read_be_i16()?;
+    let num_vert_origin_y_metrics = parser.read_be_u16()?;
+    let mut vert_origin_y_metrics = Vec::with_capacity(num_vert_origin_y_metrics as usize);
+    for _ in 0..num_vert_origin_y_metrics {
+        vert_origin_y_metrics.push((parser.read_be_u16()?, parser.read_be_i16()?));
+    }
+    if vert_origin_y_metrics
+        .windows(2)
+        .any(|pair| pair[0].0 >= pair[1].0)
+    {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "VORG metrics are not sorted by glyph id",
+        ));
+    }
+    Ok(VorgTable {
+        default_vert_origin_y,
+        vert_origin_y_metrics,
+    })
+}
+
+fn parse_vmtx_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+    num_of_long_ver_metrics: u16,
+    num_glyphs: u16,
+) -> Result<VmtxTable, CapyError> {
+    let (vmtx_offset, _) = lookup_offset_for_tag(TableTag::Vmtx, font_directory_table)?;
+    parser.set_offset(vmtx_offset)?;
+    let mut v_metrics = Vec::new();
+    // As in hmtx, metrics past the last glyph are never read
+    for _ in 0..num_of_long_ver_metrics.min(num_glyphs) {
+        v_metrics.push(LongVerMetric {
+            advance_height: parser.read_be_u16()?,
+            top_side_bearing: parser.read_be_i16()?,
+        });
+    }
+    let mut top_side_bearings = Vec::new();
+    for _ in num_of_long_ver_metrics.min(num_glyphs)..num_glyphs {
+        top_side_bearings.push(parser.read_be_i16()?);
+    }
+    Ok(VmtxTable {
+        v_metrics,
+        top_side_bearings,
+    })
+}
+
+fn parse_os2_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<Os2Table, CapyError> {
+    // Where sxHeight sits; sCapHeight follows it
+    const SX_HEIGHT_OFFSET: usize = 86;
+
+    let (os2_offset, os2_length) = lookup_offset_for_tag(TableTag::Os2, font_directory_table)?;
+    parser.set_offset(os2_offset)?;
+    let version = parser.read_be_u16()?;
+    let (mut sx_height, mut s_cap_height) = (None, None);
+    // Some fonts claim version 2 but ship the shorter version 1 table
+    if version >= 2 && os2_length >= SX_HEIGHT_OFFSET + 4 {
+        parser.set_offset(os2_offset + SX_HEIGHT_OFFSET)?;
+        sx_height = Some(parser.read_be_i16()?);
+        s_cap_height = Some(parser.read_be_i16()?);
+    }
+    Ok(Os2Table {
+        sx_height,
+        s_cap_height,
+    })
+}
+
+fn parse_post_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<PostTable, CapyError> {
+    let (post_offset, _) = lookup_offset_for_tag(TableTag::Post, font_directory_table)?;
+    parser.set_offset(post_offset)?;
+    Ok(PostTable {
+        version: parser.read_be_u32()?,
+        italic_angle: parser.read_be_u32()?,
+        underline_position: parser.read_be_i16()?,
+        underline_thickness: parser.read_be_i16()?,
+        is_fixed_pitch: parser.read_be_u32()?,
+    })
+}
+
+fn parse_meta_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<MetaTable, CapyError> {
+    let (meta_offset, meta_length) = lookup_offset_for_tag(TableTag::Meta, font_directory_table)?;
+    parser.set_offset(meta_offset)?;
+    let _version = parser.read_be_u32()?;
+    let _flags = parser.read_be_u32()?;
+    let _reserved = parser.read_be_u32()?;
+    let data_maps_count = parser.read_be_u32()?;
+
+    let mut data_maps = Vec::new();
+    for _ in 0..data_maps_count {
+        let tag = parser.read_be_u32()?;
+        let data_offset = parser.read_be_u32()? as usize;
+        let data_length = parser.read_be_u32()? as usize;
+        if data_offset + data_length > meta_length {
+            return Err(CapyError::new(
+                ErrorCode::OutOfRange,
+                "meta data map points outside the meta table",
+            ));
+        }
+        data_maps.push((tag, data_offset, data_length));
+    }
+
+    let mut design_languages = Vec::new();
+    let mut supported_languages = Vec::new();
+    for (tag, data_offset, data_length) in data_maps {
+        let languages = match &tag.to_be_bytes() {
+            b"dlng" => &mut design_languages,
+            b"slng" => &mut supported_languages,
+            _ => continue,
+        };
+        // Both maps hold a comma-separated list of ScriptLangTags in UTF-8
+        parser.set_offset(meta_offset + data_offset)?;
+        let mut data = Vec::with_capacity(data_length);
+        for _ in 0..data_length {
+            data.push(parser.read_be_u8()?);
+        }
+        languages.extend(
+            String::from_utf8_lossy(&data)
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from),
+        );
+    }
+    Ok(MetaTable {
+        design_languages,
+        supported_languages,
+    })
+}
+
+fn parse_name_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<NameTable, CapyError> {
+    let (name_offset, name_length) = lookup_offset_for_tag(TableTag::Name, font_directory_table)?;
+    parser.set_offset(name_offset)?;
+    let _version = parser.read_be_u16()?;
+    let count = parser.read_be_u16()?;
+    let storage_offset = parser.read_be_u16()? as usize;
+
+    let mut records = Vec::new();
+    for index in 0..count as usize {
+        let record_offset = 6 + index * 12;
+        if record_offset + 12 > name_length {
+            break;
+        }
+        parser.set_offset(name_offset + record_offset)?;
+        let platform_id = parser.read_be_u16()?;
+        let encoding_id = parser.read_be_u16()?;
+        let language_id = parser.read_be_u16()?;
+        let name_id = parser.read_be_u16()?;
+        let length = parser.read_be_u16()? as usize;
+        let string_offset = parser.read_be_u16()? as usize;
+        // Names aren't needed to render, so a broken record shouldn't cost the whole font
+        if storage_offset + string_offset + length > name_length {
+            continue;
+        }
+
+        parser.set_offset(name_offset + storage_offset + string_offset)?;
+        let string = match (platform_id, encoding_id) {
+            // UTF-16BE: Windows symbol and Unicode BMP/full encodings, and every Unicode one
+            (PLATFORM_UNICODE, _) | (PLATFORM_WINDOWS, 0 | 1 | 10) => {
+                let mut units = Vec::with_capacity(length / 2);
+                for _ in 0..length / 2 {
+                    units.push(parser.read_be_u16()?);
+                }
+                String::from_utf16_lossy(&units)
+            }
+            // Roman is the only Mac script we decode
+            (PLATFORM_MACINTOSH, 0) => {
+                let mut string = String::with_capacity(length);
+                for _ in 0..length {
+                    string.push(match parser.read_be_u8()? {
+                        byte @ 0..=0x7f => byte as char,
+                        byte => MAC_ROMAN[byte as usize - 0x80],
+                    });
+                }
+                string
+            }
+            _ => continue,
+        };
+        records.push(NameRecord {
+            platform_id,
+            language_id,
+            name_id,
+            string,
+        });
+    }
+    Ok(NameTable { records })
+}
+
+fn parse_gasp_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<GaspTable, CapyError> {
+    let (gasp_offset, _) = lookup_offset_for_tag(TableTag::Gasp, font_directory_table)?;
+    parser.set_offset(gasp_offset)?;
+    let version = parser.read_be_u16()?;
+    let num_ranges = parser.read_be_u16()?;
+    let mut ranges = Vec::with_capacity(num_ranges as usize);
+    for _ in 0..num_ranges {
+        let max_ppem = parser.read_be_u16()?;
+        let mut behavior = parser.read_be_u16()?;
+        // The symmetric flags were added in version 1 and are undefined before it
+        if version == 0 {
+            behavior &= GaspFlags::GRIDFIT | GaspFlags::DOGRAY;
+        }
+        ranges.push((max_ppem, behavior));
+    }
+    if ranges.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "gasp ranges are not sorted by ppem",
+        ));
+    }
+    Ok(GaspTable { ranges })
+}
+
+fn parse_bitmap_location_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+    color: bool,
+) -> Result<BitmapLocationTable, CapyError> {
+    let (location_tag, data_tag) = match color {
+        true => (TableTag::Cblc, TableTag::Cbdt),
+        false => (TableTag::Eblc, TableTag::Ebdt),
+    };
+    let (location_offset, _) = lookup_offset_for_tag(location_tag, font_directory_table)?;
+    let (_, data_length) = lookup_offset_for_tag(data_tag, font_directory_table)?;
+    parser.set_offset(location_offset)?;
+    let _version = parser.read_be_u32()?;
+    let num_sizes = parser.read_be_u32()?;
+
+    // (index subtable array offset, number of index subtables, ppem, bit depth)
+    let mut sizes = Vec::new();
+    for _ in 0..num_sizes {
+        let array_offset = parser.read_be_u32()? as usize;
+        let _index_tables_size = parser.read_be_u32()?;
+        let num_subtables = parser.read_be_u32()?;
+        let _color_ref = parser.read_be_u32()?;
+        // Horizontal and vertical line metrics, then the glyph range the strike covers
+        parser.skip(28)?;
+        let _ppem_x = parser.read_be_u8()?;
+        let ppem = parser.read_be_u8()?;
+        let bit_depth = parser.read_be_u8()?;
+        let _flags = parser.read_be_u8()?;
+        sizes.push((array_offset, num_subtables, ppem, bit_depth));
+    }
+
+    let mut strikes = Vec::new();
+    for (array_offset, num_subtables, ppem, bit_depth) in sizes {
+        let array_start = location_offset + array_offset;
+        let mut ranges = Vec::new();
+        for index in 0..num_subtables as usize {
+            parser.set_offset(array_start + index * 8)?;
+            let first_glyph = parser.read_be_u16()?;
+            let last_glyph = parser.read_be_u16()?;
+            let subtable_offset = parser.read_be_u32()? as usize;
+            if last_glyph < first_glyph {
+                return Err(CapyError::new(
+                    ErrorCode::DataLoss,
+                    "bitmap index subtable ends before it starts",
+                ));
+            }
+            parser.set_offset(array_start + subtable_offset)?;
+            if let Some(range) = parse_bitmap_index_subtable(parser, first_glyph, last_glyph)? {
+                if range.glyphs.iter().any(|&(_, _, end)| end > data_length) {
+                    return Err(CapyError::new(
+                        ErrorCode::OutOfRange,
+                        "bitmap glyph extends past the end of its data table",
+                    ));
+                }
+                ranges.push(range);
+            }
+        }
+        strikes.push(BitmapStrike {
+            ppem: ppem as u16,
+            bit_depth,
+            ranges,
+        });
+    }
+    Ok(BitmapLocationTable { color, strikes })
+}
+
+/// Parses the index subtable at the parser's position, covering `first_glyph..=last_glyph`.
+/// Returns `None` for index formats we don't know, whose glyphs are then left without bitmaps.
+fn parse_bitmap_index_subtable(
+    parser: &mut ByteParser,
+    first_glyph: u16,
+    last_glyph: u16,
+) -> Result<Option<BitmapGlyphRange>, CapyError> {
+    let index_format = parser.read_be_u16()?;
+    let image_format = parser.read_be_u16()?;
+    let image_data_offset = parser.read_be_u32()? as usize;
+    let glyph_count = (last_glyph - first_glyph) as usize + 1;
+
+    let mut metrics = None;
+    // Offsets of each glyph's image from image_data_offset, with the end of the last one
+    let mut glyph_ids = Vec::new();
+    let mut offsets = Vec::new();
+    match index_format {
+        // Variable-size images, with u32 or u16 offsets for every glyph in the range
+        1 | 3 => {
+            for _ in 0..glyph_count + 1 {
+                offsets.push(match index_format {
+                    1 => parser.read_be_u32()? as usize,
+                    _ => parser.read_be_u16()? as usize,
+                });
+            }
+            glyph_ids.extend(first_glyph..=last_glyph);
+        }
+        // Same-size images for every glyph in the range, sharing metrics
+        2 => {
+            let image_size = parser.read_be_u32()? as usize;
+            metrics = Some(read_big_bitmap_metrics(parser)?);
+            offsets.extend((0..=glyph_count).map(|index| index * image_size));
+            glyph_ids.extend(first_glyph..=last_glyph);
+        }
+        // Variable-size images for only some of the glyphs in the range
+        4 => {
+            let num_glyphs = parser.read_be_u32()?;
+            for _ in 0..num_glyphs + 1 {
+                glyph_ids.push(parser.read_be_u16()?);
+                offsets.push(parser.read_be_u16()? as usize);
+            }
+            // The last pair only marks where the last image ends
+            glyph_ids.pop();
+        }
+        // Same-size images for only some of the glyphs in the range, sharing metrics
+        5 => {
+            let image_size = parser.read_be_u32()? as usize;
+            metrics = Some(read_big_bitmap_metrics(parser)?);
+            let num_glyphs = parser.read_be_u32()? as usize;
+            for _ in 0..num_glyphs {
+                glyph_ids.push(parser.read_be_u16()?);
+            }
+            offsets.extend((0..=num_glyphs).map(|index| index * image_size));
+        }
+        _ => return Ok(None),
+    }
+
+    let mut glyphs: Vec<(u16, usize, usize)> = glyph_ids
+        .into_iter()
+        .zip(offsets.windows(2))
+        // Equal offsets mean the glyph has no bitmap in this strike
+        .filter(|(_, range)| range[0] < range[1])
+        .map(|(glyph_id, range)| {
+            (
+                glyph_id,
+                image_data_offset + range[0],
+                image_data_offset + range[1],
+            )
+        })
+        .collect();
+    glyphs.sort_by_key(|&(glyph_id, _, _)| glyph_id);
+    Ok(Some(BitmapGlyphRange {
+        image_format,
+        metrics,
+        glyphs,
+    }))
+}
+
+fn read_small_bitmap_metrics(parser: &mut ByteParser) -> Result<BitmapMetrics, CapyError> {
+    let height = parser.read_be_u8()?;
+    let width = parser.read_be_u8()?;
+    let bearing_x = parser.read_be_u8()? as i8;
+    let bearing_y = parser.read_be_u8()? as i8;
+    let _advance = parser.read_be_u8()?;
+    Ok(BitmapMetrics {
+        height,
+        width,
+        bearing_x,
+        bearing_y,
+    })
+}
+
+/// Reads big glyph metrics, keeping the horizontal ones.
+fn read_big_bitmap_metrics(parser: &mut ByteParser) -> Result<BitmapMetrics, CapyError> {
+    let metrics = read_small_bitmap_metrics(parser)?;
+    // Vertical bearings and advance
+    parser.skip(3)?;
+    Ok(metrics)
+}
+
+/// Decodes the image stored at `start..end` of the EBDT or CBDT table.
+fn decode_bitmap_glyph(
+    data: &[u8],
+    strike: &BitmapStrike,
+    range: &BitmapGlyphRange,
+    (start, end): (usize, usize),
+) -> Result<BitmapGlyph, CapyError> {
+    let mut parser = ByteParser::new(data);
+    parser.set_offset(start)?;
+    let shared_metrics = || {
+        range.metrics.ok_or(CapyError::new(
+            ErrorCode::DataLoss,
+            "bitmap glyph has no metrics",
+        ))
+    };
+    let (metrics, encoding) = match range.image_format {
+        1 => (
+            read_small_bitmap_metrics(&mut parser)?,
+            BitmapEncoding::ByteAligned,
+        ),
+        2 => (
+            read_small_bitmap_metrics(&mut parser)?,
+            BitmapEncoding::BitAligned,
+        ),
+        5 => (shared_metrics()?, BitmapEncoding::BitAligned),
+        6 => (
+            read_big_bitmap_metrics(&mut parser)?,
+            BitmapEncoding::ByteAligned,
+        ),
+        7 => (
+            read_big_bitmap_metrics(&mut parser)?,
+            BitmapEncoding::BitAligned,
+        ),
+        17 => (read_small_bitmap_metrics(&mut parser)?, BitmapEncoding::Png),
+        18 => (read_big_bitmap_metrics(&mut parser)?, BitmapEncoding::Png),
+        19 => (shared_metrics()?, BitmapEncoding::Png),
+        _ => {
+            return Err(CapyError::new(
+                ErrorCode::Unimplemented,
+                "unsupported embedded bitmap image format",
+            ))
+        }
+    };
+    let (width, height) = (metrics.width as usize, metrics.height as usize);
+
+    let pixels = match encoding {
+        BitmapEncoding::Png => {
+            let length = parser.read_be_u32()? as usize;
+            let image = data
+                .get(parser.position()..parser.position() + length)
+                .filter(|_| parser.position() + length <= end)
+                .ok_or(CapyError::new(
+                    ErrorCode::OutOfRange,
+                    "bitmap PNG extends past its glyph data",
+                ))?;
+            let (png_width, png_height, rgba) = decode_png(image)?;
+            return Ok(BitmapGlyph {
+                ppem: strike.ppem,
+                width: png_width,
+                height: png_height,
+                left: metrics.bearing_x as i32,
+                top: metrics.bearing_y as i32,
+                pixels: BitmapPixels::Rgba(rgba),
+            });
+        }
+        BitmapEncoding::ByteAligned | BitmapEncoding::BitAligned => {
+            let bit_depth = strike.bit_depth as usize;
+            if !matches!(bit_depth, 1 | 2 | 4 | 8) {
+                return Err(CapyError::new(
+                    ErrorCode::DataLoss,
+                    "embedded bitmap has an invalid bit depth",
+                ));
+            }
+            let row_bits = match encoding {
+                BitmapEncoding::ByteAligned => (width * bit_depth).next_multiple_of(8),
+                _ => width * bit_depth,
+            };
+            let image = data.get(parser.position()..end).unwrap_or_default();
+            if image.len() * 8 < row_bits * height {
+                return Err(CapyError::new(
+                    ErrorCode::OutOfRange,
+                    "bitmap image is smaller than its metrics",
+                ));
+            }
+            let max_value = (1 << bit_depth) - 1;
+            let mut coverage = Vec::with_capacity(width * height);
+            for row in 0..height {
+                for col in 0..width {
+                    // Pixels are packed from the most significant bit down
+                    let bit = row * row_bits + col * bit_depth;
+                    let byte = image[bit / 8] as usize;
+                    let value = (byte >> (8 - bit_depth - bit % 8)) & max_value;
+                    coverage.push((value * 255 / max_value) as u8);
+                }
+            }
+            BitmapPixels::Coverage(coverage)
+        }
+    };
+    Ok(BitmapGlyph {
+        ppem: strike.ppem,
+        width,
+        height,
+        left: metrics.bearing_x as i32,
+        top: metrics.bearing_y as i32,
+        pixels,
+    })
+}
+
+/// Decodes a PNG into its width, height and RGBA pixels.
+fn decode_png(image: &[u8]) -> Result<(usize, usize, Vec<u8>), CapyError> {
+    let invalid = || CapyError::new(ErrorCode::DataLoss, "bitmap glyph has an invalid PNG");
+    let mut decoder = png::Decoder::new(image);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|_| invalid())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|_| invalid())?;
+    buffer.truncate(info.buffer_size());
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .flat_map(|&gray| [gray, gray, gray, 255])
+            .collect(),
+        png::ColorType::Indexed => return Err(invalid()),
+    };
+    Ok((info.width as usize, info.height as usize, rgba))
+}
+
+fn parse_loca_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+    head_table: &HeadTable,
+    num_glyphs: u16,
+) -> Result<LocaTable, CapyError> {
+    let (loca_offset, loca_length) = lookup_offset_for_tag(TableTag::Loca, font_directory_table)?;
+    // loca has one entry per glyph plus one marking the end of the last glyph, and at most
+    // enough padding to round the table up to four bytes
+    let entry_size = match head_table.index_to_loc_format {
+        0 => 2,
+        _ => 4,
+    };
+    let expected_length = (num_glyphs as usize + 1) * entry_size;
+    if loca_length < expected_length {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "loca table has fewer entries than maxp has glyphs",
+        ));
+    }
+    if loca_length >= expected_length.next_multiple_of(4) + entry_size {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "loca table has more entries than maxp has glyphs",
+        ));
+    }
+    parser.set_offset(loca_offset)?;
+    let mut offsets = Vec::new();
+    for _ in 0..num_glyphs as usize + 1 {
+        let offset = match head_table.index_to_loc_format {
+            // Short offsets are stored divided by two
+            0 => parser.read_be_u16()? as u32 * 2,
+            _ => parser.read_be_u32()?,
+        };
+        offsets.push(offset);
+    }
+    Ok(LocaTable { offsets })
+}
+
+fn parse_glyf_table(
+    font_directory_table: &FontDirectoryTable,
+    loca_table: &LocaTable,
+) -> Result<GlyfTable, CapyError> {
+    let (_, glyf_length) = lookup_offset_for_tag(TableTag::Glyf, font_directory_table)?;
+    if loca_table
+        .offsets
+        .iter()
+        .any(|&offset| offset as usize > glyf_length)
+    {
+        return Err(CapyError::new(
+            ErrorCode::OutOfRange,
+            "loca offset points past the end of the glyf table",
+        ));
+    }
+    let glyph_count = loca_table.offsets.len().saturating_sub(1);
+    Ok(GlyfTable {
+        glyphs: (0..glyph_count).map(|_| OnceLock::new()).collect(),
+    })
+}
+
+/// Parses the glyph spanning `start..end` of the glyf table.
+fn parse_glyph_in_range(glyf: &[u8], start: usize, end: usize) -> Result<GlyfSubtable, CapyError> {
+    if start >= end {
+        // Glyphs without an outline (e.g. space) have an empty loca range
+        return Ok(GlyfSubtable::empty());
+    }
+    let mut parser = ByteParser::new(glyf);
+    parser.set_offset(start)?;
+    let glyph = parse_glyph_subtable(&mut parser, end)?;
+    // A glyph whose data runs past its loca range would have read into the next glyph
+    if parser.position() > end {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "glyph data extends past its loca range",
+        ));
+    }
+    Ok(glyph)
+}
+
+/// Parses the glyph at the parser's position, which must end by `glyph_end`.
+fn parse_glyph_subtable(
+    parser: &mut ByteParser,
+    glyph_end: usize,
+) -> Result<GlyfSubtable, CapyError> {
+    let number_of_contours = parser.read_be_i16()?;
+    let x_min = parser.read_be_i16()?;
+    let y_min = parser.read_be_i16()?;
+    let x_max = parser.read_be_i16()?;
+    let y_max = parser.read_be_i16()?;
+
+    let mut end_pts_of_contours = Vec::new();
+    for _ in 0..number_of_contours {
+        end_pts_of_contours.push(parser.read_be_u16()?);
+    }
+
+    if number_of_contours <= 0 {
+        let components = match number_of_contours {
+            0 => Vec::new(),
+            _ => parse_glyph_components(parser)?,
+        };
+        return Ok(GlyfSubtable {
+            number_of_contours,
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            components,
+            ..GlyfSubtable::empty()
+        });
+    }
+
+    let instruction_length = parser.read_be_u16()?;
+    // Checked up front, since a corrupt length could otherwise be read as instructions
+    if parser.position() + instruction_length as usize > glyph_end {
+        return Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "glyph instructions extend past its loca range",
+        ));
+    }
+    let mut instructions = Vec::new();
+    for _ in 0..instruction_length {
+        instructions.push(parser.read_be_u8()?);
+    }
+
+    let num_points = end_pts_of_contours[number_of_contours as usize - 1]
+        .checked_add(1)
+        .ok_or(CapyError::new(
+            ErrorCode::DataLoss,
+            "glyph has more than 65535 points",
+        ))?;
+    let mut flags = Vec::new();
+    let mut i = 0;
+    while i < num_points {
+        let flag = parser.read_be_u8()?;
+        flags.push(flag);
+        if flag & 0x08 != 0 {
+            let repeat_count = parser.read_be_u8()?;
+            for _ in 0..repeat_count {
+                flags.push(flag);
+                i += 1;
+            }
+        }
+        i += 1;
+    }
+
+    let mut x_coordinates = Vec::new();
+    let mut y_coordinates = Vec::new();
+    let mut x: i16 = 0;
+    let mut y: i16 = 0;
+
+    // Coordinates wrap rather than overflow on corrupt deltas; such glyphs are rejected once
+    // their data is found to run past the loca range
+    for flag in &flags {
+        if flag & 0x02 != 0 {
+            let dx = parser.read_be_u8()?;
+            x = x.wrapping_add(if flag & 0x10 != 0 {
+                dx as i16
+            } else {
+                -(dx as i16)
+            });
+        } else if flag & 0x10 == 0 {
+            x = x.wrapping_add(parser.read_be_i16()?);
+        }
+        x_coordinates.push(x);
+    }
+
+    for flag in &flags {
+        if flag & 0x04 != 0 {
+            let dy = parser.read_be_u8()?;
+            y = y.wrapping_add(if flag & 0x20 != 0 {
+                dy as i16
+            } else {
+                -(dy as i16)
+            });
+        } else if flag & 0x20 == 0 {
+            y = y.wrapping_add(parser.read_be_i16()?);
+        }
+        y_coordinates.push(y);
+    }
+
+    Ok(GlyfSubtable {
+        number_of_contours,
+        x_min,
+        y_min,
+        x_max,
+        y_max,
+        end_pts_of_contours,
+        instruction_length,
+        instructions,
+        flags,
+        x_coordinates,
+        y_coordinates,
+        components: Vec::new(),
+    })
+}
+
+/// Parses the component records of a composite glyph. Their instructions are not read.
+fn parse_glyph_components(parser: &mut ByteParser) -> Result<Vec<GlyphComponent>, CapyError> {
+    let read_f2dot14 = |parser: &mut ByteParser| -> Result<f32, CapyError> {
+        Ok(parser.read_be_i16()? as f32 / 16384.0)
+    };
+
+    let mut components = Vec::new();
+    loop {
+        let flags = parser.read_be_u16()?;
+        let glyph_index = parser.read_be_u16()?;
+        let are_words = flags & GlyphComponent::ARG_1_AND_2_ARE_WORDS != 0;
+        let are_xy = flags & GlyphComponent::ARGS_ARE_XY_VALUES != 0;
+        // Offsets are signed, point numbers unsigned
+        let (arg1, arg2) = match (are_words, are_xy) {
+            (true, true) => (parser.read_be_i16()? as i32, parser.read_be_i16()? as i32),
+            (true, false) => (parser.read_be_u16()? as i32, parser.read_be_u16()? as i32),
+            (false, true) => (
+                parser.read_be_u8()? as i8 as i32,
+                parser.read_be_u8()? as i8 as i32,
+            ),
+            (false, false) => (parser.read_be_u8()? as i32, parser.read_be_u8()? as i32),
+        };
+
+        let transform = if flags & GlyphComponent::WE_HAVE_A_SCALE != 0 {
+            let scale = read_f2dot14(parser)?;
+            [scale, 0.0, 0.0, scale]
+        } else if flags & GlyphComponent::WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            let xscale = read_f2dot14(parser)?;
+            let yscale = read_f2dot14(parser)?;
+            [xscale, 0.0, 0.0, yscale]
+        } else if flags & GlyphComponent::WE_HAVE_A_TWO_BY_TWO != 0 {
+            [
+                read_f2dot14(parser)?,
+                read_f2dot14(parser)?,
+                read_f2dot14(parser)?,
+                read_f2dot14(parser)?,
+            ]
+        } else {
+            [1.0, 0.0, 0.0, 1.0]
+        };
+
+        components.push(GlyphComponent {
+            glyph_index,
+            flags,
+            arg1,
+            arg2,
+            transform,
+        });
+        if flags & GlyphComponent::MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+fn parse_kern_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+    options: ParseOptions,
+) -> Result<KernTable, CapyError> {
+    let (kern_offset, kern_length) = lookup_offset_for_tag(TableTag::Kern, font_directory_table)?;
+    parser.set_offset(kern_offset)?;
+
+    // Apple's variant of the table widens the version and table count to 32 bits (version
+    // 0x00010000) and uses a different subtable header.
+    let is_apple = parser.read_be_u16()? == 1;
+    let num_subtables = match is_apple {
+        true => {
+            parser.skip(2)?;
+            parser.read_be_u32()?
+        }
+        false => parser.read_be_u16()? as u32,
+    };
+
+    let mut pairs = HashMap::new();
+    let mut class_subtables = Vec::new();
+    let mut skipped_subtables = Vec::new();
+    let mut subtable_offset = parser.position();
+    for _ in 0..num_subtables {
+        parser.set_offset(subtable_offset)?;
+        let (length, coverage, format, is_horizontal) = match is_apple {
+            true => {
+                let length = parser.read_be_u32()? as usize;
+                let coverage = parser.read_be_u16()?;
+                let _tuple_index = parser.read_be_u16()?;
+                // Skip vertical, cross-stream and variation subtables
+                (length, coverage, coverage & 0xFF, coverage & 0xE000 == 0)
+            }
+            false => {
+                let _version = parser.read_be_u16()?;
+                let length = parser.read_be_u16()? as usize;
+                let coverage = parser.read_be_u16()?;
+                // Horizontal, and neither minimum values nor cross-stream
+                (length, coverage, coverage >> 8, coverage & 0x0007 == 0x0001)
+            }
+        };
+
+        match (format, is_horizontal) {
+            (0, true) => {
+                let num_pairs = parser.read_be_u16()?;
+                let _search_range = parser.read_be_u16()?;
+                let _entry_selector = parser.read_be_u16()?;
+                let _range_shift = parser.read_be_u16()?;
+                // Subtable lengths are often wrong in fonts with many pairs, since they overflow
+                // 16 bits, so only the table's end bounds the pairs
+                let pairs_that_fit =
+                    (kern_offset + kern_length).saturating_sub(parser.position()) / 6;
+                let num_pairs = match num_pairs as usize > pairs_that_fit {
+                    true if options.strict => {
+                        return Err(CapyError::new(
+                            ErrorCode::DataLoss,
+                            "kern subtable has more pairs than fit in the table",
+                        ))
+                    }
+                    true => pairs_that_fit,
+                    false => num_pairs as usize,
+                };
+                for _ in 0..num_pairs {
+                    let left = parser.read_be_u16()?;
+                    let right = parser.read_be_u16()?;
+                    let value = parser.read_be_i16()?;
+                    let kerning = pairs.entry((left, right)).or_insert(0i16);
+                    *kerning = kerning.saturating_add(value);
+                }
+            }
+            (2, true) => {
+                class_subtables.push(parse_kern_class_subtable(parser, subtable_offset, length)?)
+            }
+            _ => skipped_subtables.push(coverage),
+        }
+        subtable_offset += length;
+    }
+    Ok(KernTable {
+        pairs,
+        class_subtables,
+        skipped_subtables,
+    })
+}
+
+/// Parses the body of a format 2 kern subtable, with `parser` just past the subtable header.
+fn parse_kern_class_subtable(
+    parser: &mut ByteParser,
+    subtable_offset: usize,
+    length: usize,
+) -> Result<KernClassSubtable, CapyError> {
+    let _row_width = parser.read_be_u16()?;
+    let left_class_offset = parser.read_be_u16()?;
+    let right_class_offset = parser.read_be_u16()?;
+    let array_offset = parser.read_be_u16()?;
+
+    let mut read_class_table = |offset: u16| -> Result<KernClassTable, CapyError> {
+        parser.set_offset(subtable_offset + offset as usize)?;
+        let first_glyph = parser.read_be_u16()?;
+        let num_glyphs = parser.read_be_u16()?;
+        let classes = (0..num_glyphs)
+            .map(|_| parser.read_be_u16())
+            .collect::<Result<_, _>>()?;
+        Ok(KernClassTable {
+            first_glyph,
+            classes,
+        })
+    };
+    let left_classes = read_class_table(left_class_offset)?;
+    let right_classes = read_class_table(right_class_offset)?;
+
+    // The array has no length of its own and runs to the end of the subtable
+    parser.set_offset(subtable_offset + array_offset as usize)?;
+    let num_values = length.saturating_sub(array_offset as usize) / 2;
+    let values = (0..num_values)
+        .map(|_| parser.read_be_i16())
+        .collect::<Result<_, _>>()?;
+    Ok(KernClassSubtable {
+        left_classes,
+        right_classes,
+        array_offset,
+        values,
+    })
+}
+
+fn parse_gpos_table(
+    parser: &mut ByteParser,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<GposTable, CapyError> {
+    const KERN_FEATURE_TAG: u32 = u32::from_be_bytes(*b"kern");
+    const PAIR_ADJUSTMENT_LOOKUP: u16 = 2;
+    const EXTENSION_LOOKUP: u16 = 9;
+
+    let (gpos_offset, _) = lookup_offset_for_tag(TableTag::Gpos, font_directory_table)?;
+    parser.set_offset(gpos_offset)?;
+    let _major_version = parser.read_be_u16()?;
+    let _minor_version = parser.read_be_u16()?;
+    let _script_list_offset = parser.read_be_u16()?;
+    let feature_list_offset = gpos_offset + parser.read_be_u16()? as usize;
+    let lookup_list_offset = gpos_offset + parser.read_be_u16()? as usize;
+
+    // Collect the lookups referenced by every 'kern' feature
+    parser.set_offset(feature_list_offset)?;
+    let feature_count = parser.read_be_u16()?;
+    let mut kern_feature_offsets = Vec::new();
+    for _ in 0..feature_count {
+        let tag = parser.read_be_u32()?;
+        let feature_offset = feature_list_offset + parser.read_be_u16()? as usize;
+        if tag == KERN_FEATURE_TAG {
+            kern_feature_offsets.push(feature_offset);
+        }
+    }
+    let mut lookup_indices = Vec::new();
+    for feature_offset in kern_feature_offsets {
+        parser.set_offset(feature_offset)?;
+        let _feature_params_offset = parser.read_be_u16()?;
+        let lookup_index_count = parser.read_be_u16()?;
+        for _ in 0..lookup_index_count {
+            lookup_indices.push(parser.read_be_u16()?);
+        }
+    }
+    // Lookups are applied in lookup list order, whichever features reference them
+    lookup_indices.sort_unstable();
+    lookup_indices.dedup();
+
+    parser.set_offset(lookup_list_offset)?;
+    let lookup_count = parser.read_be_u16()?;
+    let mut lookup_offsets = Vec::new();
+    for _ in 0..lookup_count {
+        lookup_offsets.push(lookup_list_offset + parser.read_be_u16()? as usize);
+    }
+
+    let mut lookups = Vec::new();
+    for lookup_index in lookup_indices {
+        let lookup_offset = *lookup_offsets
+            .get(lookup_index as usize)
+            .ok_or(CapyError::new(
+                ErrorCode::OutOfRange,
+                "GPOS feature references a lookup that doesn't exist",
+            ))?;
+        parser.set_offset(lookup_offset)?;
+        let lookup_type = parser.read_be_u16()?;
+        let _lookup_flag = parser.read_be_u16()?;
+        let subtable_count = parser.read_be_u16()?;
+        let mut subtable_offsets = Vec::new();
+        for _ in 0..subtable_count {
+            subtable_offsets.push(lookup_offset + parser.read_be_u16()? as usize);
+        }
+
+        let mut subtables = Vec::new();
+        for mut subtable_offset in subtable_offsets {
+            let mut subtable_type = lookup_type;
+            if lookup_type == EXTENSION_LOOKUP {
+                // Extension subtables wrap the real subtable behind a 32-bit offset
+                parser.set_offset(subtable_offset)?;
+                let _pos_format = parser.read_be_u16()?;
+                subtable_type = parser.read_be_u16()?;
+                subtable_offset += parser.read_be_u32()? as usize;
+            }
+            if subtable_type == PAIR_ADJUSTMENT_LOOKUP {
+                // Like the kern table, skip subtables in formats we can't read and keep the rest
+                match parse_pair_adjustment(parser, subtable_offset) {
+                    Ok(subtable) => subtables.push(subtable),
+                    Err(e) if e.code() == ErrorCode::Unimplemented => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if !subtables.is_empty() {
+            lookups.push(subtables);
+        }
+    }
+    Ok(GposTable { lookups })
+}
+
+fn parse_pair_adjustment(
+    parser: &mut ByteParser,
+    subtable_offset: usize,
+) -> Result<PairAdjustment, CapyError> {
+    parser.set_offset(subtable_offset)?;
+    let pos_format = parser.read_be_u16()?;
+    let coverage_offset = subtable_offset + parser.read_be_u16()? as usize;
+    let value_format1 = parser.read_be_u16()?;
+    let value_format2 = parser.read_be_u16()?;
+
+    match pos_format {
+        1 => {
+            let pair_set_count = parser.read_be_u16()?;
+            let mut pair_set_offsets = Vec::new();
+            for _ in 0..pair_set_count {
+                pair_set_offsets.push(subtable_offset + parser.read_be_u16()? as usize);
+            }
+            let mut pair_sets = Vec::new();
+            for pair_set_offset in pair_set_offsets {
+                parser.set_offset(pair_set_offset)?;
+                let pair_value_count = parser.read_be_u16()?;
+                let mut pair_set = Vec::new();
+                for _ in 0..pair_value_count {
+                    let second_glyph = parser.read_be_u16()?;
+                    let x_advance = read_value_record(parser, value_format1)?;
+                    read_value_record(parser, value_format2)?;
+                    pair_set.push((second_glyph, x_advance));
+                }
+                pair_sets.push(pair_set);
+            }
+            Ok(PairAdjustment::Pairs {
+                coverage: parse_coverage(parser, coverage_offset)?,
+                pair_sets,
+            })
+        }
+        2 => {
+            let class_def1_offset = subtable_offset + parser.read_be_u16()? as usize;
+            let class_def2_offset = subtable_offset + parser.read_be_u16()? as usize;
+            let class1_count = parser.read_be_u16()?;
+            let class2_count = parser.read_be_u16()?;
+            let mut x_advances = Vec::new();
+            for _ in 0..class1_count as usize * class2_count as usize {
+                x_advances.push(read_value_record(parser, value_format1)?);
+                read_value_record(parser, value_format2)?;
+            }
+            Ok(PairAdjustment::Classes {
+                coverage: parse_coverage(parser, coverage_offset)?,
+                class_def1: parse_class_def(parser, class_def1_offset)?,
+                class_def2: parse_class_def(parser, class_def2_offset)?,
+                class2_count,
+                x_advances,
+            })
+        }
+        _ => Err(CapyError::new(
+            ErrorCode::Unimplemented,
+            "unsupported GPOS pair adjustment format",
+        )),
+    }
+}
+
+/// Reads a GPOS ValueRecord and returns its XAdvance, the only field kerning uses.
+fn read_value_record(parser: &mut ByteParser, value_format: u16) -> Result<i16, CapyError> {
+    const X_ADVANCE: u16 = 0x0004;
+
+    let mut x_advance = 0;
+    for bit in 0..8 {
+        let field = 1 << bit;
+        if value_format & field != 0 {
+            let value = parser.read_be_i16()?;
+            if field == X_ADVANCE {
+                x_advance = value;
+            }
+        }
+    }
+    Ok(x_advance)
+}
+
+fn parse_coverage(parser: &mut ByteParser, offset: usize) -> Result<Coverage, CapyError> {
+    parser.set_offset(offset)?;
+    let format = parser.read_be_u16()?;
+    let count = parser.read_be_u16()?;
+    match format {
+        1 => {
+            let mut glyphs = Vec::new();
+            for _ in 0..count {
+                glyphs.push(parser.read_be_u16()?);
+            }
+            Ok(Coverage::Glyphs(glyphs))
+        }
+        2 => Ok(Coverage::Ranges(parse_range_records(parser, count)?)),
+        _ => Err(CapyError::new(
+            ErrorCode::Unimplemented,
+            "unsupported coverage table format",
+        )),
+    }
+}
+
+fn parse_class_def(parser: &mut ByteParser, offset: usize) -> Result<ClassDef, CapyError> {
+    parser.set_offset(offset)?;
+    let format = parser.read_be_u16()?;
+    match format {
+        1 => {
+            let start_glyph_id = parser.read_be_u16()?;
+            let glyph_count = parser.read_be_u16()?;
+            let mut classes = Vec::new();
+            for _ in 0..glyph_count {
+                classes.push(parser.read_be_u16()?);
+            }
+            Ok(ClassDef::Glyphs {
+                start_glyph_id,
+                classes,
+            })
+        }
+        2 => {
+            let range_count = parser.read_be_u16()?;
+            Ok(ClassDef::Ranges(parse_range_records(parser, range_count)?))
+        }
+        _ => Err(CapyError::new(
+            ErrorCode::Unimplemented,
+            "unsupported class definition table format",
+        )),
+    }
+}
+
+fn parse_range_records(parser: &mut ByteParser, count: u16) -> Result<Vec<RangeRecord>, CapyError> {
+    let mut ranges = Vec::new();
+    for _ in 0..count {
+        ranges.push(RangeRecord {
+            start_glyph_id: parser.read_be_u16()?,
+            end_glyph_id: parser.read_be_u16()?,
+            value: parser.read_be_u16()?,
+        });
+    }
+    Ok(ranges)
+}
+
+/// Sums a table as big-endian u32 words, zero-padding the last one. The head table's
+/// checkSumAdjustment is counted as zero, since it is computed after the checksums.
+fn table_checksum(tag: u32, table: &[u8]) -> u32 {
+    const CHECK_SUM_ADJUSTMENT_OFFSET: usize = 8;
+    let mut sum = 0u32;
+    for (index, word) in table.chunks(4).enumerate() {
+        if tag == TableTag::Head as u32 && index * 4 == CHECK_SUM_ADJUSTMENT_OFFSET {
+            continue;
+        }
+        let mut bytes = [0; 4];
+        bytes[..word.len()].copy_from_slice(word);
+        sum = sum.wrapping_add(u32::from_be_bytes(bytes));
+    }
+    sum
+}
+
+fn read_file_to_byte_buffer(filepath: &str) -> Result<Vec<u8>, CapyError> {
+    let mut file = std::fs::File::open(filepath)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn has_table(tag: TableTag, font_directory_table: &FontDirectoryTable) -> bool {
+    let desired_tag = tag as u32;
+    font_directory_table
+        .table_directory_subtables
+        .iter()
+        .any(|dir| dir.tag == desired_tag)
+}
+
+/// Returns the offset and length in bytes of a table from the font directory.
+fn lookup_offset_for_tag(
+    tag: TableTag,
+    font_directory_table: &FontDirectoryTable,
+) -> Result<(usize, usize), CapyError> {
+    let desired_tag = tag as u32;
+    let table_dir = font_directory_table
+        .table_directory_subtables
+        .iter()
+        .find(|&dir| dir.tag == desired_tag)
+        .ok_or_else(|| {
+            CapyError::new(ErrorCode::NotFound, "table not found in FontDirectoryTable")
+        })?;
+    Ok((table_dir.offset as usize, table_dir.length as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARIAL: &[u8] = include_bytes!("../assets/fonts/arial.ttf");
+
+    fn parse(bytes: &[u8]) -> Result<Font, CapyError> {
+        parse_from_bytes(bytes.to_vec(), ParseOptions::default())
+    }
+
+    const STRICT: ParseOptions = ParseOptions {
+        strict: true,
+        verify_checksums: false,
+    };
+
+    fn arial_glyph(char: char) -> u16 {
+        parse(ARIAL).unwrap().glyph_index(char).unwrap()
+    }
+
+    /// The `(tag, data)` of every table in `font`, in directory order.
+    fn tables(font: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        (0..num_tables)
+            .map(|index| {
+                let entry = &font[12 + index * 16..28 + index * 16];
+                let offset = u32::from_be_bytes(entry[8..12].try_into().unwrap()) as usize;
+                let length = u32::from_be_bytes(entry[12..16].try_into().unwrap()) as usize;
+                (
+                    entry[..4].try_into().unwrap(),
+                    font[offset..offset + length].to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    fn table(font: &[u8], tag: &[u8; 4]) -> Vec<u8> {
+        tables(font)
+            .into_iter()
+            .find(|(table_tag, _)| table_tag == tag)
+            .map(|(_, data)| data)
+            .unwrap()
+    }
+
+    /// `font` rebuilt with `tag`'s table replaced by `data`, or added if it has none.
+    fn with_table(font: &[u8], tag: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut tables = tables(font);
+        tables.retain(|(table_tag, _)| table_tag != tag);
+        tables.push((*tag, data.to_vec()));
+        build_font(font, tables)
+    }
+
+    fn without_table(font: &[u8], tag: &[u8; 4]) -> Vec<u8> {
+        let mut tables = tables(font);
+        tables.retain(|(table_tag, _)| table_tag != tag);
+        build_font(font, tables)
+    }
+
+    /// A font with `font`'s scalar type and `tables`, with the directory sorted, aligned and
+    /// checksummed like a conforming font's.
+    fn build_font(font: &[u8], mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+        tables.sort();
+
+        let entry_selector = (tables.len() as u16).ilog2() as u16;
+        let search_range = (1 << entry_selector) * 16;
+        let mut out = font[..4].to_vec();
+        for field in [
+            tables.len() as u16,
+            search_range,
+            entry_selector,
+            tables.len() as u16 * 16 - search_range,
+        ] {
+            out.extend(field.to_be_bytes());
+        }
+        let mut offset = 12 + tables.len() * 16;
+        let mut body = Vec::new();
+        for (tag, data) in &tables {
+            let mut padded = data.clone();
+            padded.resize(data.len().next_multiple_of(4), 0);
+            out.extend(tag);
+            out.extend(table_checksum(u32::from_be_bytes(*tag), data).to_be_bytes());
+            out.extend((offset as u32).to_be_bytes());
+            out.extend((data.len() as u32).to_be_bytes());
+            offset += padded.len();
+            body.extend(padded);
+        }
+        out.extend(body);
+        out
+    }
+
+    /// A name table holding `records` of `(platform, encoding, language, name id, string
+    /// offset, length)`, followed by `storage`.
+    fn name_table(records: &[(u16, u16, u16, u16, u16, u16)], storage: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let storage_offset = 6 + records.len() as u16 * 12;
+        for field in [0, records.len() as u16, storage_offset] {
+            data.extend(field.to_be_bytes());
+        }
+        for &(platform, encoding, language, name_id, offset, length) in records {
+            for field in [platform, encoding, language, name_id, length, offset] {
+                data.extend(field.to_be_bytes());
+            }
+        }
+        data.extend(storage);
+        data
+    }
+
+    fn utf16_be(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    /// A GPOS table whose only feature is 'kern', using one pair adjustment lookup made of
+    /// `subtables`.
+    fn gpos_table(subtables: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        // Header, then an empty script list at 10, the feature list at 12 and the lookup list
+        // at 26
+        for field in [1, 0, 10, 12, 26] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        data.extend(0u16.to_be_bytes());
+        data.extend(1u16.to_be_bytes());
+        data.extend(b"kern");
+        for field in [8, 0, 1, 0] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        for field in [1, 4] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        // The lookup, at 30
+        let mut subtable_offset = 6 + subtables.len() * 2;
+        for field in [2, 0, subtables.len() as u16] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        for subtable in subtables {
+            data.extend((subtable_offset as u16).to_be_bytes());
+            subtable_offset += subtable.len();
+        }
+        for subtable in subtables {
+            data.extend(subtable);
+        }
+        data
+    }
+
+    /// A format 1 pair adjustment subtable moving `right` by `x_advance` after `left`.
+    fn pair_subtable(left: u16, right: u16, x_advance: i16) -> Vec<u8> {
+        // Format, coverage at 18, XAdvance only for the first glyph, one pair set at 12
+        let mut data = Vec::new();
+        for field in [
+            1,
+            18,
+            0x0004,
+            0,
+            1,
+            12,
+            1,
+            right,
+            x_advance as u16,
+            1,
+            1,
+            left,
+        ] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        data
+    }
+
+    /// A Microsoft kern table holding `subtables` of `(coverage, body)`.
+    fn kern_table(subtables: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for field in [0, subtables.len() as u16] {
+            data.extend(u16::to_be_bytes(field));
+        }
+        for (coverage, body) in subtables {
+            for field in [0, 6 + body.len() as u16, *coverage] {
+                data.extend(u16::to_be_bytes(field));
+            }
+            data.extend(body);
+        }
+        data
+    }
+
+    /// The body of a format 0 kern subtable with a single pair.
+    fn kern_pair_body(left: u16, right: u16, value: i16) -> Vec<u8> {
+        [1, 6, 0, 0, left, right, value as u16]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect()
+    }
+
+    /// Arial without GPOS, so kerning comes from `kern` alone.
+    fn arial_with_kern_table(kern: &[u8]) -> Font {
+        parse(&with_table(&without_table(ARIAL, b"GPOS"), b"kern", kern)).unwrap()
+    }
+
+    #[test]
+    fn kern_format_2_subtable_looks_up_classes() {
+        let (a, v) = (arial_glyph('A'), arial_glyph('V'));
+        // Offsets count from the subtable header. 'A' is the only left class, its row at 26;
+        // 'V' is right class 2, the second value in the row.
+        let body: Vec<u8> = [4, 14, 20, 26, a, 1, 26, v, 1, 2, 0, -77i16 as u16]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        let font = arial_with_kern_table(&kern_table(&[(0x0201, body)]));
+        assert_eq!(font.kerning(a, v), -77);
+        assert_eq!(font.kerning(v, a), 0);
+    }
+
+    #[test]
+    fn summary_names_skipped_kern_subtables() {
+        let (a, v) = (arial_glyph('A'), arial_glyph('V'));
+        let kern = kern_table(&[
+            (0x0001, kern_pair_body(a, v, -50)),
+            // Cross-stream kerning
+            (0x0005, kern_pair_body(a, v, 300)),
+        ]);
+        let font = arial_with_kern_table(&kern);
+        assert_eq!(font.kerning(a, v), -50);
+        assert!(font
+            .summary()
+            .contains("Kerning: kern table (skipped subtables with coverage 0x0005)\n"));
+    }
+
+    #[test]
+    fn unsupported_gpos_pair_format_is_skipped() {
+        let (a, v) = (arial_glyph('A'), arial_glyph('V'));
+        let format_3 = [3u16, 0, 0, 0]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        let gpos = gpos_table(&[format_3, pair_subtable(a, v, -123)]);
+        let font = parse(&with_table(ARIAL, b"GPOS", &gpos)).unwrap();
+        assert_eq!(font.kerning(a, v), -123);
+    }
+
+    #[test]
+    fn x_and_cap_height_come_from_os2_version_2() {
+        let mut os2 = table(ARIAL, b"OS/2");
+        os2[86..90].copy_from_slice(&[0x01, 0x00, 0x02, 0x00]);
+        let font = parse(&with_table(ARIAL, b"OS/2", &os2)).unwrap();
+        assert_eq!((font.x_height(), font.cap_height()), (Some(256), Some(512)));
+    }
+
+    #[test]
+    fn x_and_cap_height_are_measured_without_os2_version_2() {
+        let arial = parse(ARIAL).unwrap();
+        let measured = |char| {
+            let (_, _, _, y_max) = arial
+                .glyph_bounds(arial.glyph_index(char).unwrap())
+                .unwrap();
+            Some(y_max)
+        };
+        // A version 1 table is too short to hold the heights
+        let mut os2 = table(ARIAL, b"OS/2");
+        os2.truncate(86);
+        os2[..2].copy_from_slice(&1u16.to_be_bytes());
+        let font = parse(&with_table(ARIAL, b"OS/2", &os2)).unwrap();
+        assert_eq!(font.x_height(), measured('x'));
+        assert_eq!(font.cap_height(), measured('H'));
+    }
+
+    #[test]
+    fn odd_length_table_padded_before_the_next_parses() {
+        // The Mac Roman "Capy!" leaves the table 33 bytes long, so three bytes of padding
+        // follow it
+        let family = utf16_be("Capy!");
+        let mut name = name_table(&[(PLATFORM_MACINTOSH, 0, 0, NAME_FAMILY, 10, 5)], &family);
+        name.extend(b"Capy!");
+        assert_eq!(name.len() % 4, 1);
+        let bytes = with_table(ARIAL, b"name", &name);
+        let font = parse_from_bytes(
+            bytes,
+            ParseOptions {
+                strict: true,
+                verify_checksums: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(font.family_name(), Some("Capy!"));
+    }
+
+    #[test]
+    fn name_record_past_the_table_end_is_skipped() {
+        let family = utf16_be("Capy");
+        let name = name_table(
+            &[
+                (PLATFORM_WINDOWS, 1, 0x0409, NAME_FAMILY, 1000, 8),
+                (
+                    PLATFORM_WINDOWS,
+                    1,
+                    0x0409,
+                    NAME_FAMILY,
+                    0,
+                    family.len() as u16,
+                ),
+            ],
+            &family,
+        );
+        let font = parse(&with_table(ARIAL, b"name", &name)).unwrap();
+        assert_eq!(font.family_name(), Some("Capy"));
+    }
+
+    #[test]
+    fn truncated_name_record_array_is_ignored() {
+        let mut name = name_table(&[(PLATFORM_WINDOWS, 1, 0x0409, NAME_FAMILY, 0, 0)], &[]);
+        // Claim a second record that isn't there
+        name[3] = 2;
+        let font = parse(&with_table(ARIAL, b"name", &name)).unwrap();
+        assert_eq!(font.family_name(), Some(""));
+    }
+
+    #[test]
+    fn overlong_kern_pair_count_is_clamped_unless_strict() {
+        let (a, v) = (arial_glyph('A'), arial_glyph('V'));
+        let mut body = kern_pair_body(a, v, -50);
+        // Claim five pairs where only one follows
+        body[1] = 5;
+        let bytes = with_table(
+            &without_table(ARIAL, b"GPOS"),
+            b"kern",
+            &kern_table(&[(1, body)]),
+        );
+        assert_eq!(parse(&bytes).unwrap().kerning(a, v), -50);
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn more_hmetrics_than_glyphs_are_clamped_unless_strict() {
+        let arial = parse(ARIAL).unwrap();
+        let mut hhea = table(ARIAL, b"hhea");
+        hhea[34..36].copy_from_slice(&(arial.num_glyphs() + 10).to_be_bytes());
+        let bytes = with_table(ARIAL, b"hhea", &hhea);
+        let font = parse(&bytes).unwrap();
+        for glyph_id in [0, 1, arial.num_glyphs() - 1] {
+            assert_eq!(font.advance_width(glyph_id), arial.advance_width(glyph_id));
+        }
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn truncated_hmtx_shares_its_last_metric_unless_strict() {
+        let arial = parse(ARIAL).unwrap();
+        let mut hmtx = table(ARIAL, b"hmtx");
+        hmtx.truncate(100 * 4);
+        let bytes = with_table(ARIAL, b"hmtx", &hmtx);
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.advance_width(50), arial.advance_width(50));
+        assert_eq!(font.advance_width(150), arial.advance_width(99));
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn from_reader_honours_parse_options() {
+        let mut bytes = ARIAL.to_vec();
+        let post = table(ARIAL, b"post");
+        let offset = ARIAL
+            .windows(post.len())
+            .position(|window| window == post)
+            .unwrap();
+        bytes[offset + 16] ^= 0xFF;
+        let reader = || std::io::Cursor::new(bytes.clone());
+        assert!(Font::from_reader(reader(), ParseOptions::default()).is_ok());
+        let checked = ParseOptions {
+            strict: false,
+            verify_checksums: true,
+        };
+        let err = Font::from_reader(reader(), checked).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    fn is_parsed<T>(table: &LazyTable<T>) -> bool {
+        table.table.get().is_some()
+    }
+
+    fn parsed_glyphs(font: &Font) -> usize {
+        font.glyf_table
+            .glyphs
+            .iter()
+            .filter(|slot| slot.get().is_some())
+            .count()
+    }
+
+    #[test]
+    fn listing_family_names_parses_only_the_name_table() {
+        let font = parse(ARIAL).unwrap();
+        assert_eq!(font.family_name(), Some("Arial"));
+        assert!(is_parsed(&font.name_table));
+        let others = [
+            is_parsed(&font.kern_table),
+            is_parsed(&font.gpos_table),
+            is_parsed(&font.vmtx_table),
+            is_parsed(&font.vorg_table),
+            is_parsed(&font.os2_table),
+            is_parsed(&font.post_table),
+            is_parsed(&font.meta_table),
+            is_parsed(&font.gasp_table),
+        ];
+        assert_eq!(others, [false; 8]);
+        assert_eq!(parsed_glyphs(&font), 0);
+
+        font.kerning(arial_glyph('A'), arial_glyph('V'));
+        assert!(is_parsed(&font.gpos_table));
+        assert_eq!(parsed_glyphs(&font), 0);
+    }
+
+    #[test]
+    fn malformed_glyph_is_missing_unless_strict() {
+        let arial = parse(ARIAL).unwrap();
+        let h = arial_glyph('H');
+        let mut glyf = table(ARIAL, b"glyf");
+        let start = arial.loca_table.offsets[h as usize] as usize;
+        let number_of_contours = i16::from_be_bytes([glyf[start], glyf[start + 1]]) as usize;
+        // Instructions that would run into the next glyph
+        let instruction_length = start + 10 + number_of_contours * 2;
+        glyf[instruction_length..instruction_length + 2].copy_from_slice(&[0xFF, 0xFF]);
+        let bytes = with_table(ARIAL, b"glyf", &glyf);
+
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.glyph_bounds(h), None);
+        assert!(font.glyph_bounds(arial_glyph('I')).is_some());
+        assert_eq!(font.validate().unwrap_err().code(), ErrorCode::DataLoss);
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn malformed_optional_table_is_missing_unless_strict() {
+        // Version 1.0, default origin 900, and two metrics out of glyph id order
+        let vorg: Vec<u8> = [1u16, 0, 900, 2, 5, 800, 3, 700]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        let bytes = with_table(ARIAL, b"VORG", &vorg);
+        let font = parse(&bytes).unwrap();
+        // Arial has no vertical metrics to fall back on either
+        assert_eq!(font.vertical_origin_y(5), None);
+        assert_eq!(font.validate().unwrap_err().code(), ErrorCode::DataLoss);
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    /// The segment a linear scan finds: the first whose end code is at or past `code_point`.
+    fn linear_find_segment(table: &CmapFormatFourTable, code_point: u16) -> Option<usize> {
+        table.end_code.iter().position(|&end| code_point <= end)
+    }
+
+    #[test]
+    fn format_4_binary_search_matches_a_linear_scan() {
+        let font = parse(ARIAL).unwrap();
+        let tables = font
+            .cmap_table
+            .subtables
+            .iter()
+            .filter_map(|subtable| match subtable {
+                CmapSubtable::FormatFour(table) => Some(table),
+                _ => None,
+            });
+        let mut checked = 0;
+        for table in tables {
+            for code_point in 0..=u16::MAX {
+                assert_eq!(
+                    table.find_segment(code_point),
+                    linear_find_segment(table, code_point),
+                    "U+{code_point:04X}"
+                );
+            }
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn format_4_binary_search_handles_a_segment_count_that_is_not_a_power_of_two() {
+        // Three segments: 0x20..=0x7E, 0x100..=0x17F and the final 0xFFFF one
+        let table = CmapFormatFourTable {
+            format: 4,
+            length: 0,
+            language: 0,
+            seg_count_x2: 6,
+            search_range: 4,
+            entry_selector: 1,
+            range_shift: 2,
+            end_code: vec![0x7E, 0x17F, 0xFFFF],
+            reserved_pad: 0,
+            start_code: vec![0x20, 0x100, 0xFFFF],
+            id_delta: vec![0xFFE3, 0xFF60, 1],
+            id_range_offset: vec![0, 0, 0],
+            glyph_id_array: Vec::new(),
+            search_fields_valid: true,
+        };
+        for code_point in 0..=u16::MAX {
+            assert_eq!(
+                table.find_segment(code_point),
+                linear_find_segment(&table, code_point),
+                "U+{code_point:04X}"
+            );
+        }
+        assert_eq!(table.glyph_index('A' as u32), Some(0x41 - 0x1D));
+        assert_eq!(table.glyph_index(0x7F), None);
+        assert_eq!(table.glyph_index(0x100), Some(0x100 - 0xA0));
+    }
+
+    #[test]
+    fn outline_at_size_scales_and_flips_to_pixel_extents() {
+        let font = parse(ARIAL).unwrap();
+        let glyph_id = arial_glyph('H');
+        let (x_min, y_min, x_max, y_max) = font.glyph_bounds(glyph_id).unwrap();
+        let scale = font.scale_factor(32.0);
+        let points: Vec<PointF32> = font
+            .outline_at_size(glyph_id, 32.0)
+            .unwrap()
+            .into_iter()
+            .flat_map(|contour| contour.points)
+            .collect();
+        let extent = |coordinate: fn(&PointF32) -> f32| {
+            let values = points.iter().map(coordinate);
+            let min = values.clone().fold(f32::INFINITY, f32::min);
+            (min, values.fold(f32::NEG_INFINITY, f32::max))
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        let (left, right) = extent(|point| point.x);
+        assert!(close(left, x_min as f32 * scale) && close(right, x_max as f32 * scale));
+        // y-up font units become y-down pixels, so the top of the glyph is the smallest y
+        let (top, bottom) = extent(|point| point.y);
+        assert!(close(top, -(y_max as f32) * scale) && close(bottom, -(y_min as f32) * scale));
+        assert!(top < 0.0 && bottom >= 0.0);
+    }
+
+    /// A format 4 subtable mapping 'A'..='C' through the glyph id array to glyphs 10..=12,
+    /// followed by the 0xFFFF sentinel segment.
+    fn format_4_subtable(id_range_offset: u16, length_adjustment: i16) -> Vec<u8> {
+        let glyph_ids = [10u16, 11, 12];
+        let length = (14 + 4 * 4 + 2 + 2 * glyph_ids.len()) as i16 + length_adjustment;
+        let mut fields = vec![4, length as u16, 0, 4, 4, 1, 0];
+        fields.extend([0x43, 0xFFFF, 0, 0x41, 0xFFFF, 0, 1, id_range_offset, 0]);
+        fields.extend(glyph_ids);
+        fields
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn format_4_glyph_id_array_is_sized_by_the_subtable_length() {
+        // Bytes after the subtable must not be read into the glyph id array
+        let mut subtable = format_4_subtable(4, 0);
+        subtable.extend([0xAB; 8]);
+        let table = parse_cmap_format_four(&mut ByteParser::new(&subtable)).unwrap();
+        assert_eq!(table.glyph_id_array, [10, 11, 12]);
+        assert_eq!(table.glyph_index('A' as u32), Some(10));
+        assert_eq!(table.glyph_index('C' as u32), Some(12));
+        assert_eq!(table.glyph_index('D' as u32), None);
+    }
+
+    #[test]
+    fn inconsistent_format_4_subtables_are_out_of_range() {
+        let cases = [
+            // Too short for even the segment arrays
+            format_4_subtable(4, -10),
+            // Glyph id array cut short of the segment's last code
+            format_4_subtable(4, -2),
+            // Offset pointing past the glyph id array
+            format_4_subtable(8, 0),
+        ];
+        for subtable in cases {
+            let err = parse_cmap_format_four(&mut ByteParser::new(&subtable)).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn arial_format_4_lookups_match_the_glyph_order() {
+        let font = parse(ARIAL).unwrap();
+        assert_eq!(font.glyph_index('A'), Some(36));
+        assert_eq!(font.glyph_index('a'), Some(68));
+        assert_eq!(font.glyph_index(' '), Some(3));
+        assert_eq!(font.glyph_index('\u{E000}'), None);
+    }
+
+    #[test]
+    fn raw_table_returns_the_directory_entry_bytes() {
+        let font = parse(ARIAL).unwrap();
+        let head = font.raw_table(*b"head").unwrap();
+        assert_eq!(
+            head.len(),
+            font.table_info(*b"head").unwrap().length as usize
+        );
+        assert_eq!(head, table(ARIAL, b"head"));
+        // Tables without a parser are returned too
+        assert_eq!(font.raw_table(*b"GSUB").unwrap(), table(ARIAL, b"GSUB"));
+        assert!(font.raw_table(*b"zzzz").is_none());
+
+        let font = Font::from_reader(std::io::Cursor::new(ARIAL), ParseOptions::default()).unwrap();
+        assert_eq!(font.raw_table(*b"head").unwrap(), table(ARIAL, b"head"));
+    }
+
+    #[test]
+    fn lookup_offset_for_tag_returns_the_directory_length() {
+        let font = parse(ARIAL).unwrap();
+        for (tag, data) in tables(ARIAL) {
+            let Some(tag) = PARSED_TABLES
+                .iter()
+                .find(|&&parsed| parsed as u32 == u32::from_be_bytes(tag))
+            else {
+                continue;
+            };
+            let (offset, length) = lookup_offset_for_tag(*tag, &font.font_directory_table).unwrap();
+            assert_eq!(length, data.len());
+            assert_eq!(&ARIAL[offset..offset + length], data);
+        }
+    }
+
+    /// A cmap table with `(platform_id, platform_specific_id, offset)` encoding records,
+    /// followed by `subtables`.
+    fn cmap_table(records: &[(u16, u16, u32)], subtables: &[u8]) -> Vec<u8> {
+        let mut cmap = [0u16, records.len() as u16]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect::<Vec<_>>();
+        for &(platform_id, platform_specific_id, offset) in records {
+            cmap.extend(platform_id.to_be_bytes());
+            cmap.extend(platform_specific_id.to_be_bytes());
+            cmap.extend(offset.to_be_bytes());
+        }
+        cmap.extend(subtables);
+        cmap
+    }
+
+    #[test]
+    fn cmap_reads_are_bounded_by_the_cmap_length() {
+        let subtable = format_4_subtable(4, 0);
+        let font = parse(&with_table(
+            ARIAL,
+            b"cmap",
+            &cmap_table(&[(3, 1, 12)], &subtable),
+        ))
+        .unwrap();
+        assert_eq!(font.glyph_index('B'), Some(11));
+
+        let cases = [
+            // Encoding record pointing past the table
+            cmap_table(&[(3, 1, 12 + subtable.len() as u32)], &subtable),
+            // Subtable claiming more bytes than the table has left
+            cmap_table(&[(3, 1, 12)], &format_4_subtable(4, 4)),
+        ];
+        for cmap in cases {
+            let err = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn format_4_lookups_survive_a_corrupted_search_range() {
+        let mut subtable = format_4_subtable(4, 0);
+        // search_range is the fifth field; 0x40 sends the header-driven search past end_code
+        subtable[8..10].copy_from_slice(&0x40u16.to_be_bytes());
+        let table = parse_cmap_format_four(&mut ByteParser::new(&subtable)).unwrap();
+        assert!(!table.search_fields_valid);
+        for code_point in 0..=u16::MAX {
+            assert_eq!(
+                table.find_segment(code_point),
+                linear_find_segment(&table, code_point),
+                "U+{code_point:04X}"
+            );
+        }
+
+        let cmap = cmap_table(&[(3, 1, 12)], &subtable);
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        assert_eq!(font.glyph_index('A'), Some(10));
+        assert_eq!(font.glyph_index('C'), Some(12));
+        assert_eq!(font.glyph_index('D'), None);
+        let err = parse_from_bytes(with_table(ARIAL, b"cmap", &cmap), STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+        let cmap = cmap_table(&[(3, 1, 12)], &format_4_subtable(4, 0));
+        assert!(parse_from_bytes(with_table(ARIAL, b"cmap", &cmap), STRICT).is_ok());
+    }
+
+    /// A vhea table with `num_of_long_ver_metrics` and zeroes elsewhere.
+    fn vhea_table(num_of_long_ver_metrics: u16) -> Vec<u8> {
+        let mut vhea = 0x0001_1000u32.to_be_bytes().to_vec();
+        vhea.resize(34, 0);
+        vhea.extend(num_of_long_ver_metrics.to_be_bytes());
+        vhea
+    }
+
+    #[test]
+    fn vertical_metrics_come_from_vhea_and_vmtx() {
+        let num_glyphs = parse(ARIAL).unwrap().num_glyphs();
+        // Two full metrics, then bearings only for the remaining glyphs
+        let mut vmtx: Vec<u8> = [2048u16, 100, 1024, 200]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        for glyph_id in 2..num_glyphs {
+            vmtx.extend((glyph_id as i16).to_be_bytes());
+        }
+        let font = with_table(ARIAL, b"vhea", &vhea_table(2));
+        let font = parse(&with_table(&font, b"vmtx", &vmtx)).unwrap();
+
+        assert_eq!(font.vertical_advance(0), Some(2048));
+        assert_eq!(font.vertical_advance(1), Some(1024));
+        assert_eq!(font.top_side_bearing(1), Some(200));
+        // Later glyphs share the last advance and have a bearing of their own
+        assert_eq!(font.vertical_advance(num_glyphs - 1), Some(1024));
+        assert_eq!(
+            font.top_side_bearing(num_glyphs - 1),
+            Some(num_glyphs as i16 - 1)
+        );
+
+        let without_vmtx = parse(&with_table(ARIAL, b"vhea", &vhea_table(2))).unwrap();
+        assert_eq!(without_vmtx.vertical_advance(0), None);
+        assert_eq!(parse(ARIAL).unwrap().vertical_advance(0), None);
+    }
+
+    #[test]
+    fn peeking_does_not_advance_the_parser() {
+        let bytes = [0x00, 0x04, 0x12, 0x34, 0xFF];
+        let mut parser = ByteParser::new(&bytes);
+        assert_eq!(parser.peek_be_u16().unwrap(), 4);
+        assert_eq!(parser.peek_be_u16().unwrap(), 4);
+        assert_eq!(parser.position(), 0);
+        assert_eq!(parser.read_be_u16().unwrap(), 4);
+        assert_eq!(parser.peek_be_u16().unwrap(), 0x1234);
+        assert_eq!(parser.position(), 2);
+
+        parser.skip(2).unwrap();
+        assert_eq!(parser.remaining(), 1);
+        let err = parser.peek_be_u16().unwrap_err();
+        assert_eq!(err.code(), ErrorCode::OutOfRange);
+        assert_eq!(parser.remaining(), 1);
+    }
+
+    #[test]
+    fn position_and_remaining_account_for_the_base_offset() {
+        let bytes = [0; 10];
+        let mut parser = ByteParser::with_base(&bytes, 100);
+        assert_eq!((parser.position(), parser.remaining()), (100, 10));
+        parser.set_offset(106).unwrap();
+        assert_eq!((parser.position(), parser.remaining()), (106, 4));
+        parser.set_offset(110).unwrap();
+        assert_eq!(parser.remaining(), 0);
+        assert!(parser.set_offset(99).is_err());
+        assert!(parser.set_offset(111).is_err());
+        assert!(parser.skip(1).is_err());
+    }
+
+    #[test]
+    fn from_reader_matches_parsing_the_whole_buffer() {
+        let buffered = parse(ARIAL).unwrap();
+        let font = Font::from_reader(std::io::Cursor::new(ARIAL), ParseOptions::default()).unwrap();
+        for char in ['a', 'g', 'Q', '&', '@', '\u{E9}'] {
+            let glyph_id = font.glyph_index(char).unwrap();
+            assert_eq!(Some(glyph_id), buffered.glyph_index(char));
+            assert_eq!(
+                font.advance_width(glyph_id),
+                buffered.advance_width(glyph_id)
+            );
+            assert_eq!(font.outline(glyph_id), buffered.outline(glyph_id));
+        }
+        assert_eq!(font.family_name(), buffered.family_name());
+        assert!(font.family_name().is_some());
+        // Tables nothing parses are never read
+        assert!(font.raw_table(*b"GSUB").is_none());
+        assert!(buffered.raw_table(*b"GSUB").is_some());
+    }
+
+    #[test]
+    fn windows_unicode_subtable_is_preferred_over_an_earlier_mac_one() {
+        // With no id_range_offset the Mac subtable maps 'A' to glyph 0x41 instead of 10
+        let mac = format_4_subtable(0, 0);
+        let windows = format_4_subtable(4, 0);
+        let records_size = 4 + 2 * 8;
+        let records = [
+            (1, 0, records_size),
+            (3, 1, records_size + mac.len() as u32),
+        ];
+        let cmap = cmap_table(&records, &[mac.as_slice(), &windows].concat());
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        assert_eq!(font.glyph_index('A'), Some(10));
+        let chosen = font.cmap_encoding().unwrap();
+        assert_eq!((chosen.platform_id, chosen.platform_specific_id), (3, 1));
+        assert_eq!(font.cmap_encodings().len(), 2);
+
+        // Listed the other way round, the choice doesn't change
+        let records = [
+            (3, 1, records_size + mac.len() as u32),
+            (1, 0, records_size),
+        ];
+        let cmap = cmap_table(&records, &[mac.as_slice(), &windows].concat());
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        assert_eq!(font.glyph_index('A'), Some(10));
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn mapped_font_parses_like_the_buffered_one() {
+        let path = "assets/fonts/arial.ttf";
+        let mapped = parse_from_mmap(path, ParseOptions::default()).unwrap();
+        let buffered = parse_from_file(path, ParseOptions::default()).unwrap();
+        assert_eq!(mapped.num_glyphs(), buffered.num_glyphs());
+        assert_eq!(mapped.family_name(), buffered.family_name());
+        for char in ['a', 'g', 'Q', '&', '@'] {
+            let glyph_id = mapped.glyph_index(char).unwrap();
+            assert_eq!(Some(glyph_id), buffered.glyph_index(char));
+            assert_eq!(
+                mapped.advance_width(glyph_id),
+                buffered.advance_width(glyph_id)
+            );
+            assert_eq!(mapped.outline(glyph_id), buffered.outline(glyph_id));
+        }
+        assert_eq!(mapped.raw_table(*b"GSUB"), buffered.raw_table(*b"GSUB"));
+    }
+
+    fn with_scalar_type(scalar_type: &[u8; 4]) -> Vec<u8> {
+        let mut font = ARIAL.to_vec();
+        font[..4].copy_from_slice(scalar_type);
+        font
+    }
+
+    #[test]
+    fn truetype_scalar_types_parse() {
+        for (scalar_type, version) in [
+            (&[0, 1, 0, 0], SfntVersion::TrueType),
+            (b"true", SfntVersion::AppleTrueType),
+        ] {
+            let font = parse(&with_scalar_type(scalar_type)).unwrap();
+            assert_eq!(font.sfnt_version(), version);
+        }
+    }
+
+    #[test]
+    fn other_scalar_types_fail_fast() {
+        for (scalar_type, code) in [
+            (b"OTTO", ErrorCode::Unimplemented),
+            (b"typ1", ErrorCode::Unimplemented),
+            (b"ttcf", ErrorCode::Unimplemented),
+            (b"wOFF", ErrorCode::InvalidArgument),
+        ] {
+            let err = parse(&with_scalar_type(scalar_type)).unwrap_err();
+            assert_eq!(err.code(), code, "{}", String::from_utf8_lossy(scalar_type));
+        }
+    }
+
+    /// A meta table holding `maps` of tag and data, in order.
+    fn meta_table(maps: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut meta = Vec::new();
+        for field in [1, 0, 0, maps.len() as u32] {
+            meta.extend(field.to_be_bytes());
+        }
+        let mut data_offset = 16 + maps.len() * 12;
+        for (tag, data) in maps {
+            meta.extend(*tag);
+            meta.extend((data_offset as u32).to_be_bytes());
+            meta.extend((data.len() as u32).to_be_bytes());
+            data_offset += data.len();
+        }
+        for (_, data) in maps {
+            meta.extend(*data);
+        }
+        meta
+    }
+
+    #[test]
+    fn meta_table_lists_design_and_supported_languages() {
+        let meta = meta_table(&[
+            (b"dlng", b"Latn, Cyrl"),
+            (b"appl", b"ignored"),
+            (b"slng", b"Latn,Grek,,Cyrl,en-Latn"),
+        ]);
+        let font = parse(&with_table(ARIAL, b"meta", &meta)).unwrap();
+        assert_eq!(font.design_languages(), ["Latn", "Cyrl"]);
+        assert_eq!(
+            font.supported_languages(),
+            ["Latn", "Grek", "Cyrl", "en-Latn"]
+        );
+
+        let font = parse(&without_table(ARIAL, b"meta")).unwrap();
+        assert!(font.design_languages().is_empty());
+        assert!(font.supported_languages().is_empty());
+    }
+
+    #[test]
+    fn meta_data_map_outside_the_table_is_rejected_when_strict() {
+        let mut meta = meta_table(&[(b"dlng", b"Latn")]);
+        meta.truncate(meta.len() - 1);
+        let font = with_table(ARIAL, b"meta", &meta);
+        assert!(parse(&font).unwrap().design_languages().is_empty());
+        let err = Font::from_reader(std::io::Cursor::new(font), STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::OutOfRange);
+    }
+
+    #[test]
+    fn content_hash_identifies_the_font_by_its_tables() {
+        let hash = parse(ARIAL).unwrap().content_hash();
+        assert_eq!(parse(ARIAL).unwrap().content_hash(), hash);
+        let from_reader =
+            Font::from_reader(std::io::Cursor::new(ARIAL), ParseOptions::default()).unwrap();
+        assert_eq!(from_reader.content_hash(), hash);
+        // Moving tables around in the file doesn't change the font
+        let rebuilt = build_font(ARIAL, tables(ARIAL));
+        assert_ne!(rebuilt, ARIAL);
+        assert_eq!(parse(&rebuilt).unwrap().content_hash(), hash);
+
+        let mut os2 = table(ARIAL, b"OS/2");
+        os2[4] ^= 1;
+        let changed = parse(&with_table(ARIAL, b"OS/2", &os2)).unwrap();
+        assert_ne!(changed.content_hash(), hash);
+    }
+
+    #[test]
+    fn table_tags_list_the_directory_in_order() {
+        let font = parse(ARIAL).unwrap();
+        let tags = font.table_tags();
+        let expected: Vec<[u8; 4]> = tables(ARIAL).into_iter().map(|(tag, _)| tag).collect();
+        assert_eq!(tags, expected);
+        for tag in [b"cmap", b"head", b"hhea", b"maxp", b"glyf"] {
+            assert!(tags.contains(tag));
+        }
+
+        let head = font.table_info(*b"head").unwrap();
+        assert_eq!(head.length as usize, table(ARIAL, b"head").len());
+        let entry = 12 + 16 * tags.iter().position(|tag| tag == b"head").unwrap();
+        assert_eq!(head.checksum.to_be_bytes(), ARIAL[entry + 4..entry + 8]);
+        assert_eq!(head.offset.to_be_bytes(), ARIAL[entry + 8..entry + 12]);
+        assert!(font.table_info(*b"CFF ").is_none());
+    }
+
+    /// `font` with glyph `glyph_id`'s data in glyf replaced by `data`. The loca table is
+    /// rewritten in the long format.
+    fn with_glyph(font: &[u8], glyph_id: u16, data: &[u8]) -> Vec<u8> {
+        let mut head = table(font, b"head");
+        let loca = table(font, b"loca");
+        let glyf = table(font, b"glyf");
+        let offsets: Vec<usize> = match i16::from_be_bytes([head[50], head[51]]) {
+            0 => loca
+                .chunks(2)
+                .map(|offset| u16::from_be_bytes([offset[0], offset[1]]) as usize * 2)
+                .collect(),
+            _ => loca
+                .chunks(4)
+                .map(|offset| u32::from_be_bytes(offset.try_into().unwrap()) as usize)
+                .collect(),
+        };
+        let mut new_glyf = Vec::new();
+        let mut new_loca = Vec::new();
+        for (index, range) in offsets.windows(2).enumerate() {
+            new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+            match index == glyph_id as usize {
+                true => new_glyf.extend(data),
+                false => new_glyf.extend(&glyf[range[0]..range[1]]),
+            }
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+        new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+        head[50..52].copy_from_slice(&1i16.to_be_bytes());
+
+        let font = with_table(font, b"head", &head);
+        let font = with_table(&font, b"loca", &new_loca);
+        with_table(&font, b"glyf", &new_glyf)
+    }
+
+    /// A composite glyph made of `(flags, glyph id, dx, dy, scale)` components, with word-sized
+    /// xy offsets.
+    fn composite_glyph(components: &[(u16, u16, i16, i16, Option<f32>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for field in [-1i16, 0, 0, 0, 0] {
+            data.extend(field.to_be_bytes());
+        }
+        for (index, &(flags, glyph_id, dx, dy, scale)) in components.iter().enumerate() {
+            let mut flags =
+                flags | GlyphComponent::ARG_1_AND_2_ARE_WORDS | GlyphComponent::ARGS_ARE_XY_VALUES;
+            if index + 1 < components.len() {
+                flags |= GlyphComponent::MORE_COMPONENTS;
+            }
+            if scale.is_some() {
+                flags |= GlyphComponent::WE_HAVE_A_SCALE;
+            }
+            data.extend(flags.to_be_bytes());
+            data.extend(glyph_id.to_be_bytes());
+            data.extend(dx.to_be_bytes());
+            data.extend(dy.to_be_bytes());
+            if let Some(scale) = scale {
+                data.extend(((scale * 16384.0) as i16).to_be_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn use_my_metrics_takes_the_advance_from_that_component() {
+        let (w, period, composite) = (arial_glyph('W'), arial_glyph('.'), arial_glyph('i'));
+        let font = parse(ARIAL).unwrap();
+        assert_ne!(font.advance_width(w), font.advance_width(composite));
+
+        let glyph = composite_glyph(&[
+            (0, period, 0, 0, None),
+            (GlyphComponent::USE_MY_METRICS, w, 0, 0, None),
+        ]);
+        let font = parse(&with_glyph(ARIAL, composite, &glyph)).unwrap();
+        assert_eq!(font.advance_width(composite), font.advance_width(w));
+
+        // Without the flag the composite keeps its own hmtx advance
+        let glyph = composite_glyph(&[(0, period, 0, 0, None), (0, w, 0, 0, None)]);
+        let font = parse(&with_glyph(ARIAL, composite, &glyph)).unwrap();
+        assert_eq!(
+            font.advance_width(composite),
+            parse(ARIAL).unwrap().advance_width(composite)
+        );
+    }
+
+    #[test]
+    fn component_offsets_are_scaled_only_when_asked() {
+        let (base, composite) = (arial_glyph('H'), arial_glyph('i'));
+        let base_outline = parse(ARIAL).unwrap().outline(base).unwrap();
+        let first_point = |flags: u16| {
+            let glyph = composite_glyph(&[(flags, base, 200, 100, Some(0.5))]);
+            let font = parse(&with_glyph(ARIAL, composite, &glyph)).unwrap();
+            let point = &font.outline(composite).unwrap()[0].points[0];
+            (point.x, point.y)
+        };
+        let (x, y) = (
+            base_outline[0].points[0].x as f32 * 0.5,
+            base_outline[0].points[0].y as f32 * 0.5,
+        );
+        let expected = |dx: f32, dy: f32| ((x + dx).round() as i16, (y + dy).round() as i16);
+        assert_eq!(first_point(0), expected(200.0, 100.0));
+        assert_eq!(
+            first_point(GlyphComponent::UNSCALED_COMPONENT_OFFSET),
+            expected(200.0, 100.0)
+        );
+        assert_eq!(
+            first_point(GlyphComponent::SCALED_COMPONENT_OFFSET),
+            expected(100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn kern_pairs_merge_gpos_over_the_kern_table() {
+        let [a, v, t, o, l] = ['A', 'V', 'T', 'o', 'L'].map(arial_glyph);
+        let kern = kern_table(&[
+            (0x0001, kern_pair_body(a, v, -80)),
+            (0x0001, kern_pair_body(t, o, -50)),
+        ]);
+        let gpos = gpos_table(&[pair_subtable(a, v, -100), pair_subtable(l, t, -120)]);
+        let font = with_table(ARIAL, b"kern", &kern);
+        let font = parse(&with_table(&font, b"GPOS", &gpos)).unwrap();
+        let mut expected = vec![(a, v, -100), (t, o, -50), (l, t, -120)];
+        expected.sort();
+        assert_eq!(font.kern_pairs(), expected);
+
+        let font = arial_with_kern_table(&kern);
+        assert_eq!(font.kern_pairs().len(), 2);
+    }
+
+    #[test]
+    fn arial_kern_pairs_match_kerning() {
+        let font = parse(ARIAL).unwrap();
+        let pairs = font.kern_pairs();
+        assert!(pairs.len() > 100);
+        assert!(pairs
+            .windows(2)
+            .all(|pair| (pair[0].0, pair[0].1) < (pair[1].0, pair[1].1)));
+        for &(left, right, value) in &pairs {
+            assert_ne!(value, 0);
+            assert_eq!(font.kerning(left, right), value);
+        }
+        let (a, v) = (arial_glyph('A'), arial_glyph('V'));
+        let av = pairs
+            .iter()
+            .find(|&&(left, right, _)| (left, right) == (a, v));
+        assert!(av.is_some_and(|&(_, _, value)| value < 0));
+    }
+
+    /// `font` with maxp claiming `num_glyphs` glyphs.
+    fn with_num_glyphs(font: &[u8], num_glyphs: u16) -> Vec<u8> {
+        let mut maxp = table(font, b"maxp");
+        maxp[4..6].copy_from_slice(&num_glyphs.to_be_bytes());
+        with_table(font, b"maxp", &maxp)
+    }
+
+    #[test]
+    fn maxp_and_loca_must_agree_on_the_glyph_count() {
+        let num_glyphs = parse(ARIAL).unwrap().num_glyphs();
+        for (num_glyphs, message) in [
+            (num_glyphs + 1, "fewer entries"),
+            (num_glyphs - 2, "more entries"),
+        ] {
+            let err = parse(&with_num_glyphs(ARIAL, num_glyphs)).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::DataLoss);
+            assert!(err.to_string().contains(message), "{err}");
+        }
+    }
+
+    /// A closed contour through on-curve `points`.
+    fn square(points: [(i16, i16); 4]) -> Contour {
+        Contour {
+            points: points
+                .iter()
+                .map(|&(x, y)| Point {
+                    x,
+                    y,
+                    on_curve: true,
+                })
+                .collect(),
+        }
+    }
+
+    /// Coverage of `contours` at one pixel per font unit under the non-zero rule.
+    fn fill(contours: &[Contour]) -> crate::rasterizer::CoverageBuffer {
+        use crate::rasterizer::{bounding_box, flatten, Rasterizer, SupersampleRasterizer};
+        let contours: Vec<ContourF32> = contours
+            .iter()
+            .map(|contour| ContourF32 {
+                points: contour
+                    .points
+                    .iter()
+                    .map(|point| PointF32 {
+                        x: point.x as f32,
+                        y: -point.y as f32,
+                        on_curve: true,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let segments = flatten(&contours);
+        SupersampleRasterizer.fill(&segments, &bounding_box(&segments))
+    }
+
+    #[test]
+    fn hole_with_reversed_winding_is_left_empty_after_normalization() {
+        // Both squares run counter-clockwise, so the non-zero rule fills the hole
+        let mut contours = vec![
+            square([(0, 0), (12, 0), (12, 12), (0, 12)]),
+            square([(4, 4), (8, 4), (8, 8), (4, 8)]),
+        ];
+        let (ring, hole) = ((1, 1), (6, 6));
+        let coverage = fill(&contours);
+        assert_eq!(coverage.get(hole.0, hole.1), 1.0);
+
+        normalize_winding(&mut contours);
+        assert!(contours[0].signed_area() < 0);
+        assert!(contours[1].signed_area() > 0);
+        let coverage = fill(&contours);
+        assert_eq!(coverage.get(ring.0, ring.1), 1.0);
+        assert_eq!(coverage.get(hole.0, hole.1), 0.0);
+    }
+
+    #[test]
+    fn platform_encoding_pairs_map_to_their_meaning() {
+        let cases = [
+            ((0, 3), CmapPlatformEncoding::UnicodeBmp),
+            ((0, 4), CmapPlatformEncoding::UnicodeFull),
+            ((0, 6), CmapPlatformEncoding::UnicodeFull),
+            ((3, 0), CmapPlatformEncoding::WindowsSymbol),
+            ((3, 1), CmapPlatformEncoding::WindowsUcs2),
+            ((3, 10), CmapPlatformEncoding::WindowsUcs4),
+            ((1, 0), CmapPlatformEncoding::MacRoman),
+        ];
+        for ((platform_id, platform_specific_id), expected) in cases {
+            assert_eq!(
+                CmapPlatformEncoding::from_ids(platform_id, platform_specific_id),
+                expected
+            );
+        }
+        assert_eq!(
+            CmapPlatformEncoding::from_ids(3, 2),
+            CmapPlatformEncoding::Other {
+                platform_id: 3,
+                platform_specific_id: 2
+            }
+        );
+    }
+
+    #[test]
+    fn full_unicode_subtable_is_chosen_among_several() {
+        let subtable = format_4_subtable(4, 0);
+        let records_size = 4 + 5 * 8;
+        let pairs = [(1, 0), (3, 0), (0, 3), (3, 10), (3, 1)];
+        let records: Vec<(u16, u16, u32)> = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, &(platform_id, platform_specific_id))| {
+                let offset = records_size + (i * subtable.len()) as u32;
+                (platform_id, platform_specific_id, offset)
+            })
+            .collect();
+        let cmap = cmap_table(&records, &subtable.repeat(pairs.len()));
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        let chosen = font.cmap_encoding().unwrap();
+        assert_eq!(
+            chosen.platform_encoding(),
+            CmapPlatformEncoding::WindowsUcs4
+        );
+        assert_eq!(font.glyph_index('B'), Some(11));
+    }
+
+    /// A one-point simple glyph with `instruction_length` in its header but a single
+    /// instruction byte, 16 bytes in all.
+    fn glyph_with_instruction_length(instruction_length: u16) -> Vec<u8> {
+        let mut glyph: Vec<u8> = [1i16, 0, 0, 0, 0, 0]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        glyph.extend(instruction_length.to_be_bytes());
+        // One instruction byte, then a flag for an on-curve point at the origin
+        glyph.extend([0xB0, 0x31]);
+        glyph
+    }
+
+    #[test]
+    fn instructions_overflowing_the_glyph_slot_are_rejected() {
+        let h = arial_glyph('H');
+        let font = parse(&with_glyph(ARIAL, h, &glyph_with_instruction_length(1))).unwrap();
+        assert!(font.glyph_bounds(h).is_some());
+
+        // Three bytes of instructions would end one byte into the next glyph
+        let bytes = with_glyph(ARIAL, h, &glyph_with_instruction_length(3));
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.glyph_bounds(h), None);
+        assert!(font.glyph_bounds(arial_glyph('I')).is_some());
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        assert!(
+            err.to_string().contains("instructions extend past"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn end_point_0xffff_is_data_loss_not_an_overflow() {
+        let h = arial_glyph('H');
+        // One contour whose last end point would make the point count 0x10000
+        let mut glyph: Vec<u8> = [1i16, 0, 0, 0, 0, -1, 0]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        glyph.push(0x31);
+        let bytes = with_glyph(ARIAL, h, &glyph);
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.glyph_bounds(h), None);
+        assert!(font.glyph_bounds(arial_glyph('I')).is_some());
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        assert!(err.to_string().contains("65535 points"), "{err}");
+    }
+
+    /// A format 0 subtable mapping 'A' to glyph 7.
+    fn format_0_subtable() -> Vec<u8> {
+        let mut subtable = [0u16, 262, 0]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect::<Vec<_>>();
+        let mut glyph_ids = [0u8; 256];
+        glyph_ids[b'A' as usize] = 7;
+        subtable.extend(glyph_ids);
+        subtable
+    }
+
+    #[test]
+    fn active_subtable_has_the_expected_format() {
+        let font = parse(ARIAL).unwrap();
+        assert_eq!(font.cmap_table.active_subtable().unwrap().format(), 4);
+
+        let records_size = 4 + 8;
+        let cmap = cmap_table(&[(1, 0, records_size)], &format_0_subtable());
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        let active = font.cmap_table.active_subtable().unwrap();
+        assert!(matches!(active, CmapSubtable::FormatZero(_)));
+        assert_eq!(font.glyph_index('A'), Some(7));
+
+        // A format 12 subtable we can't decode is passed over for the BMP one
+        let records_size = 4 + 2 * 8;
+        let format_12 = [0, 12, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0];
+        let format_4 = format_4_subtable(4, 0);
+        let records = [(3, 10, records_size), (3, 1, records_size + 16)];
+        let cmap = cmap_table(&records, &[format_12.as_slice(), &format_4].concat());
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        let active = font.cmap_table.active_subtable().unwrap();
+        assert!(matches!(active, CmapSubtable::FormatFour(_)));
+        assert_eq!(font.cmap_encodings()[0].format, 12);
+    }
+
+    /// A format 14 subtable: U+FE0E gives 'A' through 'C' their default glyphs, and U+FE0F
+    /// maps 'A' to glyph 99.
+    fn format_14_subtable() -> Vec<u8> {
+        let u24 = |value: u32| value.to_be_bytes()[1..].to_vec();
+        let mut subtable = 14u16.to_be_bytes().to_vec();
+        subtable.extend(49u32.to_be_bytes());
+        subtable.extend(2u32.to_be_bytes());
+        for (selector, default_offset, non_default_offset) in
+            [(0xFE0E, 32u32, 0u32), (0xFE0F, 0, 40)]
+        {
+            subtable.extend(u24(selector));
+            subtable.extend(default_offset.to_be_bytes());
+            subtable.extend(non_default_offset.to_be_bytes());
+        }
+        // Default UVS table: one range starting at 'A' with two more characters
+        subtable.extend(1u32.to_be_bytes());
+        subtable.extend(u24('A' as u32));
+        subtable.push(2);
+        // Non-default UVS table
+        subtable.extend(1u32.to_be_bytes());
+        subtable.extend(u24('A' as u32));
+        subtable.extend(99u16.to_be_bytes());
+        subtable
+    }
+
+    #[test]
+    fn variation_sequences_are_looked_up_in_the_format_14_subtable() {
+        let format_4 = format_4_subtable(4, 0);
+        let format_14 = format_14_subtable();
+        assert_eq!(format_14.len(), 49);
+        let records_size = 4 + 2 * 8;
+        let records = [
+            (0, 5, records_size),
+            (3, 1, records_size + format_14.len() as u32),
+        ];
+        let cmap = cmap_table(&records, &[format_14.as_slice(), &format_4].concat());
+        let font = parse(&with_table(ARIAL, b"cmap", &cmap)).unwrap();
+        assert_eq!(font.glyph_index('A'), Some(10));
+
+        assert_eq!(font.glyph_for_variation('A', '\u{FE0E}'), Some(10));
+        assert_eq!(font.glyph_for_variation('C', '\u{FE0E}'), Some(12));
+        assert_eq!(font.glyph_for_variation('D', '\u{FE0E}'), None);
+        assert_eq!(font.glyph_for_variation('A', '\u{FE0F}'), Some(99));
+        assert_eq!(font.glyph_for_variation('B', '\u{FE0F}'), None);
+        assert_eq!(font.glyph_for_variation('A', '\u{FE00}'), None);
+
+        // Without a format 14 subtable no sequence is supported
+        let font = parse(ARIAL).unwrap();
+        assert_eq!(font.glyph_for_variation('A', '\u{FE0E}'), None);
+    }
+
+    fn gasp_table(version: u16, ranges: &[(u16, u16)]) -> Vec<u8> {
+        let mut fields = vec![version, ranges.len() as u16];
+        fields.extend(
+            ranges
+                .iter()
+                .flat_map(|&(max_ppem, behavior)| [max_ppem, behavior]),
+        );
+        fields
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn gasp_behavior_comes_from_the_range_covering_the_size() {
+        let flags = |gridfit, grayscale, symmetric| GaspFlags {
+            gridfit,
+            grayscale,
+            symmetric_gridfit: symmetric,
+            symmetric_smoothing: symmetric,
+        };
+        let ranges = [(8, 0x0002), (16, 0x0001), (0xFFFF, 0x000F)];
+        let font = parse(&with_table(ARIAL, b"gasp", &gasp_table(1, &ranges))).unwrap();
+        assert_eq!(font.gasp_behavior(6), flags(false, true, false));
+        assert_eq!(font.gasp_behavior(8), flags(false, true, false));
+        assert_eq!(font.gasp_behavior(12), flags(true, false, false));
+        assert_eq!(font.gasp_behavior(100), flags(true, true, true));
+
+        // Version 0 tables can't ask for symmetric rendering
+        let font = parse(&with_table(ARIAL, b"gasp", &gasp_table(0, &ranges))).unwrap();
+        assert_eq!(font.gasp_behavior(100), flags(true, true, false));
+
+        // Without a gasp table everything is grid-fitted and smoothed
+        let font = parse(&without_table(ARIAL, b"gasp")).unwrap();
+        assert_eq!(font.gasp_behavior(12), flags(true, true, false));
+    }
+
+    #[test]
+    fn unsorted_gasp_ranges_are_missing_unless_strict() {
+        let bytes = with_table(ARIAL, b"gasp", &gasp_table(1, &[(16, 0x0001), (8, 0x0002)]));
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.gasp_behavior(12), GaspFlags::DEFAULT);
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn summary_names_the_family_and_glyph_count() {
+        let font = parse(ARIAL).unwrap();
+        let summary = font.summary();
+        assert!(summary.contains(&format!("Family: {}\n", font.family_name().unwrap())));
+        assert!(summary.contains(&format!("{} glyphs", font.num_glyphs())));
+        assert!(summary.contains(&format!("{} units per em", font.units_per_em())));
+        assert!(summary.contains("(3, 1) format 4 (active)"));
+        assert!(summary.contains("U+0020-U+007E"));
+
+        let font = parse(&without_table(&without_table(ARIAL, b"GPOS"), b"kern")).unwrap();
+        assert!(font.summary().contains("Kerning: none\n"));
+    }
+
+    #[test]
+    fn signed_fonts_report_their_signature() {
+        let font = parse(ARIAL).unwrap();
+        assert!(font.is_digitally_signed());
+        assert_eq!(
+            font.digital_signature(),
+            Some(table(ARIAL, b"DSIG").as_slice())
+        );
+
+        let font = parse(&without_table(ARIAL, b"DSIG")).unwrap();
+        assert!(!font.is_digitally_signed());
+        assert_eq!(font.digital_signature(), None);
+
+        // A placeholder table with no signatures in it
+        let placeholder = [0, 0, 0, 1, 0, 0, 0, 1];
+        let font = parse(&with_table(ARIAL, b"DSIG", &placeholder)).unwrap();
+        assert!(!font.is_digitally_signed());
+        assert!(font.digital_signature().is_some());
+    }
+
+    #[test]
+    fn non_font_data_is_rejected_before_reading_the_directory() {
+        let html = b"<!DOCTYPE html>\n<html><head><title>404 Not Found</title></head>\
+            <body><h1>Not Found</h1></body></html>\n";
+        // A TrueType signature claiming far more tables than the file has room for
+        let mut huge_count = ARIAL[..12].to_vec();
+        huge_count[4..6].copy_from_slice(&u16::MAX.to_be_bytes());
+        for bytes in [html.as_slice(), &huge_count, &ARIAL[..2]] {
+            let err = parse(bytes).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::InvalidArgument);
+            let err = Font::from_reader(std::io::Cursor::new(bytes), ParseOptions::default())
+                .err()
+                .unwrap();
+            assert_eq!(err.code(), ErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn decoding_a_glyph_again_hits_the_outline_cache() {
+        let font = parse(ARIAL).unwrap();
+        let a = arial_glyph('a');
+        let first = font.outline_at_size(a, 12.0);
+        assert_eq!(
+            font.outline_cache_stats(),
+            CacheStats { hits: 0, misses: 1 }
+        );
+        // Another size reuses the same outline in font units
+        let second = font.outline_at_size(a, 48.0);
+        assert_eq!(
+            font.outline_cache_stats(),
+            CacheStats { hits: 1, misses: 1 }
+        );
+        assert_eq!(first.unwrap().len(), second.unwrap().len());
+    }
+
+    #[test]
+    fn outline_cache_evicts_the_least_recently_used_glyph() {
+        let font = parse(ARIAL).unwrap().with_outline_cache_capacity(2);
+        let [a, b, c] = ['a', 'b', 'c'].map(arial_glyph);
+        font.outline(a);
+        font.outline(b);
+        font.outline(a);
+        // 'b' is now the least recently used, so 'c' takes its place
+        font.outline(c);
+        font.outline(a);
+        assert_eq!(font.outline_cache_stats().hits, 2);
+        font.outline(b);
+        assert_eq!(font.outline_cache_stats().misses, 4);
+
+        let uncached = parse(ARIAL).unwrap().with_outline_cache_capacity(0);
+        uncached.outline(a);
+        uncached.outline(a);
+        assert_eq!(uncached.outline_cache_stats().hits, 0);
+    }
+
+    #[test]
+    fn vertical_origin_comes_from_vorg_overrides_or_its_default() {
+        // Version 1.0, default origin 900, and overrides for glyphs 3 and 5
+        let vorg: Vec<u8> = [1u16, 0, 900, 2, 3, 700, 5, (-20i16) as u16]
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect();
+        let font = parse(&with_table(ARIAL, b"VORG", &vorg)).unwrap();
+        assert_eq!(font.vertical_origin_y(3), Some(700));
+        assert_eq!(font.vertical_origin_y(5), Some(-20));
+        assert_eq!(font.vertical_origin_y(4), Some(900));
+        assert_eq!(font.vertical_origin_y(arial_glyph('A')), Some(900));
+        assert!(font.validate().is_ok());
+    }
+
+    #[test]
+    fn apple_true_font_loads_its_glyphs_through_glyf() {
+        let bytes = with_scalar_type(b"true");
+        let reference = parse(ARIAL).unwrap();
+        let a = arial_glyph('A');
+        for font in [
+            parse(&bytes).unwrap(),
+            Font::from_reader(std::io::Cursor::new(&bytes), ParseOptions::default()).unwrap(),
+        ] {
+            assert_eq!(font.sfnt_version(), SfntVersion::AppleTrueType);
+            assert_eq!(font.glyph_index('A'), Some(a));
+            assert_eq!(font.outline(a), reference.outline(a));
+        }
+
+        let err = parse(&with_scalar_type(b"typ1")).unwrap_err();
+        assert!(err.to_string().contains("PostScript Type 1"), "{err}");
+    }
+
+    /// `font` with hhea declaring `number_of_hmetrics` long horizontal metrics.
+    fn with_number_of_hmetrics(font: &[u8], number_of_hmetrics: u16) -> Vec<u8> {
+        let mut hhea = table(font, b"hhea");
+        hhea[34..36].copy_from_slice(&number_of_hmetrics.to_be_bytes());
+        with_table(font, b"hhea", &hhea)
+    }
+
+    #[test]
+    fn hmetrics_past_the_last_glyph_are_ignored_unless_strict() {
+        let arial = parse(ARIAL).unwrap();
+        let num_glyphs = arial.num_glyphs();
+        let last = num_glyphs - 1;
+        // Arial gives its last glyph only a bearing. Make that a long metric, follow it with
+        // bogus ones, and have hhea claim all of them.
+        let mut hmtx = table(ARIAL, b"hmtx");
+        let last_bearing = hmtx.split_off(hmtx.len() - 2);
+        hmtx.extend(arial.advance_width(last).to_be_bytes());
+        hmtx.extend(last_bearing);
+        hmtx.extend([0xAB; 600 * 4]);
+        let font = with_table(ARIAL, b"hmtx", &hmtx);
+        let bytes = with_number_of_hmetrics(&font, num_glyphs + 600);
+        let font = parse(&bytes).unwrap();
+        assert_eq!(font.advance_width(last), arial.advance_width(last));
+        assert_eq!(font.left_side_bearing(last), arial.left_side_bearing(last));
+        assert_eq!(font.left_side_bearing(num_glyphs), None);
+        let err = parse_from_bytes(bytes, STRICT).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
+
+    #[test]
+    fn glyphs_past_the_long_metrics_share_the_last_advance() {
+        let hmtx = table(ARIAL, b"hmtx");
+        let font = parse(&with_number_of_hmetrics(ARIAL, 1000)).unwrap();
+        let last_advance = u16::from_be_bytes([hmtx[999 * 4], hmtx[999 * 4 + 1]]);
+        assert_eq!(font.advance_width(999), last_advance);
+        assert_eq!(font.advance_width(1000), last_advance);
+        assert_eq!(font.advance_width(font.num_glyphs() - 1), last_advance);
+        // Bearings after the long metrics come from the trailing array
+        let trailing = i16::from_be_bytes([hmtx[4000], hmtx[4001]]);
+        assert_eq!(font.left_side_bearing(1000), Some(trailing));
+
+        let err = parse(&with_number_of_hmetrics(ARIAL, 0)).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+    }
 }
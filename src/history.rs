@@ -0,0 +1,90 @@
+use crate::url::Url;
+
+/// Back/forward navigation stack. `index` points at the page currently shown.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<Url>,
+    index: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a newly visited page, discarding any pages that were ahead of the current one.
+    pub fn push(&mut self, url: Url) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.index + 1);
+        }
+        self.entries.push(url);
+        self.index = self.entries.len() - 1;
+    }
+
+    pub fn current(&self) -> Option<&Url> {
+        self.entries.get(self.index)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.index + 1 < self.entries.len()
+    }
+
+    pub fn back(&mut self) -> Option<&Url> {
+        if !self.can_go_back() {
+            return None;
+        }
+        self.index -= 1;
+        self.current()
+    }
+
+    pub fn forward(&mut self) -> Option<&Url> {
+        if !self.can_go_forward() {
+            return None;
+        }
+        self.index += 1;
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn back_and_forward_move_through_visited_pages() {
+        let mut history = History::new();
+        assert!(history.current().is_none());
+        assert!(history.back().is_none());
+        history.push(url("http://a.test/"));
+        history.push(url("http://b.test/"));
+        history.push(url("http://c.test/"));
+
+        assert_eq!(history.back(), Some(&url("http://b.test/")));
+        assert_eq!(history.back(), Some(&url("http://a.test/")));
+        assert!(!history.can_go_back());
+        assert!(history.back().is_none());
+        assert_eq!(history.forward(), Some(&url("http://b.test/")));
+        assert_eq!(history.forward(), Some(&url("http://c.test/")));
+        assert!(history.forward().is_none());
+    }
+
+    #[test]
+    fn visiting_a_page_discards_forward_history() {
+        let mut history = History::new();
+        history.push(url("http://a.test/"));
+        history.push(url("http://b.test/"));
+        history.back();
+        history.push(url("http://c.test/"));
+        assert_eq!(history.current(), Some(&url("http://c.test/")));
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back(), Some(&url("http://a.test/")));
+    }
+}
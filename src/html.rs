@@ -0,0 +1,279 @@
+// Tags that start a new block of text, so their content shouldn't run into the previous word
+const BLOCK_TAGS: [&str; 24] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "p",
+    "pre",
+    "td",
+    "title",
+    "tr",
+];
+// Tags whose content is never shown
+const HIDDEN_TAGS: [&str; 4] = ["head", "script", "style", "template"];
+
+/// Reduces an HTML document to its visible text. Tags are dropped, script and style contents
+/// are skipped, character references are decoded and runs of whitespace collapse to one space.
+pub fn extract_text(html: &str) -> String {
+    let mut extractor = TextExtractor::new();
+    extractor.feed(html.as_bytes());
+    extractor.finish()
+}
+
+/// Extracts the visible text of an HTML document that arrives in pieces, so a page can be shown
+/// before it has finished loading. Tags, comments, character references and UTF-8 sequences
+/// split across pieces are held back until the rest of them arrives.
+#[derive(Debug, Default)]
+pub struct TextExtractor {
+    // Bytes that can't be processed until more input arrives
+    pending: Vec<u8>,
+    text: String,
+    // The closing tag (such as "</script") that ends the hidden section we're inside of
+    hidden_until: Option<String>,
+}
+
+impl TextExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes the next piece of the document.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let complete = self.pending.len() - incomplete_utf8_suffix(&self.pending);
+        let input = String::from_utf8_lossy(&self.pending[..complete]).into_owned();
+        let consumed = self.process(&input, false);
+
+        let mut pending = input.as_bytes()[consumed..].to_vec();
+        pending.extend_from_slice(&self.pending[complete..]);
+        self.pending = pending;
+    }
+
+    /// The text extracted so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Processes whatever is still held back, treating the document as complete.
+    pub fn finish(mut self) -> String {
+        let input = String::from_utf8_lossy(&self.pending).into_owned();
+        self.process(&input, true);
+        self.text.truncate(self.text.trim_end().len());
+        self.text
+    }
+
+    /// Appends the text of `input` and returns how many of its bytes were consumed. Unless
+    /// `at_end` is set, an incomplete construct at the end is left for the next call.
+    fn process(&mut self, input: &str, at_end: bool) -> usize {
+        // ASCII lowercasing keeps byte offsets, so positions found here index into `input` as well
+        let lowercase = input.to_ascii_lowercase();
+        let mut position = 0;
+        loop {
+            if let Some(closing_tag) = &self.hidden_until {
+                match lowercase[position..].find(closing_tag.as_str()) {
+                    // The closing tag itself is handled as a normal tag below
+                    Some(index) => {
+                        position += index;
+                        self.hidden_until = None;
+                    }
+                    None if at_end => return input.len(),
+                    None => {
+                        // Keep just enough to recognize a closing tag split across pieces
+                        let mut keep = input.len().saturating_sub(closing_tag.len()).max(position);
+                        while !input.is_char_boundary(keep) {
+                            keep -= 1;
+                        }
+                        return keep;
+                    }
+                }
+            }
+
+            let Some(start) = lowercase[position..]
+                .find('<')
+                .map(|index| position + index)
+            else {
+                let end = match at_end {
+                    true => input.len(),
+                    false => position + incomplete_reference_start(&input[position..]),
+                };
+                push_text(&mut self.text, &input[position..end]);
+                return end;
+            };
+            push_text(&mut self.text, &input[position..start]);
+            if lowercase[start..].starts_with("<!--") {
+                match lowercase[start..].find("-->") {
+                    Some(index) => position = start + index + 3,
+                    None if at_end => return input.len(),
+                    None => return start,
+                }
+                continue;
+            }
+            let Some(end) = lowercase[start..].find('>').map(|index| start + index + 1) else {
+                // An unterminated tag swallows the rest of the document
+                return if at_end { input.len() } else { start };
+            };
+            position = end;
+
+            let tag = &lowercase[start + 1..end - 1];
+            let is_closing = tag.starts_with('/');
+            let name: String = tag
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect();
+            if !is_closing && HIDDEN_TAGS.contains(&name.as_str()) {
+                // <head> still holds the <title>, which is worth showing
+                if name != "head" {
+                    self.hidden_until = Some(format!("</{}", name));
+                }
+            } else if BLOCK_TAGS.contains(&name.as_str()) {
+                push_space(&mut self.text);
+            }
+        }
+    }
+}
+
+/// How many bytes at the end of `bytes` form the start of a UTF-8 sequence that isn't complete.
+fn incomplete_utf8_suffix(bytes: &[u8]) -> usize {
+    for suffix in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - suffix];
+        // Continuation bytes belong to a lead byte further back
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        let sequence_length = match byte {
+            0b1100_0000..=0b1101_1111 => 2,
+            0b1110_0000..=0b1110_1111 => 3,
+            0b1111_0000..=0b1111_0111 => 4,
+            _ => 1,
+        };
+        return if sequence_length > suffix { suffix } else { 0 };
+    }
+    0
+}
+
+/// Where a character reference that may continue in the next piece starts in `text`, or its
+/// length when there is none.
+fn incomplete_reference_start(text: &str) -> usize {
+    match text.rfind('&') {
+        Some(ampersand) if text.len() - ampersand <= 10 && !text[ampersand..].contains(';') => {
+            ampersand
+        }
+        _ => text.len(),
+    }
+}
+
+fn push_text(text: &mut String, raw: &str) {
+    let mut rest = raw;
+    while let Some(ampersand) = rest.find('&') {
+        push_collapsed(text, &rest[..ampersand]);
+        rest = &rest[ampersand..];
+        match rest.find(';').filter(|&end| end <= 10) {
+            Some(end) => match decode_reference(&rest[1..end]) {
+                Some(char) => {
+                    push_collapsed(text, char.encode_utf8(&mut [0; 4]));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    push_collapsed(text, "&");
+                    rest = &rest[1..];
+                }
+            },
+            None => {
+                push_collapsed(text, "&");
+                rest = &rest[1..];
+            }
+        }
+    }
+    push_collapsed(text, rest);
+}
+
+fn push_collapsed(text: &mut String, raw: &str) {
+    for char in raw.chars() {
+        if char.is_whitespace() {
+            push_space(text);
+        } else {
+            text.push(char);
+        }
+    }
+}
+
+fn push_space(text: &mut String) {
+    if !text.is_empty() && !text.ends_with(' ') {
+        text.push(' ');
+    }
+}
+
+/// Decodes the body of a character reference such as `amp` or `#x41`.
+fn decode_reference(reference: &str) -> Option<char> {
+    if let Some(number) = reference.strip_prefix('#') {
+        let code_point = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        return char::from_u32(code_point);
+    }
+    match reference {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        // Non-breaking spaces are shown as plain spaces
+        "nbsp" => Some(' '),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "<html><head><title>Caf\u{e9} &amp; bar</title>\
+        <script>if (a < b) { document.write('</p>'); }</script></head>\
+        <body><!-- a <b>comment</b> --><h1>Menu</h1><p>Tea &lt;3&#x2764;</p></body></html>";
+
+    #[test]
+    fn tag_split_across_pieces_is_held_back() {
+        let mut extractor = TextExtractor::new();
+        extractor.feed(b"<p>Hello</p><p cla");
+        assert_eq!(extractor.text(), "Hello ");
+        extractor.feed(b"ss=\"x\">world</p>");
+        assert_eq!(extractor.finish(), "Hello world");
+    }
+
+    #[test]
+    fn splitting_anywhere_gives_the_same_text() {
+        let expected = extract_text(PAGE);
+        assert_eq!(expected, "Caf\u{e9} & bar Menu Tea <3\u{2764}");
+        for split in 1..PAGE.len() {
+            let mut extractor = TextExtractor::new();
+            extractor.feed(&PAGE.as_bytes()[..split]);
+            extractor.feed(&PAGE.as_bytes()[split..]);
+            assert_eq!(extractor.finish(), expected, "split at byte {split}");
+        }
+    }
+
+    #[test]
+    fn unterminated_constructs_are_dropped_at_the_end() {
+        assert_eq!(extract_text("text<p"), "text");
+        assert_eq!(extract_text("text<!-- never closed"), "text");
+        assert_eq!(extract_text("fish &chips"), "fish &chips");
+    }
+}
@@ -1,59 +1,2101 @@
-use crate::error::CapyError;
-use std::io::{Read, Write};
-use std::net::{TcpStream, ToSocketAddrs};
+use crate::cookie::CookieJar;
+use crate::error::{CapyError, ErrorCode};
+use crate::transport::{lock, TcpTransport, Transport};
+use crate::url::Url;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+// How long a blocked read waits before checking the cancellation token again
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const DEFAULT_READ_BUFFER_SIZE: usize = 16 * 1024;
+// Responses whose status line and header fields don't end within this many bytes are rejected
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+// Connections `fetch_many` keeps open to one host at a time, like browsers
+const MAX_CONNECTIONS_PER_HOST: usize = 6;
+// How long `fetch_with_retry` waits before its first retry, doubling for each one after
+const RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+// Prefer the content types we can display, like browsers do
+const DEFAULT_ACCEPT: &str = "text/html,application/xhtml+xml,text/plain;q=0.9,*/*;q=0.8";
+// Used when the system locale is unset or is the C/POSIX locale
+const FALLBACK_ACCEPT_LANGUAGE: &str = "en-US";
+
+type HeaderFields = Vec<(String, String)>;
+// Receives the final response's head (with an empty body) along with each piece of its body
+type BodyCallback<'a> = &'a mut dyn FnMut(&HttpResponse, &[u8]);
+type ProgressCallback = dyn Fn(DownloadProgress) + Send + Sync;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpMethod {
+    Get,
+    // Like GET, but the response has no body
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+    // Any other method, sent as given
+    Custom(String),
+}
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Custom(method) => method,
+        }
+    }
+
+    /// Whether sending the request twice has the same effect as sending it once, which makes it
+    /// safe to retry. Custom methods are assumed not to be.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            HttpMethod::Get
+                | HttpMethod::Head
+                | HttpMethod::Put
+                | HttpMethod::Delete
+                | HttpMethod::Options
+        )
+    }
+
+    // Servers may refuse these without a Content-Length, even when the body is empty
+    fn expects_body(&self) -> bool {
+        matches!(self, HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch)
+    }
+}
+
+/// What to send, which a 303 redirect turns into a bodyless GET.
+#[derive(Clone, Copy)]
+struct Request<'a> {
+    method: &'a HttpMethod,
+    body: &'a [u8],
+    // Set by `fetch_many`, which has already taken a slot for the first URL's host
+    host_slots: Option<&'a HostSlots>,
+}
+
+impl<'a> Request<'a> {
+    fn bodyless(method: &'a HttpMethod) -> Request<'a> {
+        Request {
+            method,
+            body: &[],
+            host_slots: None,
+        }
+    }
+}
+
+/// The connections open to each host and port across the fetches of one `fetch_many`, so
+/// that no host gets more than `MAX_CONNECTIONS_PER_HOST`, redirects included.
+#[derive(Default)]
+struct HostSlots {
+    open: Mutex<HashMap<(String, u16), usize>>,
+    // Signalled whenever a slot is released
+    released: Condvar,
+}
+
+impl HostSlots {
+    /// Removes the first of `pending` whose host has a free slot and takes that slot, waiting
+    /// while every pending host is at its limit. Returns None once nothing is pending.
+    fn take_unblocked(&self, pending: &Mutex<VecDeque<(usize, (String, u16))>>) -> Option<usize> {
+        let mut open = lock(&self.open);
+        loop {
+            let mut pending = lock(pending);
+            if pending.is_empty() {
+                return None;
+            }
+            let unblocked = pending.iter().position(|(_, host)| {
+                open.get(host).copied().unwrap_or(0) < MAX_CONNECTIONS_PER_HOST
+            });
+            if let Some((index, host)) = unblocked.and_then(|position| pending.remove(position)) {
+                *open.entry(host).or_insert(0) += 1;
+                return Some(index);
+            }
+            drop(pending);
+            open = self
+                .released
+                .wait(open)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Waits for a free slot for `host` and takes it.
+    fn acquire(&self, host: &(String, u16)) {
+        let mut open = lock(&self.open);
+        while open.get(host).copied().unwrap_or(0) >= MAX_CONNECTIONS_PER_HOST {
+            open = self
+                .released
+                .wait(open)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        *open.entry(host.clone()).or_insert(0) += 1;
+    }
+
+    fn release(&self, host: &(String, u16)) {
+        if let Some(count) = lock(&self.open).get_mut(host) {
+            *count = count.saturating_sub(1);
+        }
+        self.released.notify_all();
+    }
+}
+
+/// A slot taken from `HostSlots`, given back when dropped.
+struct HostSlot<'a> {
+    slots: &'a HostSlots,
+    host: (String, u16),
+}
+
+impl HostSlot<'_> {
+    /// Trades this slot for one for `host`, as when a redirect leads to another host.
+    fn move_to(&mut self, host: (String, u16)) {
+        if host != self.host {
+            // Let go first, so two fetches redirected at each other's host can't deadlock
+            self.slots.release(&self.host);
+            self.slots.acquire(&host);
+            self.host = host;
+        }
+    }
+}
+
+impl Drop for HostSlot<'_> {
+    fn drop(&mut self) {
+        self.slots.release(&self.host);
+    }
+}
+
+/// The first line of a request.
+struct RequestLine<'a> {
+    method: &'a HttpMethod,
+    // `*` for server-wide OPTIONS, otherwise the URL's path and query
+    target: &'a str,
+    http_version: HttpVersion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl HttpVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        }
+    }
+
+    fn parse(version: &str) -> Option<HttpVersion> {
+        match version {
+            "HTTP/1.0" => Some(HttpVersion::Http10),
+            "HTTP/1.1" => Some(HttpVersion::Http11),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    version: HttpVersion,
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    // Fields sent after the last chunk of a chunked body
+    trailers: Vec<(String, String)>,
+    body: Vec<u8>,
+    timeline: RequestTimeline,
+}
+
+/// How much of a response body has arrived so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownloadProgress {
+    pub received: usize,
+    // From Content-Length, when the server sent one
+    pub total: Option<usize>,
+}
+
+/// Where the time went in one request/response exchange.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimings {
+    pub dns: Duration,
+    pub connect: Duration,
+    // Always None until HTTPS is supported
+    pub tls_handshake: Option<Duration>,
+    // From sending the request until the first byte of the response arrived
+    pub first_byte: Duration,
+    pub total: Duration,
+}
+
+/// One request made while fetching a URL; every redirect adds another.
+#[derive(Debug, Clone)]
+pub struct RequestHop {
+    pub url: Url,
+    pub status: u16,
+    pub timings: RequestTimings,
+}
+
+/// Every hop of a fetch in order, ending with the request that produced the response.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimeline {
+    pub hops: Vec<RequestHop>,
+    pub total: Duration,
+}
+
+impl HttpResponse {
+    /// Builds a response directly, e.g. for a mock transport or for testing code that handles
+    /// responses. The version defaults to HTTP/1.1.
+    pub fn new(status: u16, reason: &str, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        Self {
+            version: HttpVersion::Http11,
+            status,
+            reason: reason.to_string(),
+            headers,
+            trailers: Vec::new(),
+            body,
+            timeline: RequestTimeline::default(),
+        }
+    }
+
+    /// An empty `200 OK` response.
+    pub fn ok() -> Self {
+        Self::new(200, "OK", Vec::new(), Vec::new())
+    }
+
+    pub fn with_version(mut self, version: HttpVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn with_status(mut self, status: u16, reason: &str) -> Self {
+        self.status = status;
+        self.reason = reason.to_string();
+        self
+    }
+
+    /// Adds a header after any already set, keeping earlier ones with the same name.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_trailer(mut self, name: &str, value: &str) -> Self {
+        self.trailers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: &[u8]) -> Self {
+        self.body = body.to_vec();
+        self
+    }
+
+    pub fn version(&self) -> HttpVersion {
+        self.version
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Returns the first header with the given (case-insensitive) name. Parsed responses have
+    /// repeated headers combined into one, except for `Set-Cookie`; see `headers_all`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the value of every header with the given (case-insensitive) name, in order.
+    /// This is how to read each `Set-Cookie` header, which can't be combined.
+    pub fn headers_all(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the first trailer field with the given (case-insensitive) name.
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        self.trailers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    /// The lowercased media type from the `Content-Type` header, without parameters such as
+    /// `charset`.
+    pub fn mime_type(&self) -> Option<String> {
+        let content_type = self.header("Content-Type")?;
+        let mime_type = content_type.split(';').next()?.trim();
+        (!mime_type.is_empty()).then(|| mime_type.to_ascii_lowercase())
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The body decoded as UTF-8. Invalid byte sequences become U+FFFD rather than failing the
+    /// whole page.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Timings for each request made to produce this response, including redirects.
+    pub fn timeline(&self) -> &RequestTimeline {
+        &self.timeline
+    }
+
+    /// The URL the response came from after following redirects, which relative links in the
+    /// body resolve against. `None` for responses that weren't fetched.
+    pub fn final_url(&self) -> Option<&Url> {
+        self.timeline.hops.last().map(|hop| &hop.url)
+    }
+
+    /// The URLs that redirected on the way to `final_url`, in the order they were requested.
+    pub fn redirect_chain(&self) -> Vec<&Url> {
+        let redirects = self.timeline.hops.len().saturating_sub(1);
+        self.timeline.hops[..redirects]
+            .iter()
+            .map(|hop| &hop.url)
+            .collect()
+    }
+
+    /// Turns a 4xx or 5xx status into an error whose code matches it as closely as possible.
+    pub fn error_for_status(&self) -> Result<(), CapyError> {
+        let code = match self.status {
+            401 => ErrorCode::Unauthenticated,
+            403 => ErrorCode::PermissionDenied,
+            404 | 410 => ErrorCode::NotFound,
+            408 | 504 => ErrorCode::DeadlineExceeded,
+            429 => ErrorCode::ResourceExhausted,
+            501 => ErrorCode::Unimplemented,
+            502 | 503 => ErrorCode::Unavailable,
+            400..=499 => ErrorCode::InvalidArgument,
+            500..=599 => ErrorCode::Internal,
+            _ => return Ok(()),
+        };
+        let status = format!("{} {}", self.status, self.reason);
+        Err(CapyError::new(code, "server returned an error status").with_context(&status))
+    }
+
+    fn is_redirect(&self) -> bool {
+        matches!(self.status, 301 | 302 | 303 | 307 | 308)
+    }
+}
+
+/// What a `UrlFilter` decided to do with a URL about to be fetched.
+#[derive(Debug)]
+pub enum FilterAction {
+    Allow,
+    Block,
+    Rewrite(Url),
+}
+
+/// Content policy hook consulted before every request, including each redirect hop.
+pub trait UrlFilter: Send + Sync {
+    fn filter(&self, url: &Url) -> FilterAction;
+}
+
+/// Shared flag used to abort an in-flight fetch from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn check(&self) -> Result<(), CapyError> {
+        if self.is_cancelled() {
+            return Err(CapyError::new(
+                ErrorCode::Cancelled,
+                "request was cancelled",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What can cut a fetch short: cancellation from another thread, or running out of time.
+struct StopCheck<'a> {
+    cancellation_token: &'a CancellationToken,
+    // When the whole fetch, redirects included, must have finished
+    deadline: Option<Instant>,
+}
+
+impl StopCheck<'_> {
+    fn check(&self) -> Result<(), CapyError> {
+        self.cancellation_token.check()?;
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(CapyError::new(
+                ErrorCode::DeadlineExceeded,
+                "request took longer than its total timeout",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A token sent as `Authorization: Bearer <token>`. Its `Debug` output is redacted so it
+/// can't end up in logs.
+#[derive(Clone)]
+pub struct BearerToken(String);
+
+impl BearerToken {
+    pub fn new(token: String) -> Self {
+        Self(token)
+    }
+
+    fn header_value(&self) -> String {
+        format!("Bearer {}", self.0)
+    }
+}
+
+impl std::fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BearerToken(<redacted>)")
+    }
+}
+
+pub struct HttpClient {
+    url_filter: Option<Box<dyn UrlFilter>>,
+    // Sent with every request unless a request brings its own
+    bearer_token: Option<BearerToken>,
+    max_redirects: usize,
+    // Bounds a whole fetch, however steadily the server keeps sending
+    total_timeout: Option<Duration>,
+    http_version: HttpVersion,
+    http10_fallback: bool,
+    cookie_jar: Mutex<CookieJar>,
+    read_buffer_size: usize,
+    transport: Arc<dyn Transport>,
+    progress_callback: Option<Box<ProgressCallback>>,
+    // Content negotiation headers sent with every request
+    accept: String,
+    accept_language: String,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            url_filter: None,
+            bearer_token: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            total_timeout: None,
+            http_version: HttpVersion::Http11,
+            http10_fallback: false,
+            cookie_jar: Mutex::new(CookieJar::new()),
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            transport: Arc::new(TcpTransport::new(CANCELLATION_POLL_INTERVAL)),
+            progress_callback: None,
+            accept: DEFAULT_ACCEPT.to_string(),
+            accept_language: system_accept_language(),
+        }
+    }
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_url_filter(mut self, url_filter: Box<dyn UrlFilter>) -> Self {
+        self.url_filter = Some(url_filter);
+        self
+    }
+
+    pub fn with_bearer_token(mut self, bearer_token: BearerToken) -> Self {
+        self.bearer_token = Some(bearer_token);
+        self
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Gives up on a fetch with `ErrorCode::DeadlineExceeded` once it has taken
+    /// `total_timeout`, counting from the first connection to the end of the last body, across
+    /// redirects. Unlike the per-read timeout, this also stops servers that trickle their
+    /// response. Resolving, connecting and each read wait no longer than the time left; writing
+    /// is checked when it returns.
+    pub fn with_total_timeout(mut self, total_timeout: Duration) -> Self {
+        self.total_timeout = Some(total_timeout);
+        self
+    }
+
+    pub fn with_http_version(mut self, http_version: HttpVersion) -> Self {
+        self.http_version = http_version;
+        self
+    }
+
+    /// Retry a request as HTTP/1.0 when the server answers an HTTP/1.1 request with a response
+    /// that can't be parsed. Legacy servers that only speak 1.0 are the usual culprits.
+    pub fn with_http10_fallback(mut self, http10_fallback: bool) -> Self {
+        self.http10_fallback = http10_fallback;
+        self
+    }
+
+    pub fn with_cookie_jar(mut self, cookie_jar: CookieJar) -> Self {
+        self.cookie_jar = Mutex::new(cookie_jar);
+        self
+    }
+
+    /// How many bytes each read from the socket asks for. Defaults to 16 KiB.
+    pub fn with_read_buffer_size(mut self, read_buffer_size: usize) -> Self {
+        self.read_buffer_size = read_buffer_size.max(1);
+        self
+    }
+
+    /// Replaces the TCP connections requests go over, for instance with a `MockTransport`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Called after every read of a response body, so large downloads can show their
+    /// progress. Redirect bodies aren't reported.
+    pub fn with_progress_callback(mut self, progress_callback: Box<ProgressCallback>) -> Self {
+        self.progress_callback = Some(progress_callback);
+        self
+    }
+
+    /// Overrides the `Accept` header, which defaults to preferring HTML and plain text.
+    pub fn with_accept(mut self, accept: &str) -> Self {
+        self.accept = accept.to_string();
+        self
+    }
+
+    /// Overrides the `Accept-Language` header, which defaults to the system locale.
+    pub fn with_accept_language(mut self, accept_language: &str) -> Self {
+        self.accept_language = accept_language.to_string();
+        self
+    }
+
+    /// Cookies are stored from every response and sent back on matching requests.
+    pub fn cookie_jar(&self) -> &Mutex<CookieJar> {
+        &self.cookie_jar
+    }
+
+    pub fn fetch(&self, url: &str) -> Result<HttpResponse, CapyError> {
+        self.fetch_cancellable(url, &CancellationToken::new())
+    }
+
+    /// Like `fetch`, but authorizes this request with `bearer_token` instead of the client's.
+    pub fn fetch_with_bearer_token(
+        &self,
+        url: &str,
+        bearer_token: &BearerToken,
+    ) -> Result<HttpResponse, CapyError> {
+        self.fetch_inner(
+            url,
+            Request::bodyless(&HttpMethod::Get),
+            false,
+            Some(bearer_token),
+            &CancellationToken::new(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Fetches only the headers of `url`, for instance to check its size or type before
+    /// downloading it. The response body is always empty.
+    pub fn head(&self, url: &str) -> Result<HttpResponse, CapyError> {
+        self.fetch_inner(
+            url,
+            Request::bodyless(&HttpMethod::Head),
+            false,
+            self.bearer_token.as_ref(),
+            &CancellationToken::new(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Like `fetch`, but gives up with `ErrorCode::Cancelled` soon after `cancellation_token`
+    /// is cancelled.
+    pub fn fetch_cancellable(
+        &self,
+        url: &str,
+        cancellation_token: &CancellationToken,
+    ) -> Result<HttpResponse, CapyError> {
+        self.fetch_streaming(url, cancellation_token, &mut |_, _| {})
+    }
+
+    /// Like `fetch_cancellable`, but also hands the body of the final response to `on_body` as
+    /// it arrives, already stripped of any chunked transfer coding. Redirect bodies are skipped.
+    pub fn fetch_streaming(
+        &self,
+        url: &str,
+        cancellation_token: &CancellationToken,
+        on_body: BodyCallback,
+    ) -> Result<HttpResponse, CapyError> {
+        self.fetch_inner(
+            url,
+            Request::bodyless(&HttpMethod::Get),
+            false,
+            self.bearer_token.as_ref(),
+            cancellation_token,
+            on_body,
+        )
+    }
+
+    /// Sends a bodyless request with any method. Redirects are followed, switching to GET after
+    /// a 303 like browsers do.
+    pub fn request(&self, method: &HttpMethod, url: &str) -> Result<HttpResponse, CapyError> {
+        self.request_with_body(method, url, &[])
+    }
+
+    /// Sends a request carrying `body`, such as a PUT. Redirects resend the body, except a 303,
+    /// which switches to a bodyless GET.
+    pub fn request_with_body(
+        &self,
+        method: &HttpMethod,
+        url: &str,
+        body: &[u8],
+    ) -> Result<HttpResponse, CapyError> {
+        self.fetch_inner(
+            url,
+            Request {
+                method,
+                body,
+                host_slots: None,
+            },
+            false,
+            self.bearer_token.as_ref(),
+            &CancellationToken::new(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Fetches `urls` in parallel on up to `concurrency` threads, opening at most 6 connections
+    /// to any one host at a time, redirects included. Results are in the same order as `urls`.
+    pub fn fetch_many(
+        &self,
+        urls: &[Url],
+        concurrency: usize,
+    ) -> Vec<Result<HttpResponse, CapyError>> {
+        let results: Vec<Mutex<Option<Result<HttpResponse, CapyError>>>> =
+            urls.iter().map(|_| Mutex::new(None)).collect();
+        let pending: Mutex<VecDeque<(usize, (String, u16))>> = Mutex::new(
+            urls.iter()
+                .enumerate()
+                .map(|(index, url)| (index, (url.host().to_string(), url.port())))
+                .collect(),
+        );
+        let host_slots = HostSlots::default();
+
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency.clamp(1, urls.len().max(1)) {
+                scope.spawn(|| {
+                    // Each worker takes the next URL whose host isn't at its limit
+                    while let Some(index) = host_slots.take_unblocked(&pending) {
+                        let request = Request {
+                            host_slots: Some(&host_slots),
+                            ..Request::bodyless(&HttpMethod::Get)
+                        };
+                        let result = self.fetch_inner(
+                            &urls[index].to_string(),
+                            request,
+                            false,
+                            self.bearer_token.as_ref(),
+                            &CancellationToken::new(),
+                            &mut |_, _| {},
+                        );
+                        *lock(&results[index]) = Some(result);
+                    }
+                });
+            }
+        });
+        results
+            .into_iter()
+            .map(|result| {
+                result
+                    .into_inner()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .unwrap_or(Err(CapyError::new(
+                        ErrorCode::Internal,
+                        "fetch did not complete",
+                    )))
+            })
+            .collect()
+    }
+
+    /// Like `request_with_body`, but retries up to `max_retries` times after a transient failure:
+    /// a connection that couldn't be made or was dropped, or a 502, 503 or 504 response. Only
+    /// idempotent methods are retried, since a POST or PATCH that failed midway may already
+    /// have taken effect. Each retry waits twice as long as the one before, starting at 100 ms.
+    pub fn fetch_with_retry(
+        &self,
+        method: &HttpMethod,
+        url: &str,
+        body: &[u8],
+        max_retries: usize,
+    ) -> Result<HttpResponse, CapyError> {
+        let mut delay = RETRY_INITIAL_DELAY;
+        let mut retries = 0;
+        loop {
+            let result = self.request_with_body(method, url, body);
+            let transient = match &result {
+                Ok(response) => matches!(response.status(), 502..=504),
+                Err(err) => err.code() == ErrorCode::Unavailable,
+            };
+            if !transient || !method.is_idempotent() || retries == max_retries {
+                return result;
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            retries += 1;
+        }
+    }
+
+    /// Asks the server at `url` which options it supports as a whole, with an asterisk-form
+    /// `OPTIONS * HTTP/1.1` request. The path of `url` is ignored.
+    pub fn options_server_wide(&self, url: &str) -> Result<HttpResponse, CapyError> {
+        self.fetch_inner(
+            url,
+            Request::bodyless(&HttpMethod::Options),
+            true,
+            self.bearer_token.as_ref(),
+            &CancellationToken::new(),
+            &mut |_, _| {},
+        )
+    }
+
+    fn fetch_inner(
+        &self,
+        url: &str,
+        mut request: Request,
+        asterisk_form: bool,
+        bearer_token: Option<&BearerToken>,
+        cancellation_token: &CancellationToken,
+        on_body: BodyCallback,
+    ) -> Result<HttpResponse, CapyError> {
+        let start = Instant::now();
+        let stop_check = StopCheck {
+            cancellation_token,
+            deadline: self
+                .total_timeout
+                .map(|total_timeout| start + total_timeout),
+        };
+        let mut url = Url::parse(url)?;
+        let origin = (url.host().to_string(), url.port());
+        let mut host_slot = request.host_slots.map(|slots| HostSlot {
+            slots,
+            host: origin.clone(),
+        });
+        let mut redirects = 0;
+        let mut hops = Vec::new();
+        loop {
+            url = self.apply_url_filter(url)?;
+            if let Some(host_slot) = &mut host_slot {
+                host_slot.move_to((url.host().to_string(), url.port()));
+            }
+            // Redirects to another server must not learn the token
+            let bearer_token =
+                bearer_token.filter(|_| (url.host(), url.port()) == (&origin.0, origin.1));
+            // Redirects point at a resource, so only the first request can be server-wide
+            let target = match asterisk_form && redirects == 0 {
+                true => String::from("*"),
+                false => url.request_target(),
+            };
+            let (mut response, timings) =
+                self.send(&url, request, &target, bearer_token, &stop_check, on_body)?;
+            hops.push(RequestHop {
+                url: url.clone(),
+                status: response.status(),
+                timings,
+            });
+            if !response.is_redirect() {
+                response.timeline = RequestTimeline {
+                    hops,
+                    total: start.elapsed(),
+                };
+                return Ok(response);
+            }
+
+            let location = response.header("Location").ok_or(CapyError::new(
+                ErrorCode::DataLoss,
+                "redirect response is missing a Location header",
+            ))?;
+            if redirects == self.max_redirects {
+                return Err(CapyError::new(
+                    ErrorCode::ResourceExhausted,
+                    "too many redirects",
+                ));
+            }
+            url = url.join(location)?;
+            if response.status() == 303 && *request.method != HttpMethod::Head {
+                request = Request {
+                    host_slots: request.host_slots,
+                    ..Request::bodyless(&HttpMethod::Get)
+                };
+            }
+            redirects += 1;
+        }
+    }
+
+    fn send(
+        &self,
+        url: &Url,
+        Request { method, body, .. }: Request,
+        target: &str,
+        bearer_token: Option<&BearerToken>,
+        stop_check: &StopCheck,
+        on_body: BodyCallback,
+    ) -> Result<(HttpResponse, RequestTimings), CapyError> {
+        let mut headers = vec![
+            (String::from("Accept"), self.accept.clone()),
+            (
+                String::from("Accept-Language"),
+                self.accept_language.clone(),
+            ),
+        ];
+        if let Some(bearer_token) = bearer_token {
+            headers.push((String::from("Authorization"), bearer_token.header_value()));
+        }
+        if let Some(cookie) = self.lock_cookie_jar().cookie_header(url) {
+            headers.push((String::from("Cookie"), cookie));
+        }
+        if !body.is_empty() || method.expects_body() {
+            headers.push((String::from("Content-Length"), body.len().to_string()));
+        }
+
+        let mut request_line = RequestLine {
+            method,
+            target,
+            http_version: self.http_version,
+        };
+        let (raw_response, mut timings) =
+            self.send_request(url, &request_line, &headers, body, stop_check, on_body)?;
+        let response = match parse_response(&raw_response, method) {
+            Err(_) if self.http10_fallback && self.http_version == HttpVersion::Http11 => {
+                request_line.http_version = HttpVersion::Http10;
+                let (raw_response, retry_timings) =
+                    self.send_request(url, &request_line, &headers, body, stop_check, on_body)?;
+                timings = retry_timings;
+                parse_response(&raw_response, method)
+            }
+            response => response,
+        }?;
+
+        let mut cookie_jar = self.lock_cookie_jar();
+        for (name, value) in response.headers() {
+            if name.eq_ignore_ascii_case("Set-Cookie") {
+                cookie_jar.store(url, value);
+            }
+        }
+        Ok((response, timings))
+    }
+
+    fn send_request(
+        &self,
+        url: &Url,
+        request_line: &RequestLine,
+        headers: &[(String, String)],
+        body: &[u8],
+        stop_check: &StopCheck,
+        on_body: BodyCallback,
+    ) -> Result<(Vec<u8>, RequestTimings), CapyError> {
+        let start = Instant::now();
+        let mut timings = RequestTimings::default();
+        if url.scheme() != "http" {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "Only HTTP URLs are supported",
+            ));
+        }
+
+        let mut request = serialize_request(url, request_line, headers)?.into_bytes();
+        request.extend_from_slice(body);
+
+        stop_check.check()?;
+        let mut stream =
+            self.transport
+                .connect(url.host(), url.port(), stop_check.deadline, &mut timings)?;
+
+        // Send the HTTP request
+        stop_check.check()?;
+        stream.write_all(&request)?;
+        let request_sent = Instant::now();
+
+        // Read the response, polling the cancellation token and deadline between reads
+        let mut response = Vec::new();
+        let mut body_stream = BodyStream::new(on_body, self.progress_callback.as_deref());
+        let mut buffer = vec![0; self.read_buffer_size];
+        let mut head_complete = false;
+        loop {
+            stop_check.check()?;
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => {
+                    if response.is_empty() {
+                        timings.first_byte = request_sent.elapsed();
+                    }
+                    response.extend_from_slice(&buffer[..read]);
+                    if !head_complete {
+                        head_complete = check_header_size(&response)?;
+                    }
+                    // A HEAD response ends with its headers, whatever Content-Length says
+                    if *request_line.method == HttpMethod::Head {
+                        if let Some(header_end) = find_header_end(&response) {
+                            response.truncate(header_end + 4);
+                            break;
+                        }
+                        continue;
+                    }
+                    body_stream.update(&response);
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        timings.total = start.elapsed();
+        Ok((response, timings))
+    }
+
+    fn lock_cookie_jar(&self) -> std::sync::MutexGuard<'_, CookieJar> {
+        // A panic while holding the lock can't leave the jar half-updated, so keep using it
+        self.cookie_jar
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn apply_url_filter(&self, url: Url) -> Result<Url, CapyError> {
+        let Some(url_filter) = &self.url_filter else {
+            return Ok(url);
+        };
+        match url_filter.filter(&url) {
+            FilterAction::Allow => Ok(url),
+            FilterAction::Block => Err(CapyError::new(
+                ErrorCode::PermissionDenied,
+                "request blocked by url filter",
+            )),
+            FilterAction::Rewrite(rewritten) => Ok(rewritten),
+        }
+    }
+}
 
 pub fn fetch_url(url: &str) -> Result<String, CapyError> {
-    // Parse the URL
-    let (host, path) = parse_url(url)?;
+    let response = HttpClient::new().fetch(url)?;
+    Ok(response.text())
+}
+
+fn parse_response(response: &[u8], method: &HttpMethod) -> Result<HttpResponse, CapyError> {
+    // Separate headers from body
+    check_header_size(response)?;
+    let header_end = find_header_end(response).ok_or(CapyError::new(
+        ErrorCode::DataLoss,
+        "response has no blank line ending its header block",
+    ))?;
+    let mut response_head = parse_head(&response[..header_end])?;
+    if *method == HttpMethod::Head {
+        return Ok(response_head);
+    }
+    let body = &response[header_end + 4..];
+    (response_head.body, response_head.trailers) = match is_chunked(&response_head.headers) {
+        true => decode_chunked(body)?,
+        false => (body.to_vec(), Vec::new()),
+    };
+    Ok(response_head)
+}
+
+/// Turns the POSIX locale (e.g. `de_DE.UTF-8`) into a language tag (`de-DE`).
+fn system_accept_language() -> String {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    // Drop the encoding and modifier
+    let tag = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or("")
+        .replace('_', "-");
+    match tag.as_str() {
+        "" | "C" | "POSIX" => FALLBACK_ACCEPT_LANGUAGE.to_string(),
+        _ => tag,
+    }
+}
+
+fn find_header_end(response: &[u8]) -> Option<usize> {
+    response.windows(4).position(|window| window == b"\r\n\r\n")
+}
+
+/// Fails once `response`, read so far, is past `MAX_HEADER_SIZE` without its header block
+/// having ended, so a broken server can't make us buffer headers forever. Returns whether the
+/// header block is complete.
+fn check_header_size(response: &[u8]) -> Result<bool, CapyError> {
+    let searched = &response[..response.len().min(MAX_HEADER_SIZE + 4)];
+    match find_header_end(searched) {
+        Some(_) => Ok(true),
+        None if response.len() > MAX_HEADER_SIZE => Err(CapyError::new(
+            ErrorCode::DataLoss,
+            "response header block is larger than 64 KiB",
+        )),
+        None => Ok(false),
+    }
+}
+
+/// Parses the status line and header fields that precede the body into a response whose body
+/// is still empty.
+fn parse_head(head: &[u8]) -> Result<HttpResponse, CapyError> {
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts
+        .next()
+        .and_then(HttpVersion::parse)
+        .ok_or(CapyError::new(
+            ErrorCode::InvalidArgument,
+            "response has an unsupported HTTP version",
+        ))?;
+    let status = parts
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(CapyError::new(
+            ErrorCode::InvalidArgument,
+            "response has a malformed status line",
+        ))?;
+    let reason = parts.next().unwrap_or_default();
+    let headers = combine_repeated_fields(lines.filter_map(parse_header_line).collect())?;
+
+    Ok(HttpResponse::new(status, reason, headers, Vec::new()).with_version(version))
+}
+
+/// Builds the request head. Every piece is checked for line breaks and NULs first, since a URL
+/// or header value carrying `\r\n` would otherwise smuggle extra headers into the request.
+fn serialize_request(
+    url: &Url,
+    request_line: &RequestLine,
+    headers: &[(String, String)],
+) -> Result<String, CapyError> {
+    check_no_control_characters(request_line.method.as_str(), "HTTP method")?;
+    check_no_control_characters(request_line.target, "Request target")?;
+    check_no_control_characters(url.host(), "Host")?;
+    let mut request = format!(
+        "{} {} {}\r\nHost: {}\r\nConnection: close\r\n",
+        request_line.method.as_str(),
+        request_line.target,
+        request_line.http_version.as_str(),
+        url.host()
+    );
+    for (name, value) in headers {
+        check_no_control_characters(name, "Header name")?;
+        check_no_control_characters(value, "Header value")?;
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    Ok(request)
+}
 
-    // Resolve DNS
-    let addr = format!("{}:80", host);
-    let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
-    if addrs.is_empty() {
+fn check_no_control_characters(value: &str, what: &'static str) -> Result<(), CapyError> {
+    if value.contains(['\r', '\n', '\0']) {
         return Err(CapyError::new(
-            crate::error::ErrorCode::InvalidArgument,
-            "Could not resolve address",
+            ErrorCode::InvalidArgument,
+            "Request contains a line break or NUL character",
+        )
+        .with_context(what));
+    }
+    Ok(())
+}
+
+fn is_chunked(headers: &[(String, String)]) -> bool {
+    // Chunked must be the last transfer coding applied, so only that position counts
+    headers
+        .iter()
+        .rev()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Transfer-Encoding"))
+        .and_then(|(_, value)| value.split(',').next_back())
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+fn parse_header_line(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once(':')?;
+    Some((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Merges fields that appear more than once into the first one, joining the values with ", "
+/// as RFC 9110 allows for list-valued fields. `Set-Cookie` values may themselves contain
+/// commas (in `Expires`), so each one is kept as a separate field. `Content-Length` isn't a
+/// list: repeats of the same length collapse to one, and differing lengths are an error.
+fn combine_repeated_fields(fields: HeaderFields) -> Result<HeaderFields, CapyError> {
+    let mut combined: HeaderFields = Vec::with_capacity(fields.len());
+    for (name, value) in fields {
+        let existing = combined.iter_mut().find(|(existing_name, _)| {
+            existing_name.eq_ignore_ascii_case(&name) && !name.eq_ignore_ascii_case("Set-Cookie")
+        });
+        match existing {
+            Some((_, existing_value)) => {
+                existing_value.push_str(", ");
+                existing_value.push_str(&value);
+            }
+            None => combined.push((name, value)),
+        }
+    }
+    let content_length = combined
+        .iter_mut()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"));
+    if let Some((_, value)) = content_length {
+        let mut lengths = value.split(',').map(str::trim);
+        let first = lengths.next().unwrap_or_default().to_string();
+        if lengths.any(|length| length != first) {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "response has conflicting Content-Length values",
+            ));
+        }
+        *value = first;
+    }
+    Ok(combined)
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body into its data and the trailer fields that
+/// follow the last chunk.
+fn decode_chunked(body: &[u8]) -> Result<(Vec<u8>, HeaderFields), CapyError> {
+    let truncated = || CapyError::new(ErrorCode::DataLoss, "chunked body is truncated");
+    let next_line = |rest: &[u8]| rest.windows(2).position(|window| window == b"\r\n");
+
+    let mut data = Vec::new();
+    let mut rest = body;
+    loop {
+        let line_end = next_line(rest).ok_or_else(truncated)?;
+        let size_line = String::from_utf8_lossy(&rest[..line_end]);
+        // Chunk extensions after ';' carry nothing we use
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| {
+            CapyError::new(ErrorCode::InvalidArgument, "chunk size is not a hex number")
+        })?;
+        rest = &rest[line_end + 2..];
+        if size == 0 {
+            break;
+        }
+        if rest.len() < size + 2 || &rest[size..size + 2] != b"\r\n" {
+            return Err(truncated());
+        }
+        data.extend_from_slice(&rest[..size]);
+        rest = &rest[size + 2..];
+    }
+
+    // Trailer fields end at an empty line, which some servers leave off entirely
+    let mut trailers = Vec::new();
+    while let Some(line_end) = next_line(rest).filter(|&line_end| line_end > 0) {
+        trailers.extend(parse_header_line(&String::from_utf8_lossy(
+            &rest[..line_end],
+        )));
+        rest = &rest[line_end + 2..];
+    }
+    Ok((data, combine_repeated_fields(trailers)?))
+}
+
+/// Passes the body of a response to a callback while the response is still being read.
+struct BodyStream<'a> {
+    on_body: BodyCallback<'a>,
+    progress_callback: Option<&'a ProgressCallback>,
+    state: BodyStreamState,
+    // How much of the raw response has been dealt with
+    consumed: usize,
+    // Body bytes handed to `on_body` so far
+    received: usize,
+}
+
+enum BodyStreamState {
+    WaitingForHead,
+    Plain(HttpResponse),
+    Chunked(HttpResponse, ChunkedDecoder),
+    // Redirects and responses whose head can't be parsed aren't streamed
+    Ignored,
+}
+
+impl<'a> BodyStream<'a> {
+    fn new(on_body: BodyCallback<'a>, progress_callback: Option<&'a ProgressCallback>) -> Self {
+        Self {
+            on_body,
+            progress_callback,
+            state: BodyStreamState::WaitingForHead,
+            consumed: 0,
+            received: 0,
+        }
+    }
+
+    /// Streams whatever part of the body `response`, the raw response read so far, has added.
+    fn update(&mut self, response: &[u8]) {
+        if let BodyStreamState::WaitingForHead = self.state {
+            let Some(header_end) = find_header_end(response) else {
+                return;
+            };
+            self.consumed = header_end + 4;
+            self.state = match parse_head(&response[..header_end]) {
+                Ok(head) if head.is_redirect() => BodyStreamState::Ignored,
+                Ok(head) if is_chunked(&head.headers) => {
+                    BodyStreamState::Chunked(head, ChunkedDecoder::default())
+                }
+                Ok(head) => BodyStreamState::Plain(head),
+                Err(_) => BodyStreamState::Ignored,
+            };
+        }
+
+        let new_bytes = &response[self.consumed..];
+        self.consumed = response.len();
+        let (delivered, total) = match &mut self.state {
+            BodyStreamState::Plain(head) if !new_bytes.is_empty() => {
+                (self.on_body)(head, new_bytes);
+                let total = head
+                    .header("Content-Length")
+                    .and_then(|length| length.parse().ok());
+                (new_bytes.len(), total)
+            }
+            BodyStreamState::Chunked(head, decoder) => {
+                let data = decoder.feed(new_bytes);
+                if !data.is_empty() {
+                    (self.on_body)(head, &data);
+                }
+                (data.len(), None)
+            }
+            _ => return,
+        };
+        if delivered == 0 {
+            return;
+        }
+        self.received += delivered;
+        if let Some(progress_callback) = self.progress_callback {
+            progress_callback(DownloadProgress {
+                received: self.received,
+                total,
+            });
+        }
+    }
+}
+
+/// Incremental counterpart of `decode_chunked` that yields chunk data as soon as it arrives.
+/// Malformed input just ends the stream, since the complete body is decoded again afterwards.
+#[derive(Default)]
+struct ChunkedDecoder {
+    // Bytes of an incomplete chunk size line
+    pending: Vec<u8>,
+    // Data bytes left in the current chunk
+    remaining: usize,
+    done: bool,
+}
+
+impl ChunkedDecoder {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(bytes);
+        let mut data = Vec::new();
+        let mut position = 0;
+        while !self.done && position < self.pending.len() {
+            if self.remaining > 0 {
+                let available = self.remaining.min(self.pending.len() - position);
+                data.extend_from_slice(&self.pending[position..position + available]);
+                position += available;
+                self.remaining -= available;
+                continue;
+            }
+            let Some(line_end) = self.pending[position..]
+                .windows(2)
+                .position(|window| window == b"\r\n")
+            else {
+                break;
+            };
+            let line = String::from_utf8_lossy(&self.pending[position..position + line_end]);
+            position += line_end + 2;
+            // The empty line is the CRLF that ends the previous chunk's data
+            if line.is_empty() {
+                continue;
+            }
+            let size = line.split(';').next().unwrap_or_default().trim();
+            match usize::from_str_radix(size, 16) {
+                Ok(size) if size > 0 => self.remaining = size,
+                _ => self.done = true,
+            }
+        }
+        self.pending.drain(..position);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    /// A client whose connections are served by `transport`.
+    fn client(transport: &Arc<MockTransport>) -> HttpClient {
+        HttpClient::new().with_transport(transport.clone())
+    }
+
+    #[test]
+    fn repeated_fields_combine_except_set_cookie() {
+        let head = b"HTTP/1.1 200 OK\r\nVary: Accept\r\nSet-Cookie: a=1\r\n\
+            vary: Cookie\r\nSet-Cookie: b=2; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\n\r\n";
+        let response = parse_head(head).unwrap();
+        assert_eq!(response.header("Vary"), Some("Accept, Cookie"));
+        assert_eq!(
+            response.headers_all("set-cookie"),
+            ["a=1", "b=2; Expires=Wed, 21 Oct 2026 07:28:00 GMT"]
+        );
+    }
+
+    #[test]
+    fn identical_content_lengths_collapse_to_one() {
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\n";
+        assert_eq!(
+            parse_head(head).unwrap().header("Content-Length"),
+            Some("5")
+        );
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 5, 5\r\n\r\n";
+        assert_eq!(
+            parse_head(head).unwrap().header("Content-Length"),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn conflicting_content_lengths_are_rejected() {
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n";
+        let error = parse_head(head).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn repeated_content_length_still_reports_a_progress_total() {
+        let transport = Arc::new(MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhello",
         ));
+        let totals = Arc::new(Mutex::new(Vec::new()));
+        let seen = totals.clone();
+        let response = client(&transport)
+            .with_progress_callback(Box::new(move |progress: DownloadProgress| {
+                seen.lock().unwrap().push(progress.total)
+            }))
+            .fetch("http://example.com/")
+            .unwrap();
+        assert_eq!(response.body(), b"hello");
+        let totals = totals.lock().unwrap();
+        assert!(!totals.is_empty());
+        assert!(totals.iter().all(|&total| total == Some(5)));
     }
 
-    // Connect to the server
-    let mut stream = TcpStream::connect(addrs[0])?;
+    const UNAVAILABLE: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+    const OK: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
 
-    // Send HTTP GET request
-    let request = format!(
-        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
-        path, host
-    );
-    stream.write_all(request.as_bytes())?;
+    #[test]
+    fn idempotent_request_is_retried_after_a_503() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, UNAVAILABLE)
+                .with_response("example.com", 80, OK),
+        );
+        let response = client(&transport)
+            .fetch_with_retry(&HttpMethod::Put, "http://example.com/", b"data", 3)
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.ends_with("data")));
+    }
 
-    // Read the response
-    let mut response = String::new();
-    stream.read_to_string(&mut response)?;
+    #[test]
+    fn post_is_not_retried() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, UNAVAILABLE)
+                .with_response("example.com", 80, OK),
+        );
+        let response = client(&transport)
+            .fetch_with_retry(&HttpMethod::Post, "http://example.com/", b"data", 3)
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(transport.requests().len(), 1);
+    }
 
-    // Separate headers from body
-    let body = response
-        .split("\r\n\r\n")
-        .nth(1)
-        .ok_or(CapyError::new(
-            crate::error::ErrorCode::InvalidArgument,
-            "TODO: add message",
-        ))?
-        .to_string();
-    Ok(body)
-}
-
-fn parse_url(url: &str) -> Result<(&str, String), CapyError> {
-    if url.starts_with("http://") {
-        let url = &url[7..]; // strip "http://"
-        if let Some((host, path)) = url.split_once('/') {
-            Ok((host, format!("/{}", path)))
-        } else {
-            Ok((url, String::from("/")))
-        }
-    } else {
-        Err(CapyError::new(
-            crate::error::ErrorCode::InvalidArgument,
-            "Only HTTP URLs are supported",
-        ))
+    #[test]
+    fn broken_pipe_counts_as_transient() {
+        let err = CapyError::from(std::io::Error::from(ErrorKind::BrokenPipe));
+        assert_eq!(err.code(), ErrorCode::Unavailable);
+    }
+
+    #[test]
+    fn trickling_response_fails_at_the_total_timeout() {
+        let body = vec![b'a'; 1000];
+        let mut response = b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n".to_vec();
+        response.extend_from_slice(&body);
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, &response)
+                .with_read_delay(Duration::from_millis(5)),
+        );
+        let start = Instant::now();
+        let err = client(&transport)
+            .with_total_timeout(Duration::from_millis(200))
+            .fetch("http://example.com/")
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+        // Each byte arrived well within any per-read timeout, but the whole took too long
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    fn urls(urls: &[&str]) -> Vec<Url> {
+        urls.iter().map(|url| Url::parse(url).unwrap()).collect()
+    }
+
+    #[test]
+    fn fetch_many_returns_results_in_order() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response(
+                    "a.test",
+                    80,
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na",
+                )
+                .with_response(
+                    "b.test",
+                    80,
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nb",
+                )
+                .with_response(
+                    "c.test",
+                    80,
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nc",
+                ),
+        );
+        let results = client(&transport).fetch_many(
+            &urls(&["http://c.test/", "http://a.test/", "http://b.test/"]),
+            3,
+        );
+        let bodies: Vec<String> = results.into_iter().map(|r| r.unwrap().text()).collect();
+        assert_eq!(bodies, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn fetch_many_opens_at_most_six_connections_per_host() {
+        let mut transport = MockTransport::new().with_read_delay(Duration::from_millis(1));
+        for _ in 0..10 {
+            transport = transport.with_response("a.test", 80, OK);
+        }
+        let transport = Arc::new(transport);
+        let results = client(&transport).fetch_many(&urls(&["http://a.test/"; 10]), 10);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(
+            transport.peak_connections_to("a.test", 80),
+            MAX_CONNECTIONS_PER_HOST
+        );
+    }
+
+    #[test]
+    fn fetch_many_skips_past_a_host_at_its_limit() {
+        let mut transport = MockTransport::new().with_read_delay(Duration::from_millis(1));
+        for _ in 0..8 {
+            transport = transport.with_response("a.test", 80, OK);
+        }
+        let transport = Arc::new(transport.with_response("b.test", 80, OK));
+        let mut list = vec!["http://a.test/"; 8];
+        list.push("http://b.test/");
+        let results = client(&transport).fetch_many(&urls(&list), 8);
+        assert!(results.iter().all(Result::is_ok));
+        // b.test is started by the worker that would otherwise wait for a slot on a.test
+        let requests = transport.requests();
+        let b = requests
+            .iter()
+            .position(|request| request.contains("Host: b.test"))
+            .unwrap();
+        assert!(b < 7, "b.test was connection {b}");
+    }
+
+    #[test]
+    fn fetch_many_counts_redirects_against_their_target_host() {
+        let mut transport = MockTransport::new().with_read_delay(Duration::from_millis(1));
+        let mut list = Vec::new();
+        for i in 0..8 {
+            let host = format!("h{i}.test");
+            transport = transport
+                .with_response(
+                    &host,
+                    80,
+                    b"HTTP/1.1 302 Found\r\nLocation: http://b.test/\r\nContent-Length: 0\r\n\r\n",
+                )
+                .with_response("b.test", 80, OK);
+            list.push(format!("http://{host}/"));
+        }
+        let transport = Arc::new(transport);
+        let list: Vec<&str> = list.iter().map(String::as_str).collect();
+        let results = client(&transport).fetch_many(&urls(&list), 8);
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap().status() == 200));
+        assert!(transport.peak_connections_to("b.test", 80) <= MAX_CONNECTIONS_PER_HOST);
+    }
+
+    #[test]
+    fn line_breaks_in_header_values_are_rejected_before_sending() {
+        for value in ["text/html\r\nX-Injected: 1", "text/html\nX-Injected: 1"] {
+            let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+            let err = client(&transport)
+                .with_accept(value)
+                .fetch("http://example.com/")
+                .unwrap_err();
+            assert_eq!(err.code(), ErrorCode::InvalidArgument);
+            assert_eq!(err.context_chain(), ["Header value"]);
+            assert!(transport.requests().is_empty());
+        }
+    }
+
+    #[test]
+    fn line_breaks_in_the_request_target_are_rejected() {
+        let url = Url::parse("http://example.com/").unwrap();
+        for target in ["/a\r\nX-Injected: 1", "/a\nX-Injected: 1"] {
+            let request_line = RequestLine {
+                method: &HttpMethod::Get,
+                target,
+                http_version: HttpVersion::Http11,
+            };
+            let err = serialize_request(&url, &request_line, &[]).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::InvalidArgument);
+            assert_eq!(err.context_chain(), ["Request target"]);
+        }
+    }
+
+    #[test]
+    fn configured_http_version_is_sent_in_the_request_line() {
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+        client(&transport)
+            .with_http_version(HttpVersion::Http10)
+            .fetch("http://example.com/")
+            .unwrap();
+        assert!(transport.requests()[0].starts_with("GET / HTTP/1.0\r\n"));
+    }
+
+    #[test]
+    fn unparseable_response_is_retried_as_http10_when_enabled() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, b"garbage\r\n\r\n")
+                .with_response("example.com", 80, OK),
+        );
+        let response = client(&transport)
+            .with_http10_fallback(true)
+            .fetch("http://example.com/")
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("GET / HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with("GET / HTTP/1.0\r\n"));
+
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, b"garbage\r\n\r\n")
+                .with_response("example.com", 80, OK),
+        );
+        assert!(client(&transport).fetch("http://example.com/").is_err());
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn cookie_set_at_login_is_sent_on_the_next_request() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response(
+                    "example.com",
+                    80,
+                    b"HTTP/1.1 200 OK\r\nSet-Cookie: session=abc123; Path=/\r\n\
+                    Content-Length: 0\r\n\r\n",
+                )
+                .with_response("example.com", 80, OK),
+        );
+        let client = client(&transport);
+        client.fetch("http://example.com/login").unwrap();
+        client.fetch("http://example.com/inbox").unwrap();
+        let requests = transport.requests();
+        assert!(!requests[0].contains("Cookie:"));
+        assert!(requests[1].contains("\r\nCookie: session=abc123\r\n"));
+    }
+
+    #[test]
+    fn cancelling_mid_read_stops_the_fetch() {
+        let mut response = b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n".to_vec();
+        response.extend_from_slice(&[b'a'; 1000]);
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("example.com", 80, &response)
+                .with_read_delay(Duration::from_millis(5)),
+        );
+        let cancellation_token = CancellationToken::new();
+        let canceller = {
+            let cancellation_token = cancellation_token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                cancellation_token.cancel();
+            })
+        };
+        let start = Instant::now();
+        let err = client(&transport)
+            .fetch_cancellable("http://example.com/", &cancellation_token)
+            .unwrap_err();
+        canceller.join().unwrap();
+        assert_eq!(err.code(), ErrorCode::Cancelled);
+        // The whole body would have taken five seconds to trickle in
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn chunked_trailers_are_exposed_separately_from_headers() {
+        let transport = Arc::new(MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: Content-MD5\r\n\r\n\
+            5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\
+            Content-MD5: 5eb63bbbe01eeed093cb22bb8f5acdc3\r\nServer-Timing: total;dur=12\r\n\r\n",
+        ));
+        let response = client(&transport).fetch("http://example.com/").unwrap();
+        assert_eq!(response.body(), b"hello world");
+        assert_eq!(
+            response.trailer("content-md5"),
+            Some("5eb63bbbe01eeed093cb22bb8f5acdc3")
+        );
+        assert_eq!(response.trailers().len(), 2);
+        assert_eq!(response.header("Content-MD5"), None);
+    }
+
+    #[test]
+    fn redirected_fetch_records_a_hop_per_request() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response(
+                    "example.com",
+                    80,
+                    b"HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n",
+                )
+                .with_response("example.com", 80, OK)
+                .with_read_delay(Duration::from_millis(1)),
+        );
+        let response = client(&transport).fetch("http://example.com/old").unwrap();
+        let timeline = response.timeline();
+        let hops: Vec<(String, u16)> = timeline
+            .hops
+            .iter()
+            .map(|hop| (hop.url.to_string(), hop.status))
+            .collect();
+        assert_eq!(
+            hops,
+            [
+                (String::from("http://example.com/old"), 301),
+                (String::from("http://example.com/new"), 200)
+            ]
+        );
+        for hop in &timeline.hops {
+            assert!(hop.timings.first_byte > Duration::ZERO);
+            assert!(hop.timings.total >= hop.timings.first_byte);
+            assert!(hop.timings.tls_handshake.is_none());
+        }
+        let hop_total: Duration = timeline.hops.iter().map(|hop| hop.timings.total).sum();
+        assert!(timeline.total >= hop_total);
+    }
+
+    #[test]
+    fn bearer_token_is_sent_but_never_shown() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("api.test", 80, OK)
+                .with_response("api.test", 80, OK),
+        );
+        let client = client(&transport).with_bearer_token(BearerToken::new("s3cret".into()));
+        let response = client.fetch("http://api.test/").unwrap();
+        client
+            .fetch_with_bearer_token("http://api.test/", &BearerToken::new("other".into()))
+            .unwrap();
+        let requests = transport.requests();
+        assert!(requests[0].contains("\r\nAuthorization: Bearer s3cret\r\n"));
+        // A per-request token replaces the client's
+        assert!(requests[1].contains("\r\nAuthorization: Bearer other\r\n"));
+        assert!(!requests[1].contains("s3cret"));
+
+        let token = BearerToken::new("s3cret".into());
+        assert_eq!(format!("{token:?}"), "BearerToken(<redacted>)");
+        assert!(!format!("{:?}", response.timeline()).contains("s3cret"));
+    }
+
+    #[test]
+    fn head_response_has_headers_and_no_body() {
+        // A HEAD response announces the body's length but never sends it
+        let transport = Arc::new(MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/pdf\r\nContent-Length: 1048576\r\n\r\n",
+        ));
+        let response = client(&transport)
+            .head("http://example.com/big.pdf")
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("Content-Length"), Some("1048576"));
+        assert_eq!(response.mime_type().as_deref(), Some("application/pdf"));
+        assert!(response.body().is_empty());
+        assert!(transport.requests()[0].starts_with("HEAD /big.pdf HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn read_buffer_size_does_not_change_the_response() {
+        let body: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let mut sized =
+            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+        sized.extend_from_slice(&body);
+        let mut chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in body.chunks(1234) {
+            chunked.extend(format!("{:x}\r\n", chunk.len()).bytes());
+            chunked.extend_from_slice(chunk);
+            chunked.extend(b"\r\n");
+        }
+        chunked.extend(b"0\r\n\r\n");
+
+        for response in [&sized, &chunked] {
+            for read_buffer_size in [1, 7, 4096, DEFAULT_READ_BUFFER_SIZE] {
+                let transport =
+                    Arc::new(MockTransport::new().with_response("example.com", 80, response));
+                let fetched = client(&transport)
+                    .with_read_buffer_size(read_buffer_size)
+                    .fetch("http://example.com/")
+                    .unwrap();
+                assert_eq!(fetched.body(), body, "buffer of {read_buffer_size} bytes");
+            }
+        }
+    }
+
+    /// A redirect response sending the client to `location`.
+    fn redirect(status: &str, location: &str) -> Vec<u8> {
+        format!("HTTP/1.1 {status}\r\nLocation: {location}\r\nContent-Length: 0\r\n\r\n")
+            .into_bytes()
+    }
+
+    #[test]
+    fn mock_transport_drives_a_redirect_chain_across_hosts() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("a.test", 80, &redirect("302 Found", "http://b.test/x"))
+                .with_response("b.test", 80, &redirect("301 Moved Permanently", "y?q=1"))
+                .with_response("b.test", 80, OK),
+        );
+        let response = client(&transport).fetch("http://a.test/").unwrap();
+        assert_eq!(response.body(), b"ok");
+        assert_eq!(
+            response.final_url().unwrap().to_string(),
+            "http://b.test/y?q=1"
+        );
+        let chain: Vec<String> = response
+            .redirect_chain()
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        assert_eq!(chain, ["http://a.test/", "http://b.test/x"]);
+        let requests = transport.requests();
+        assert!(requests[0].starts_with("GET / HTTP/1.1\r\n"));
+        assert!(requests[0].contains("\r\nHost: a.test\r\n"));
+        assert!(requests[1].starts_with("GET /x HTTP/1.1\r\n"));
+        assert!(requests[1].contains("\r\nHost: b.test\r\n"));
+        assert!(requests[2].starts_with("GET /y?q=1 HTTP/1.1\r\n"));
+    }
+
+    /// Blocks `tracker.example` and sends requests for `old.test` to `new.test`.
+    struct TrackerFilter;
+
+    impl UrlFilter for TrackerFilter {
+        fn filter(&self, url: &Url) -> FilterAction {
+            match url.host() {
+                "tracker.example" => FilterAction::Block,
+                "old.test" => FilterAction::Rewrite(
+                    Url::parse(&url.to_string().replace("old.test", "new.test")).unwrap(),
+                ),
+                _ => FilterAction::Allow,
+            }
+        }
+    }
+
+    #[test]
+    fn url_filter_blocks_without_a_network_call() {
+        let transport = Arc::new(MockTransport::new().with_response("tracker.example", 80, OK));
+        let error = client(&transport)
+            .with_url_filter(Box::new(TrackerFilter))
+            .fetch("http://tracker.example/pixel.gif")
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::PermissionDenied);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[test]
+    fn url_filter_rewrite_fetches_the_rewritten_host() {
+        let transport = Arc::new(MockTransport::new().with_response("new.test", 80, OK));
+        let response = client(&transport)
+            .with_url_filter(Box::new(TrackerFilter))
+            .fetch("http://old.test/page")
+            .unwrap();
+        assert_eq!(response.body(), b"ok");
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /page HTTP/1.1\r\n"));
+        assert!(requests[0].contains("\r\nHost: new.test\r\n"));
+    }
+
+    #[test]
+    fn url_filter_stops_a_redirect_into_a_blocked_host() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response(
+                    "a.test",
+                    80,
+                    &redirect("302 Found", "http://tracker.example/pixel.gif"),
+                )
+                .with_response("tracker.example", 80, OK),
+        );
+        let error = client(&transport)
+            .with_url_filter(Box::new(TrackerFilter))
+            .fetch("http://a.test/")
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::PermissionDenied);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("\r\nHost: a.test\r\n"));
+    }
+
+    #[test]
+    fn redirects_past_the_limit_are_an_error() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("a.test", 80, &redirect("302 Found", "/1"))
+                .with_response("a.test", 80, &redirect("302 Found", "/2"))
+                .with_response("a.test", 80, OK),
+        );
+        let err = client(&transport)
+            .with_max_redirects(1)
+            .fetch("http://a.test/")
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ResourceExhausted);
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    /// Every progress report a fetch of `response` makes, reading `read_buffer_size` at a time.
+    fn progress_reports(response: &[u8], read_buffer_size: usize) -> Vec<DownloadProgress> {
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, response));
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        client(&transport)
+            .with_read_buffer_size(read_buffer_size)
+            .with_progress_callback(Box::new(move |progress: DownloadProgress| {
+                seen.lock().unwrap().push(progress)
+            }))
+            .fetch("http://example.com/")
+            .unwrap();
+        let reports = reports.lock().unwrap().clone();
+        reports
+    }
+
+    #[test]
+    fn progress_counts_increase_up_to_the_body_length() {
+        let mut response = b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n".to_vec();
+        response.extend_from_slice(&[b'a'; 1000]);
+        let reports = progress_reports(&response, 64);
+        assert!(reports.len() > 1);
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].received < pair[1].received));
+        assert_eq!(reports.last().unwrap().received, 1000);
+        assert!(reports.iter().all(|progress| progress.total == Some(1000)));
+
+        // Chunked bodies have no known total
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let reports = progress_reports(chunked, 4);
+        assert!(reports.iter().all(|progress| progress.total.is_none()));
+        assert_eq!(reports.last().unwrap().received, 11);
+    }
+
+    #[test]
+    fn accept_language_is_sent_and_can_be_overridden() {
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+        client(&transport).fetch("http://example.com/").unwrap();
+        assert!(transport.requests()[0].contains("\r\nAccept-Language: "));
+
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+        client(&transport)
+            .with_accept_language("fr-CH, fr;q=0.9")
+            .fetch("http://example.com/")
+            .unwrap();
+        assert!(transport.requests()[0].contains("\r\nAccept-Language: fr-CH, fr;q=0.9\r\n"));
+    }
+
+    #[test]
+    fn each_method_is_serialized_into_the_request_line() {
+        let methods = [
+            (HttpMethod::Get, "GET"),
+            (HttpMethod::Head, "HEAD"),
+            (HttpMethod::Post, "POST"),
+            (HttpMethod::Put, "PUT"),
+            (HttpMethod::Delete, "DELETE"),
+            (HttpMethod::Patch, "PATCH"),
+            (HttpMethod::Options, "OPTIONS"),
+            (HttpMethod::Custom(String::from("PROPFIND")), "PROPFIND"),
+        ];
+        for (method, name) in methods {
+            let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+            client(&transport)
+                .request_with_body(&method, "http://example.com/a?b", b"")
+                .unwrap();
+            let request_line = format!("{name} /a?b HTTP/1.1\r\n");
+            assert!(transport.requests()[0].starts_with(&request_line), "{name}");
+        }
+    }
+
+    #[test]
+    fn server_wide_options_uses_the_asterisk_form() {
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+        client(&transport)
+            .options_server_wide("http://example.com/ignored/path")
+            .unwrap();
+        assert!(transport.requests()[0].starts_with("OPTIONS * HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn custom_method_with_a_line_break_is_rejected() {
+        let transport = Arc::new(MockTransport::new());
+        let method = HttpMethod::Custom(String::from("GET / HTTP/1.1\r\nX-Injected: 1"));
+        let err = client(&transport)
+            .request_with_body(&method, "http://example.com/", b"")
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[test]
+    fn constructed_404_response_exposes_its_headers_and_text() {
+        let headers = vec![(
+            String::from("Content-Type"),
+            String::from("text/plain; charset=utf-8"),
+        )];
+        let response = HttpResponse::new(404, "Not Found", headers, b"no such page".to_vec())
+            .with_header("X-Served-By", "mock");
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.reason(), "Not Found");
+        assert_eq!(response.version(), HttpVersion::Http11);
+        assert_eq!(
+            response.header("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(response.header("X-SERVED-BY"), Some("mock"));
+        assert_eq!(response.header("Location"), None);
+        assert_eq!(response.mime_type().as_deref(), Some("text/plain"));
+        assert_eq!(response.text(), "no such page");
+        assert_eq!(response.final_url(), None);
+
+        let response = HttpResponse::ok().with_status(404, "Not Found");
+        assert_eq!((response.status(), response.reason()), (404, "Not Found"));
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn two_hop_redirect_reports_its_chain_and_final_url() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response(
+                    "example.com",
+                    80,
+                    &redirect("301 Moved Permanently", "/docs/"),
+                )
+                .with_response(
+                    "example.com",
+                    80,
+                    &redirect("307 Temporary Redirect", "v2/index"),
+                )
+                .with_response("example.com", 80, OK),
+        );
+        let response = client(&transport)
+            .fetch("http://example.com/start")
+            .unwrap();
+        let final_url = response.final_url().unwrap();
+        assert_eq!(final_url.to_string(), "http://example.com/docs/v2/index");
+        let chain: Vec<String> = response
+            .redirect_chain()
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        assert_eq!(
+            chain,
+            ["http://example.com/start", "http://example.com/docs/"]
+        );
+        // Relative links on the page resolve against where it ended up
+        assert_eq!(
+            final_url.join("../img/logo.png").unwrap().to_string(),
+            "http://example.com/docs/img/logo.png"
+        );
+
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, OK));
+        let response = client(&transport).fetch("http://example.com/page").unwrap();
+        assert!(response.redirect_chain().is_empty());
+        assert_eq!(
+            response.final_url().unwrap().to_string(),
+            "http://example.com/page"
+        );
+    }
+
+    #[test]
+    fn response_without_a_blank_line_after_its_headers_is_data_loss() {
+        let transport = Arc::new(MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nbody straight after the headers",
+        ));
+        let err = client(&transport).fetch("http://example.com/").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        assert!(err.to_string().contains("no blank line"), "{err}");
+    }
+
+    #[test]
+    fn oversized_header_block_is_data_loss() {
+        let mut response = b"HTTP/1.1 200 OK\r\n".to_vec();
+        while response.len() <= MAX_HEADER_SIZE + 100 {
+            response.extend(b"X-Filler: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n");
+        }
+        response.extend(b"\r\nok");
+        let transport = Arc::new(MockTransport::new().with_response("example.com", 80, &response));
+        let err = client(&transport).fetch("http://example.com/").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DataLoss);
+        assert!(err.to_string().contains("larger than 64 KiB"), "{err}");
     }
 }
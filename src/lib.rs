@@ -0,0 +1,10 @@
+pub mod cookie;
+pub mod error;
+pub mod font;
+pub mod history;
+pub mod html;
+pub mod http;
+pub mod rasterizer;
+pub mod renderer;
+pub mod transport;
+pub mod url;
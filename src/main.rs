@@ -1,70 +1,638 @@
+use capynet::{error, font, history, html, http, rasterizer, renderer, url};
 use eframe::egui;
 use egui::{ColorImage, TextureHandle};
+use error::{CapyError, ErrorCode};
+use history::History;
+use html::TextExtractor;
+use http::{CancellationToken, HttpClient, HttpResponse};
+use rasterizer::{GridFitRasterizer, SupersampleRasterizer};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use url::Url;
 
-mod error;
-mod font;
-mod http;
-mod renderer;
+const FONT_PATH: &str = "assets/fonts/arial.ttf";
+const DEFAULT_PAGE: &str =
+    "Welcome to CapyNet! Type a URL in the address bar and press Enter to open it.";
+const ERROR_BANNER_COLOR: [u8; 4] = [255, 225, 225, 255];
+// Page zoom changes by this much per key press, within these limits
+const ZOOM_STEP: f32 = 0.1;
+const MIN_ZOOM: f32 = 0.3;
+const MAX_ZOOM: f32 = 5.0;
 
-#[derive(Default)]
 pub struct MyApp {
     texture: Option<TextureHandle>,
+    // Pixels the page is drawn into, kept between redraws so the allocation is reused
+    bitmap: Vec<u8>,
+    font: font::Font,
+    client: Arc<HttpClient>,
+    history: History,
+    page_load: Option<PageLoad>,
+    // Contents of the address bar, which may differ from the current page while typing
+    address: String,
+    page_text: String,
+    // Plain text pages keep their indentation; HTML has already had its whitespace collapsed
+    preserve_spaces: bool,
+    // Why the last load failed, shown in a banner above the page
+    error: Option<CapyError>,
+    // Text is laid out at this multiple of its normal size
+    zoom: f32,
+}
+
+/// A page being fetched on a background thread.
+struct PageLoad {
+    cancellation_token: CancellationToken,
+    events: Receiver<LoadEvent>,
+    // Text of the HTML body received so far, shown until the whole page is in
+    extractor: TextExtractor,
+}
+
+enum LoadEvent {
+    // The next piece of an HTML body
+    Html(Vec<u8>),
+    // The page to show once the fetch is over, or why it failed
+    Done(Result<Page, CapyError>),
+}
+
+/// What to draw for a loaded page.
+struct Page {
+    text: String,
+    preserve_spaces: bool,
+}
+
+enum Navigation {
+    Open(String),
+    Back,
+    Forward,
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if self.texture.is_none() {
-            // FIXME: Handle window resizing. Texture should be redrawn whenever the window size changes.
-            self.init_texture(ctx)
+        self.poll_page_load();
+        self.zoom_with_keyboard(ctx);
+
+        let mut navigation = None;
+        egui::TopBottomPanel::top("address_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let back = egui::Button::new("<");
+                if ui.add_enabled(self.history.can_go_back(), back).clicked() {
+                    navigation = Some(Navigation::Back);
+                }
+                let forward = egui::Button::new(">");
+                if ui
+                    .add_enabled(self.history.can_go_forward(), forward)
+                    .clicked()
+                {
+                    navigation = Some(Navigation::Forward);
+                }
+                let address = egui::TextEdit::singleline(&mut self.address)
+                    .hint_text("Enter a URL")
+                    .desired_width(f32::INFINITY);
+                let response = ui.add(address);
+                if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                    navigation = Some(Navigation::Open(self.address.clone()));
+                }
+            });
+        });
+        match navigation {
+            Some(Navigation::Open(address)) => self.open(ctx, &address),
+            Some(Navigation::Back) => {
+                if let Some(url) = self.history.back().cloned() {
+                    self.show(ctx, &url);
+                }
+            }
+            Some(Navigation::Forward) => {
+                if let Some(url) = self.history.forward().cloned() {
+                    self.show(ctx, &url);
+                }
+            }
+            None => {}
         }
 
-        let texture_ref = self.texture.as_ref().unwrap();
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.image(texture_ref);
+            if self.texture.is_none() {
+                // FIXME: Handle window resizing. Texture should be redrawn whenever the window size changes.
+                let size = ui.available_size();
+                self.init_texture(ctx, size.x as usize, size.y as usize);
+            }
+            if let Some(texture) = &self.texture {
+                ui.image(texture);
+            }
         });
     }
 }
 
 impl MyApp {
-    fn init_texture(&mut self, ctx: &egui::Context) {
-        let window_width = ctx.available_rect().width() as usize - 18;
-        let window_height = ctx.available_rect().height() as usize - 18;
-        let mut bitmap = draw_pixels(window_width, window_height);
-
-        renderer::render_text(&mut bitmap, "hellocapy", 50, 50, window_width, 5).unwrap();
-
-        // FIXME: Implement font rendering
-        let parsed_font = font::parse_from_file("assets/fonts/arial.ttf");
-        match parsed_font {
-            Ok(f) => println!("parsed_font {:#?}", f),
-            Err(e) => println!("failed to parse font at filepath 'invalid_filepath': {}", e),
+    fn new(font: font::Font) -> Self {
+        Self {
+            texture: None,
+            bitmap: Vec::new(),
+            font,
+            client: Arc::new(HttpClient::new()),
+            history: History::new(),
+            page_load: None,
+            address: String::new(),
+            page_text: DEFAULT_PAGE.to_string(),
+            preserve_spaces: false,
+            error: None,
+            zoom: 1.0,
         }
+    }
+
+    /// Zooms the page in, out or back to normal on Ctrl and +, - or 0 (Cmd on macOS), like a
+    /// browser. The page is laid out again at the new text size.
+    fn zoom_with_keyboard(&mut self, ctx: &egui::Context) {
+        use egui::gui_zoom::kb_shortcuts;
+        let pressed = |shortcut| ctx.input_mut(|input| input.consume_shortcut(shortcut));
+        let zoom = if pressed(&kb_shortcuts::ZOOM_RESET) {
+            1.0
+        } else if pressed(&kb_shortcuts::ZOOM_IN) || pressed(&kb_shortcuts::ZOOM_IN_SECONDARY) {
+            self.zoom + ZOOM_STEP
+        } else if pressed(&kb_shortcuts::ZOOM_OUT) {
+            self.zoom - ZOOM_STEP
+        } else {
+            return;
+        };
+        // Rounded to a whole step so repeated presses don't drift
+        let zoom = ((zoom / ZOOM_STEP).round() * ZOOM_STEP).clamp(MIN_ZOOM, MAX_ZOOM);
+        if zoom != self.zoom {
+            self.zoom = zoom;
+            // Redraw the page on the next frame
+            self.texture = None;
+        }
+    }
+
+    /// Navigates to an address typed by the user, assuming http:// when no scheme is given.
+    fn open(&mut self, ctx: &egui::Context, address: &str) {
+        let address = address.trim();
+        let address = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        };
+        match Url::parse(&address) {
+            Ok(url) => {
+                self.history.push(url.clone());
+                self.show(ctx, &url);
+            }
+            Err(e) => self.set_error(e.with_context(&address)),
+        }
+    }
+
+    /// Starts fetching `url` in the background without touching the history. HTML pages are
+    /// shown progressively as their body arrives.
+    fn show(&mut self, ctx: &egui::Context, url: &Url) {
+        if let Some(page_load) = self.page_load.take() {
+            page_load.cancellation_token.cancel();
+        }
+        self.address = url.to_string();
+        self.error = None;
+        self.set_page_text(format!("Loading {}...", url));
+
+        let cancellation_token = CancellationToken::new();
+        let (sender, events) = mpsc::channel();
+        let client = Arc::clone(&self.client);
+        let thread_token = cancellation_token.clone();
+        let ctx = ctx.clone();
+        let url = url.clone();
+        thread::spawn(move || {
+            let mut on_body = |head: &HttpResponse, bytes: &[u8]| {
+                if (200..300).contains(&head.status()) && is_html(head) {
+                    // The receiver is gone once the user navigated elsewhere, which is fine
+                    let _ = sender.send(LoadEvent::Html(bytes.to_vec()));
+                    ctx.request_repaint();
+                }
+            };
+            let result = client
+                .fetch_streaming(&url.to_string(), &thread_token, &mut on_body)
+                .and_then(|response| {
+                    response.error_for_status()?;
+                    Ok(Page {
+                        text: page_text(&response),
+                        preserve_spaces: !is_html(&response),
+                    })
+                })
+                .map_err(|e| e.with_context(&url.to_string()));
+            let _ = sender.send(LoadEvent::Done(result));
+            ctx.request_repaint();
+        });
+        self.page_load = Some(PageLoad {
+            cancellation_token,
+            events,
+            extractor: TextExtractor::new(),
+        });
+    }
+
+    /// Shows whatever the background fetch has delivered since the last frame.
+    fn poll_page_load(&mut self) {
+        let Some(page_load) = &mut self.page_load else {
+            return;
+        };
+        let mut page = None;
+        let mut error = None;
+        let mut done = false;
+        for event in page_load.events.try_iter() {
+            match event {
+                LoadEvent::Html(bytes) => {
+                    page_load.extractor.feed(&bytes);
+                    page = Some(Page {
+                        text: page_load.extractor.text().to_string(),
+                        preserve_spaces: false,
+                    });
+                }
+                LoadEvent::Done(Ok(loaded_page)) => {
+                    page = Some(loaded_page);
+                    done = true;
+                }
+                LoadEvent::Done(Err(e)) => {
+                    error = Some(e);
+                    done = true;
+                }
+            }
+        }
+        if done {
+            self.page_load = None;
+        }
+        if let Some(error) = error {
+            self.set_error(error);
+        } else if let Some(page) = page {
+            self.preserve_spaces = page.preserve_spaces;
+            self.set_page_text(page.text);
+        }
+    }
+
+    /// Replaces the page with a banner explaining why it couldn't be shown.
+    fn set_error(&mut self, error: CapyError) {
+        self.error = Some(error);
+        self.set_page_text(String::new());
+    }
+
+    fn set_page_text(&mut self, page_text: String) {
+        self.page_text = page_text;
+        // Redraw the page on the next frame
+        self.texture = None;
+    }
+
+    /// How page text is laid out in a window `window_width` pixels wide, at the current zoom.
+    fn text_options(&self, window_width: usize) -> renderer::TextOptions {
+        renderer::TextOptions {
+            size: renderer::TextOptions::default().size * self.zoom,
+            max_width: window_width.saturating_sub(100),
+            // Body text is small enough to benefit from snapping to the pixel grid
+            rasterizer: Arc::new(GridFitRasterizer {
+                inner: SupersampleRasterizer,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn init_texture(&mut self, ctx: &egui::Context, window_width: usize, window_height: usize) {
+        let window_width = window_width.max(1);
+        let window_height = window_height.max(1);
+        clear_pixels(&mut self.bitmap, window_width, window_height);
+        let options = self.text_options(window_width);
+        let bitmap = &mut self.bitmap;
+        let mut page_top = 50;
+        if let Some(error) = &self.error {
+            let text = format!("{}\n{}", error_headline(error), error_summary(error));
+            let extent = renderer::measure_text(&self.font, &text, &options);
+            // The banner spans the page's margins with some padding around the text
+            let banner_height = extent.height + 20;
+            let banner = renderer::Rect {
+                x: 40,
+                y: 40,
+                width: window_width.saturating_sub(80),
+                height: banner_height,
+            };
+            renderer::fill_rect(
+                bitmap,
+                window_width,
+                &banner,
+                ERROR_BANNER_COLOR,
+                options.blending,
+            );
+            renderer::render_text(bitmap, &text, 50, 50, window_width, &self.font, &options)
+                .unwrap();
+            page_top += banner_height + 20;
+        }
+        let page_options = renderer::TextOptions {
+            preserve_spaces: self.preserve_spaces,
+            ..options
+        };
+        renderer::render_text(
+            bitmap,
+            &self.page_text,
+            50,
+            page_top,
+            window_width,
+            &self.font,
+            &page_options,
+        )
+        .unwrap();
 
         let color_image: ColorImage =
-            ColorImage::from_rgba_unmultiplied([window_width, window_height], &bitmap);
+            ColorImage::from_rgba_unmultiplied([window_width, window_height], bitmap);
         self.texture = Some(ctx.load_texture("bitmap", color_image, Default::default()));
     }
 }
 
-fn draw_pixels(width: usize, height: usize) -> Vec<u8> {
-    let mut pixels = vec![0; width * height * 4];
-    for y in 0..height {
-        for x in 0..width {
-            let offset = (y * width + x) * 4;
-            pixels[offset] = 255; // Red
-            pixels[offset + 1] = 255; // Green
-            pixels[offset + 2] = 255; // Blue
-            pixels[offset + 3] = 255; // Alpha
+/// Turns a response into the text to display, based on its MIME type. Plain text has its line
+/// breaks normalized to `\n`, so the page text never carries a stray `\r`.
+fn page_text(response: &HttpResponse) -> String {
+    match response.mime_type().as_deref() {
+        _ if is_html(response) => html::extract_text(&response.text()),
+        Some(mime_type) if mime_type.starts_with("text/") => {
+            renderer::normalize_line_breaks(&response.text())
         }
+        // Servers that don't say what they sent are most often serving plain text
+        None => renderer::normalize_line_breaks(&response.text()),
+        Some(mime_type) => format!("Unsupported content type: {}", mime_type),
     }
-    pixels
+}
+
+fn is_html(response: &HttpResponse) -> bool {
+    matches!(
+        response.mime_type().as_deref(),
+        Some("text/html" | "application/xhtml+xml")
+    )
+}
+
+/// A short explanation of a failed load, telling timeouts and connection problems apart from
+/// errors reported by the server.
+fn error_headline(error: &CapyError) -> &'static str {
+    match error.code() {
+        ErrorCode::DeadlineExceeded => "The server took too long to respond",
+        ErrorCode::Unavailable => "Couldn't reach the server",
+        ErrorCode::NotFound => "Page not found",
+        ErrorCode::Unauthenticated | ErrorCode::PermissionDenied => "Access denied",
+        ErrorCode::Cancelled => "Loading was cancelled",
+        _ => "Couldn't load the page",
+    }
+}
+
+/// Flattens an error and its causes onto one line so it can be rendered in the page.
+fn error_summary(error: &CapyError) -> String {
+    let message = error.to_string();
+    let message: Vec<&str> = message.lines().map(str::trim).collect();
+    message.join(" ")
+}
+
+/// Reads the URL to open from `capynet <url>` or `capynet --url <url>`.
+fn url_from_args(mut args: impl Iterator<Item = String>) -> Option<String> {
+    args.next(); // Skip the program name
+    let arg = args.next()?;
+    if arg == "--url" {
+        args.next()
+    } else {
+        Some(arg)
+    }
+}
+
+/// Resets `pixels` to an opaque white `width` x `height` RGBA image. The buffer only
+/// reallocates when it has to grow, so redrawing at the same size never allocates.
+fn clear_pixels(pixels: &mut Vec<u8>, width: usize, height: usize) {
+    pixels.clear();
+    pixels.resize(width * height * 4, 255);
 }
 
 fn main() {
+    let font = match font::parse_from_file(FONT_PATH, font::ParseOptions::default()) {
+        Ok(font) => font,
+        Err(e) => {
+            eprintln!("failed to parse font at filepath '{}': {}", FONT_PATH, e);
+            return;
+        }
+    };
+    let mut app = MyApp::new(font);
+    let url = url_from_args(std::env::args());
+
     eframe::run_native(
         "CapyNet",
         eframe::NativeOptions::default(),
-        Box::new(|_cc| Box::new(MyApp::default())),
+        Box::new(move |cc| {
+            // Ctrl +/- zooms the page rather than egui's own widgets
+            cc.egui_ctx
+                .options_mut(|options| options.zoom_with_keyboard = false);
+            if let Some(url) = url {
+                app.open(&cc.egui_ctx, &url);
+            }
+            Box::new(app)
+        }),
     )
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use capynet::transport;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn url_is_read_from_either_argument_form() {
+        assert_eq!(
+            url_from_args(args(&["capynet", "http://example.com"])),
+            Some(String::from("http://example.com"))
+        );
+        assert_eq!(
+            url_from_args(args(&["capynet", "--url", "http://example.com"])),
+            Some(String::from("http://example.com"))
+        );
+        assert_eq!(url_from_args(args(&["capynet"])), None);
+        assert_eq!(url_from_args(args(&["capynet", "--url"])), None);
+    }
+
+    fn response(content_type: Option<&str>, body: &[u8]) -> HttpResponse {
+        let response = HttpResponse::ok().with_body(body);
+        match content_type {
+            Some(content_type) => response.with_header("Content-Type", content_type),
+            None => response,
+        }
+    }
+
+    #[test]
+    fn page_text_dispatches_on_the_content_type() {
+        let page = b"<html><body><p>Hello</p></body></html>";
+        let html = page_text(&response(Some("text/html; charset=utf-8"), page));
+        assert!(html.contains("Hello") && !html.contains('<'));
+        assert_eq!(
+            page_text(&response(Some("text/plain"), page)),
+            String::from_utf8_lossy(page)
+        );
+        assert_eq!(page_text(&response(None, b"a\r\nb")), "a\nb");
+        // Binary bodies never reach text layout
+        assert_eq!(
+            page_text(&response(Some("image/png"), b"\x89PNG\r\n\x1a\n")),
+            "Unsupported content type: image/png"
+        );
+    }
+
+    /// An app whose requests are answered by `transport`.
+    fn app_served_by(transport: transport::MockTransport) -> MyApp {
+        let font = font::parse_from_file(FONT_PATH, font::ParseOptions::default()).unwrap();
+        let mut app = MyApp::new(font);
+        app.client = Arc::new(HttpClient::new().with_transport(Arc::new(transport)));
+        app
+    }
+
+    /// Polls the background load until it has finished.
+    fn wait_for_load(app: &mut MyApp) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while app.page_load.is_some() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "page load never finished"
+            );
+            app.poll_page_load();
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn failed_load_shows_an_error_instead_of_the_page() {
+        let mut app = app_served_by(transport::MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+        ));
+        let ctx = egui::Context::default();
+        app.open(&ctx, "example.com/missing");
+        wait_for_load(&mut app);
+        let error = app.error.as_ref().unwrap();
+        assert_eq!(error.code(), ErrorCode::NotFound);
+        assert_eq!(error_headline(error), "Page not found");
+        assert!(error_summary(error).contains("http://example.com/missing"));
+        assert!(app.page_text.is_empty());
+
+        // Connection failures read differently from errors the server reports
+        let mut app = app_served_by(transport::MockTransport::new());
+        app.open(&ctx, "example.com");
+        wait_for_load(&mut app);
+        let error = app.error.as_ref().unwrap();
+        assert_eq!(error.code(), ErrorCode::Unavailable);
+        assert_eq!(error_headline(error), "Couldn't reach the server");
+    }
+
+    #[test]
+    fn successful_load_clears_the_error() {
+        let mut app = app_served_by(transport::MockTransport::new().with_response(
+            "example.com",
+            80,
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello",
+        ));
+        app.set_error(CapyError::new(ErrorCode::Unavailable, "connection failed"));
+        app.open(&egui::Context::default(), "example.com");
+        wait_for_load(&mut app);
+        assert!(app.error.is_none());
+        assert_eq!(app.page_text, "hello");
+    }
+
+    #[test]
+    fn invalid_utf8_renders_as_a_replacement_glyph() {
+        let text = page_text(&response(Some("text/plain"), b"ok \xFF\xFE ok"));
+        assert_eq!(text, "ok \u{FFFD}\u{FFFD} ok");
+
+        let font = font::parse_from_file(FONT_PATH, font::ParseOptions::default()).unwrap();
+        let options = renderer::TextOptions::default();
+        let lines = renderer::layout_text(&font, &text, &options);
+        let replacement = lines[0]
+            .glyphs
+            .iter()
+            .find(|glyph| glyph.char == '\u{FFFD}')
+            .unwrap();
+        assert!(replacement.advance > 0.0);
+
+        // Arial has no U+FFFD, so it is drawn as the .notdef box
+        assert_eq!(replacement.glyph_id, 0);
+        let (width, height) = (replacement.advance.ceil() as usize + 2, 30);
+        let mut pixels = vec![255; width * height * 4];
+        renderer::render_text(&mut pixels, "\u{FFFD}", 0, 0, width, &font, &options).unwrap();
+        assert!(pixels.iter().any(|&channel| channel < 255));
+    }
+
+    #[test]
+    fn mixed_line_endings_normalize_to_newlines() {
+        let body = b"crlf\r\nlf\ncr\rend\r\n\r\n";
+        let text = page_text(&response(Some("text/plain"), body));
+        assert_eq!(text, "crlf\nlf\ncr\nend\n\n");
+
+        // No stray carriage return reaches layout as a glyph
+        let font = font::parse_from_file(FONT_PATH, font::ParseOptions::default()).unwrap();
+        let lines = renderer::layout_text(&font, &text, &renderer::TextOptions::default());
+        assert!(lines.len() >= 4);
+        assert!(lines
+            .iter()
+            .flat_map(|line| &line.glyphs)
+            .all(|glyph| glyph.char != '\r'));
+    }
+
+    #[test]
+    fn redraws_at_the_same_size_reuse_the_bitmap() {
+        let mut app = app_served_by(transport::MockTransport::new());
+        app.page_text = String::from("Some page text");
+        let ctx = egui::Context::default();
+        app.init_texture(&ctx, 320, 240);
+        let (pointer, capacity) = (app.bitmap.as_ptr(), app.bitmap.capacity());
+        assert!(app.bitmap.iter().any(|&channel| channel < 255));
+
+        app.page_text = String::from("Other text");
+        app.init_texture(&ctx, 320, 240);
+        assert_eq!(app.bitmap.as_ptr(), pointer);
+        assert_eq!(app.bitmap.capacity(), capacity);
+        assert_eq!(app.bitmap.len(), 320 * 240 * 4);
+
+        // Shrinking the window doesn't reallocate either
+        app.init_texture(&ctx, 200, 100);
+        assert_eq!(app.bitmap.as_ptr(), pointer);
+        assert_eq!(app.bitmap.len(), 200 * 100 * 4);
+    }
+
+    #[test]
+    fn doubling_the_zoom_doubles_the_text_height() {
+        let mut app = app_served_by(transport::MockTransport::new());
+        let text = "One line\nand another";
+        let height = |app: &MyApp| {
+            renderer::measure_text(&app.font, text, &app.text_options(2000)).height as f32
+        };
+        let normal = height(&app);
+        app.zoom = 2.0;
+        let zoomed = height(&app);
+        assert!((zoomed / normal - 2.0).abs() < 0.1, "{normal} vs {zoomed}");
+    }
+
+    #[test]
+    fn keyboard_shortcuts_step_the_zoom_within_its_limits() {
+        let mut app = app_served_by(transport::MockTransport::new());
+        let ctx = egui::Context::default();
+        let press = |app: &mut MyApp, shortcut: &egui::KeyboardShortcut| {
+            let event = egui::Event::Key {
+                key: shortcut.logical_key,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: shortcut.modifiers,
+            };
+            let input = egui::RawInput {
+                events: vec![event],
+                modifiers: shortcut.modifiers,
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| app.zoom_with_keyboard(ctx));
+        };
+        use egui::gui_zoom::kb_shortcuts;
+        press(&mut app, &kb_shortcuts::ZOOM_IN);
+        assert!((app.zoom - 1.1).abs() < 1e-6);
+        for _ in 0..20 {
+            press(&mut app, &kb_shortcuts::ZOOM_OUT);
+        }
+        assert_eq!(app.zoom, MIN_ZOOM);
+        press(&mut app, &kb_shortcuts::ZOOM_RESET);
+        assert_eq!(app.zoom, 1.0);
+    }
+}
@@ -0,0 +1,591 @@
+use crate::font::{ContourF32, PointF32};
+use std::fmt::Debug;
+
+// Subsamples per pixel along each axis in SupersampleRasterizer
+const SUPERSAMPLE: usize = 4;
+// Maximum distance in pixels between a flattened curve and the real one
+const FLATTEN_TOLERANCE: f32 = 0.1;
+
+/// A straight piece of a flattened outline, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+/// Whole-pixel area covered by an outline. `left` and `top` are relative to the pen position
+/// on the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub left: i32,
+    pub top: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Per-pixel coverage in 0.0..=1.0, stored row by row over `bbox`.
+#[derive(Debug, Clone)]
+pub struct CoverageBuffer {
+    pub bbox: BoundingBox,
+    pub values: Vec<f32>,
+}
+
+impl CoverageBuffer {
+    pub fn get(&self, col: usize, row: usize) -> f32 {
+        self.values[row * self.bbox.width + col]
+    }
+}
+
+/// Fills a flattened outline, computing how much of each pixel in `bbox` it covers.
+pub trait Rasterizer: Debug {
+    fn fill(&self, outline: &[Segment], bbox: &BoundingBox) -> CoverageBuffer;
+}
+
+/// Samples SUPERSAMPLE x SUPERSAMPLE points per pixel with the non-zero winding rule.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SupersampleRasterizer;
+
+impl Rasterizer for SupersampleRasterizer {
+    fn fill(&self, outline: &[Segment], bbox: &BoundingBox) -> CoverageBuffer {
+        sample_coverage(outline, bbox, SUPERSAMPLE)
+    }
+}
+
+/// Tests only each pixel's center, so every pixel is either fully covered or empty. The
+/// fastest rasterizer, with the hard edges of a bitmap font.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonochromeRasterizer;
+
+impl Rasterizer for MonochromeRasterizer {
+    fn fill(&self, outline: &[Segment], bbox: &BoundingBox) -> CoverageBuffer {
+        sample_coverage(outline, bbox, 1)
+    }
+}
+
+/// Samples `samples_per_axis` x `samples_per_axis` evenly spaced points per pixel with the
+/// non-zero winding rule.
+fn sample_coverage(
+    outline: &[Segment],
+    bbox: &BoundingBox,
+    samples_per_axis: usize,
+) -> CoverageBuffer {
+    let BoundingBox {
+        left,
+        top,
+        width,
+        height,
+    } = *bbox;
+    let mut counts = vec![0u32; width * height];
+
+    let samples = samples_per_axis as f32;
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for sub_row in 0..height * samples_per_axis {
+        let sample_y = top as f32 + (sub_row as f32 + 0.5) / samples;
+        crossings.clear();
+        for segment in outline {
+            let (y0, y1) = (segment.y0, segment.y1);
+            if (y0 <= sample_y && sample_y < y1) || (y1 <= sample_y && sample_y < y0) {
+                let t = (sample_y - y0) / (y1 - y0);
+                let winding = if y1 > y0 { 1 } else { -1 };
+                crossings.push((segment.x0 + t * (segment.x1 - segment.x0), winding));
+            }
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let row = sub_row / samples_per_axis;
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            if winding == 0 {
+                continue;
+            }
+            // Count the subsample columns whose centers fall inside this span
+            let first = ((pair[0].0 - left as f32) * samples - 0.5).ceil().max(0.0) as usize;
+            let last = ((pair[1].0 - left as f32) * samples - 0.5).ceil().max(0.0) as usize;
+            for sub_col in first..last.min(width * samples_per_axis) {
+                counts[row * width + sub_col / samples_per_axis] += 1;
+            }
+        }
+    }
+
+    let total = (samples_per_axis * samples_per_axis) as f32;
+    CoverageBuffer {
+        bbox: *bbox,
+        values: counts.iter().map(|&count| count as f32 / total).collect(),
+    }
+}
+
+/// Computes the exact area each pixel covers by accumulating the signed area under every
+/// segment and summing it along each row. Faster than supersampling and smoother on edges, but
+/// overlapping contours are clamped rather than resolved by winding.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanlineRasterizer;
+
+impl Rasterizer for ScanlineRasterizer {
+    fn fill(&self, outline: &[Segment], bbox: &BoundingBox) -> CoverageBuffer {
+        let BoundingBox {
+            left,
+            top,
+            width,
+            height,
+        } = *bbox;
+        // Lines ending on the right edge spill a little past the last pixel of the buffer
+        let mut areas = vec![0.0f32; width * height + 2];
+        for segment in outline {
+            let start = (segment.x0 - left as f32, segment.y0 - top as f32);
+            let end = (segment.x1 - left as f32, segment.y1 - top as f32);
+            accumulate_line(&mut areas, width, height, start, end);
+        }
+
+        // Each row's areas sum to zero, so one running sum over the whole buffer is enough
+        let mut sum = 0.0;
+        let values = areas[..width * height]
+            .iter()
+            .map(|area| {
+                sum += area;
+                sum.abs().min(1.0)
+            })
+            .collect();
+        CoverageBuffer {
+            bbox: *bbox,
+            values,
+        }
+    }
+}
+
+/// Wraps another rasterizer, first snapping the outline's horizontal edges (baseline, x-height,
+/// cap height, bar and stem edges, curve extremes) to whole pixel rows. Points in between are
+/// stretched to follow. A cheap stand-in for hinting that makes small text noticeably crisper;
+/// it isn't worth it above roughly 24px.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GridFitRasterizer<R> {
+    pub inner: R,
+}
+
+impl<R: Rasterizer> Rasterizer for GridFitRasterizer<R> {
+    fn fill(&self, outline: &[Segment], _bbox: &BoundingBox) -> CoverageBuffer {
+        let snapped = snap_horizontal_edges(outline);
+        self.inner.fill(&snapped, &bounding_box(&snapped))
+    }
+}
+
+fn snap_horizontal_edges(outline: &[Segment]) -> Vec<Segment> {
+    // Rows are measured from the baseline, which the renderer already puts on a pixel boundary
+    let mut edges: Vec<f32> = Vec::new();
+    for (index, segment) in outline.iter().enumerate() {
+        let is_flat =
+            (segment.y1 - segment.y0).abs() < 1e-3 && (segment.x1 - segment.x0).abs() > 0.25;
+        if is_flat {
+            edges.push(segment.y0);
+        }
+        // A vertical extreme where this segment meets the next one in the same contour
+        if let Some(next) = outline.get(index + 1) {
+            let joined = segment.x1 == next.x0 && segment.y1 == next.y0;
+            if joined && (segment.y0 - segment.y1) * (next.y1 - next.y0) > 0.0 {
+                edges.push(segment.y1);
+            }
+        }
+    }
+    edges.sort_by(f32::total_cmp);
+    edges.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+
+    // Keep features that were at least half a pixel apart on separate rows
+    let mut grid: Vec<(f32, f32)> = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let mut snapped = edge.round();
+        if let Some(&(previous, previous_snapped)) = grid.last() {
+            if edge - previous >= 0.5 && snapped <= previous_snapped {
+                snapped = previous_snapped + 1.0;
+            }
+        }
+        grid.push((edge, snapped));
+    }
+
+    let fit = |y: f32| -> f32 {
+        let after = grid.partition_point(|&(edge, _)| edge < y);
+        match (
+            after.checked_sub(1).map(|index| grid[index]),
+            grid.get(after),
+        ) {
+            (Some((y0, s0)), Some(&(y1, s1))) if y1 > y0 => s0 + (y - y0) * (s1 - s0) / (y1 - y0),
+            (Some((y0, s0)), _) => y + (s0 - y0),
+            (None, Some(&(y1, s1))) => y + (s1 - y1),
+            (None, None) => y,
+        }
+    };
+    outline
+        .iter()
+        .map(|segment| Segment {
+            x0: segment.x0,
+            y0: fit(segment.y0),
+            x1: segment.x1,
+            y1: fit(segment.y1),
+        })
+        .collect()
+}
+
+/// Adds the signed area to the right of a line to the pixels it crosses, in buffer coordinates.
+fn accumulate_line(
+    areas: &mut [f32],
+    width: usize,
+    height: usize,
+    start: (f32, f32),
+    end: (f32, f32),
+) {
+    if start.1 == end.1 {
+        return;
+    }
+    let (direction, start, end) = match start.1 < end.1 {
+        true => (1.0, start, end),
+        false => (-1.0, end, start),
+    };
+    let dxdy = (end.0 - start.0) / (end.1 - start.1);
+
+    let mut x = start.0.clamp(0.0, width as f32);
+    for row in start.1.max(0.0) as usize..height.min(end.1.ceil() as usize) {
+        let row_start = row * width;
+        let dy = ((row + 1) as f32).min(end.1) - (row as f32).max(start.1);
+        let x_next = (x + dxdy * dy).clamp(0.0, width as f32);
+        let area = dy * direction;
+        let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+        let x0_floor = x0.floor();
+        let x0_index = x0_floor as usize;
+        let x1_ceil = x1.ceil();
+        let x1_index = x1_ceil as usize;
+
+        if x1_index <= x0_index + 1 {
+            // The line stays within one pixel column on this row
+            let mid = 0.5 * (x + x_next) - x0_floor;
+            areas[row_start + x0_index] += area - area * mid;
+            areas[row_start + x0_index + 1] += area * mid;
+        } else {
+            let slope = (x1 - x0).recip();
+            let x0_fraction = x0 - x0_floor;
+            let first = 0.5 * slope * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+            let x1_fraction = x1 - x1_ceil + 1.0;
+            let last = 0.5 * slope * x1_fraction * x1_fraction;
+            areas[row_start + x0_index] += area * first;
+            if x1_index == x0_index + 2 {
+                areas[row_start + x0_index + 1] += area * (1.0 - first - last);
+            } else {
+                let second = slope * (1.5 - x0_fraction);
+                areas[row_start + x0_index + 1] += area * (second - first);
+                for col in x0_index + 2..x1_index - 1 {
+                    areas[row_start + col] += area * slope;
+                }
+                let before_last = second + (x1_index - x0_index - 3) as f32 * slope;
+                areas[row_start + x1_index - 1] += area * (1.0 - before_last - last);
+            }
+            areas[row_start + x1_index] += area * last;
+        }
+        x = x_next;
+    }
+}
+
+/// The smallest whole-pixel box containing every segment.
+pub fn bounding_box(outline: &[Segment]) -> BoundingBox {
+    if outline.is_empty() {
+        return BoundingBox {
+            left: 0,
+            top: 0,
+            width: 0,
+            height: 0,
+        };
+    }
+
+    let mut min_x = f32::MAX;
+    let mut min_y = f32::MAX;
+    let mut max_x = f32::MIN;
+    let mut max_y = f32::MIN;
+    for segment in outline {
+        min_x = min_x.min(segment.x0).min(segment.x1);
+        min_y = min_y.min(segment.y0).min(segment.y1);
+        max_x = max_x.max(segment.x0).max(segment.x1);
+        max_y = max_y.max(segment.y0).max(segment.y1);
+    }
+
+    let left = min_x.floor() as i32;
+    let top = min_y.floor() as i32;
+    BoundingBox {
+        left,
+        top,
+        width: (max_x.ceil() as i32 - left).max(1) as usize,
+        height: (max_y.ceil() as i32 - top).max(1) as usize,
+    }
+}
+
+/// Converts TrueType contours (on-curve points joined by lines or quadratic curves, with
+/// implied on-curve points between consecutive off-curve ones) into line segments.
+pub fn flatten(contours: &[ContourF32]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    for contour in contours {
+        flatten_contour(&contour.points, &mut segments);
+    }
+    segments
+}
+
+/// Merges runs of connected, nearly collinear segments, dropping every point that lies within
+/// `tolerance` of the line replacing it. Curves flattened into many short steps shrink the
+/// most, and the smaller the outline the more of them can go.
+pub fn simplify(segments: &[Segment], tolerance: f32) -> Vec<Segment> {
+    let mut simplified = Vec::with_capacity(segments.len());
+    // The points of the run of connected segments being merged
+    let mut run: Vec<(f32, f32)> = Vec::new();
+    for segment in segments {
+        if run.last() != Some(&(segment.x0, segment.y0)) {
+            simplify_run(&run, tolerance, &mut simplified);
+            run.clear();
+            run.push((segment.x0, segment.y0));
+        }
+        run.push((segment.x1, segment.y1));
+    }
+    simplify_run(&run, tolerance, &mut simplified);
+    simplified
+}
+
+fn simplify_run(points: &[(f32, f32)], tolerance: f32, segments: &mut Vec<Segment>) {
+    // Stretch each segment from `anchor` as far along the run as the skipped points allow
+    let mut anchor = 0;
+    while anchor + 1 < points.len() {
+        let mut end = anchor + 1;
+        while end + 1 < points.len()
+            && points[anchor + 1..=end]
+                .iter()
+                .all(|&point| distance_to_line(point, points[anchor], points[end + 1]) <= tolerance)
+        {
+            end += 1;
+        }
+        push_segment(points[anchor], points[end], segments);
+        anchor = end;
+    }
+}
+
+// Distance from `point` to the segment from `start` to `end`
+fn distance_to_line(point: (f32, f32), start: (f32, f32), end: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = match length_squared > 0.0 {
+        true => {
+            (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+        }
+        false => 0.0,
+    };
+    let (nearest_x, nearest_y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - nearest_x).powi(2) + (point.1 - nearest_y).powi(2)).sqrt()
+}
+
+fn flatten_contour(points: &[PointF32], segments: &mut Vec<Segment>) {
+    if points.len() < 2 {
+        return;
+    }
+    let midpoint = |a: PointF32, b: PointF32| ((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+
+    // Start on an on-curve point, or on the implied one between the first two points if the
+    // contour has none.
+    let (start, first, count) = match points.iter().position(|point| point.on_curve) {
+        Some(index) => (
+            (points[index].x, points[index].y),
+            index + 1,
+            points.len() - 1,
+        ),
+        None => (midpoint(points[0], points[1]), 1, points.len()),
+    };
+
+    let mut current = start;
+    let mut control: Option<(f32, f32)> = None;
+    for i in 0..count {
+        let point = points[(first + i) % points.len()];
+        if point.on_curve {
+            let end = (point.x, point.y);
+            match control.take() {
+                Some(control) => flatten_quadratic(current, control, end, segments),
+                None => push_segment(current, end, segments),
+            }
+            current = end;
+        } else {
+            if let Some(previous) = control {
+                let end = ((previous.0 + point.x) / 2.0, (previous.1 + point.y) / 2.0);
+                flatten_quadratic(current, previous, end, segments);
+                current = end;
+            }
+            control = Some((point.x, point.y));
+        }
+    }
+    match control {
+        Some(control) => flatten_quadratic(current, control, start, segments),
+        None => push_segment(current, start, segments),
+    }
+}
+
+fn flatten_quadratic(
+    start: (f32, f32),
+    control: (f32, f32),
+    end: (f32, f32),
+    segments: &mut Vec<Segment>,
+) {
+    // The deviation from a straight line shrinks with the square of the number of steps
+    let dx = start.0 - 2.0 * control.0 + end.0;
+    let dy = start.1 - 2.0 * control.1 + end.1;
+    let deviation = (dx * dx + dy * dy).sqrt();
+    let steps = ((deviation / (8.0 * FLATTEN_TOLERANCE)).sqrt().ceil() as usize).clamp(1, 64);
+
+    let mut previous = start;
+    for step in 1..=steps {
+        let t = step as f32 / steps as f32;
+        let mt = 1.0 - t;
+        let point = (
+            mt * mt * start.0 + 2.0 * mt * t * control.0 + t * t * end.0,
+            mt * mt * start.1 + 2.0 * mt * t * control.1 + t * t * end.1,
+        );
+        push_segment(previous, point, segments);
+        previous = point;
+    }
+}
+
+fn push_segment(start: (f32, f32), end: (f32, f32), segments: &mut Vec<Segment>) {
+    segments.push(Segment {
+        x0: start.0,
+        y0: start.1,
+        x1: end.0,
+        y1: end.1,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A closed polygon through `points`, in pixels.
+    fn polygon(points: &[(f32, f32)]) -> Vec<Segment> {
+        let next = points.iter().cycle().skip(1);
+        points
+            .iter()
+            .zip(next)
+            .map(|(&(x0, y0), &(x1, y1))| Segment { x0, y0, x1, y1 })
+            .collect()
+    }
+
+    fn total(coverage: &CoverageBuffer) -> f32 {
+        coverage.values.iter().sum()
+    }
+
+    #[test]
+    fn rasterizers_agree_on_a_triangle() {
+        let triangle = polygon(&[(0.5, 0.5), (8.5, 0.5), (0.5, 8.5)]);
+        let bbox = bounding_box(&triangle);
+        let supersampled = SupersampleRasterizer.fill(&triangle, &bbox);
+        let scanline = ScanlineRasterizer.fill(&triangle, &bbox);
+        assert_eq!(supersampled.bbox, scanline.bbox);
+
+        // The triangle covers 32 square pixels
+        assert!((total(&scanline) - 32.0).abs() < 1e-3);
+        assert!((total(&supersampled) - 32.0).abs() <= 1.0);
+        for row in 0..bbox.height {
+            for col in 0..bbox.width {
+                let difference = supersampled.get(col, row) - scanline.get(col, row);
+                assert!(difference.abs() <= 0.2, "pixel {col}, {row}");
+            }
+        }
+        // Well inside is fully covered, well outside is empty
+        assert_eq!(scanline.get(1, 1), 1.0);
+        assert_eq!(supersampled.get(1, 1), 1.0);
+        assert_eq!(scanline.get(7, 7), 0.0);
+        assert_eq!(supersampled.get(7, 7), 0.0);
+    }
+
+    #[test]
+    fn monochrome_coverage_is_all_or_nothing() {
+        let triangle = polygon(&[(0.5, 0.5), (8.5, 0.5), (0.5, 8.5)]);
+        let coverage = MonochromeRasterizer.fill(&triangle, &bounding_box(&triangle));
+        assert!(coverage
+            .values
+            .iter()
+            .all(|&value| value == 0.0 || value == 1.0));
+    }
+
+    #[test]
+    fn grid_fitting_snaps_horizontal_edges_to_whole_rows() {
+        // A stem whose top and bottom fall between pixel rows
+        let stem = polygon(&[(1.0, -6.4), (5.0, -6.4), (5.0, 0.3), (1.0, 0.3)]);
+        for segment in snap_horizontal_edges(&stem) {
+            assert_eq!(segment.y0, segment.y0.round());
+            assert_eq!(segment.y1, segment.y1.round());
+        }
+
+        let unsnapped = ScanlineRasterizer.fill(&stem, &bounding_box(&stem));
+        assert!(unsnapped
+            .values
+            .iter()
+            .any(|&value| value > 0.0 && value < 1.0));
+        let snapped = GridFitRasterizer {
+            inner: ScanlineRasterizer,
+        }
+        .fill(&stem, &bounding_box(&stem));
+        // The baseline edge rounds down to row 0 and the top to row -6
+        assert_eq!((snapped.bbox.top, snapped.bbox.height), (-6, 6));
+        assert!(snapped.values.iter().all(|&value| value == 1.0));
+    }
+
+    #[test]
+    fn grid_fitting_keeps_close_features_on_separate_rows() {
+        // Edges 0.6px apart would both round to row -2
+        let bars = polygon(&[(0.0, -2.2), (4.0, -2.2), (4.0, -1.6), (0.0, -1.6)]);
+        let snapped = snap_horizontal_edges(&bars);
+        let mut rows: Vec<f32> = snapped.iter().map(|segment| segment.y0).collect();
+        rows.sort_by(f32::total_cmp);
+        rows.dedup();
+        assert_eq!(rows, [-2.0, -1.0]);
+    }
+
+    /// A circle of `radius` pixels flattened into `steps` segments, as a fixed-step flattener
+    /// would produce at any size.
+    fn circle(radius: f32, steps: usize) -> Vec<Segment> {
+        let points: Vec<(f32, f32)> = (0..steps)
+            .map(|step| {
+                let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+                (20.0 + radius * angle.cos(), 20.0 + radius * angle.sin())
+            })
+            .collect();
+        polygon(&points)
+    }
+
+    /// Distance from `(x, y)` to the nearest point of `segment`.
+    fn distance_to_segment((x, y): (f32, f32), segment: &Segment) -> f32 {
+        let (dx, dy) = (segment.x1 - segment.x0, segment.y1 - segment.y0);
+        let length_squared = (dx * dx + dy * dy).max(f32::EPSILON);
+        let t = (((x - segment.x0) * dx + (y - segment.y0) * dy) / length_squared).clamp(0.0, 1.0);
+        let (nearest_x, nearest_y) = (segment.x0 + t * dx, segment.y0 + t * dy);
+        ((x - nearest_x).powi(2) + (y - nearest_y).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn simplified_outline_stays_within_tolerance() {
+        let tolerance = 0.1;
+        for radius in [4.0, 40.0] {
+            let original = circle(radius, 128);
+            let simplified = simplify(&original, tolerance);
+            assert!(simplified.len() < original.len());
+            for segment in &original {
+                let distance = simplified
+                    .iter()
+                    .map(|simplified| distance_to_segment((segment.x0, segment.y0), simplified))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(distance <= tolerance + 1e-4, "radius {radius}: {distance}");
+            }
+            // Still a closed outline
+            assert_eq!(
+                (simplified[0].x0, simplified[0].y0),
+                (simplified.last().unwrap().x1, simplified.last().unwrap().y1)
+            );
+        }
+    }
+
+    #[test]
+    fn small_outlines_simplify_to_fewer_segments() {
+        let small = simplify(&circle(4.0, 128), 0.1).len();
+        let large = simplify(&circle(40.0, 128), 0.1).len();
+        assert!(small * 2 < large, "{small} vs {large}");
+    }
+}
@@ -1,4 +1,215 @@
 use crate::error::CapyError;
+use crate::font::{BitmapGlyph, BitmapPixels, DecorationMetrics, Font};
+use crate::rasterizer::{self, Rasterizer, Segment, SupersampleRasterizer};
+use std::sync::Arc;
+use std::time::Duration;
+
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+const ELLIPSIS: char = '\u{2026}';
+// Invisible unless a line breaks at it, when it shows as a hyphen
+const SOFT_HYPHEN: char = '\u{AD}';
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+// Translucent colors for the debug_layout overlay
+const DEBUG_LINE_BOX_COLOR: [u8; 4] = [0, 120, 255, 160];
+const DEBUG_BASELINE_COLOR: [u8; 4] = [255, 0, 0, 160];
+const DEBUG_ADVANCE_COLOR: [u8; 4] = [0, 180, 0, 96];
+const DEBUG_GLYPH_BOX_COLOR: [u8; 4] = [255, 140, 0, 160];
+// How long the caret stays on, then off, while blinking
+const CARET_BLINK_INTERVAL: Duration = Duration::from_millis(500);
+// Faux bold thickens strokes by this fraction of an em, like FreeType's emboldening
+const FAUX_BOLD_STRENGTH: f32 = 1.0 / 24.0;
+// Faux italic shifts each pixel right by this much per pixel above the baseline, about 12°
+const FAUX_ITALIC_SKEW: f32 = 0.2;
+// How far in pixels `simplify_outlines` may move a flattened outline
+const OUTLINE_SIMPLIFY_TOLERANCE: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextDirection {
+    #[default]
+    Horizontal,
+    // Top to bottom in columns running right to left, as in vertical CJK text
+    Vertical,
+}
+
+/// What to do with control characters other than tabs and line breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ControlCharacters {
+    #[default]
+    Skip,
+    // Draw U+FFFD, or .notdef if the font lacks it
+    Replace,
+}
+
+/// What happens to lines wider than `max_width`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextOverflow {
+    #[default]
+    Wrap,
+    // Keep each paragraph on one line, cutting it short with an ellipsis, as for labels
+    Ellipsis,
+}
+
+/// What the `y` passed to `render_text` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextOrigin {
+    // The top of the first line, as for text placed in a box
+    #[default]
+    TopLeft,
+    // The baseline of the first line, as typographic code usually thinks of it
+    Baseline,
+}
+
+/// How glyph coverage is blended into the background.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Blending {
+    // Blend the sRGB values directly. Cheap, but edges come out heavier than they should.
+    #[default]
+    Srgb,
+    // Blend in linear light and convert back to sRGB, which keeps stroke weight consistent
+    // across foreground/background contrasts
+    Linear,
+}
+
+/// Layout knobs for `render_text`.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    // Font size in pixels per em
+    pub size: f32,
+    // Lines wrap once they would grow wider than this
+    pub max_width: usize,
+    pub align: TextAlign,
+    // Alignment only applies to horizontal text
+    pub direction: TextDirection,
+    // Distance between tab stops in ems
+    pub tab_width: f32,
+    pub control_characters: ControlCharacters,
+    pub rasterizer: Arc<dyn Rasterizer>,
+    // Decorations drawn across each horizontal line
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub blending: Blending,
+    // Keep spaces at the start of a paragraph instead of dropping them, as plain text needs
+    // for indentation. Runs of spaces inside a line are always kept.
+    pub preserve_spaces: bool,
+    // Debugging aid: draw characters the font lacks as a box labelled with their hex codepoint
+    // instead of .notdef. Only applies to horizontal text.
+    pub missing_glyph_labels: bool,
+    pub overflow: TextOverflow,
+    // Vertical text is always placed by its top left corner
+    pub origin: TextOrigin,
+    // Synthesized styles for fonts without a bold or italic face. Faux bold thickens outlines
+    // and widens advances to match; faux italic slants outlines to the right.
+    pub faux_bold: bool,
+    pub faux_italic: bool,
+    // Debugging aid: draw each line's box and baseline, where each glyph's advance starts, and
+    // glyph bounding boxes, as thin rules under the text. Only applies to horizontal text.
+    pub debug_layout: bool,
+    // Merge nearly collinear segments of flattened outlines before filling them. Small text
+    // then rasterizes faster, at the cost of moving edges by up to a fifth of a pixel.
+    pub simplify_outlines: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            max_width: usize::MAX,
+            align: TextAlign::Left,
+            direction: TextDirection::Horizontal,
+            tab_width: 4.0,
+            control_characters: ControlCharacters::Skip,
+            rasterizer: Arc::new(SupersampleRasterizer),
+            underline: false,
+            strikethrough: false,
+            blending: Blending::Srgb,
+            preserve_spaces: false,
+            missing_glyph_labels: false,
+            origin: TextOrigin::TopLeft,
+            overflow: TextOverflow::Wrap,
+            faux_bold: false,
+            faux_italic: false,
+            debug_layout: false,
+            simplify_outlines: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedGlyph {
+    pub char: char,
+    pub glyph_id: u16,
+    // Offset from the left edge of the line. Positions keep their sub-pixel fraction so
+    // rounding doesn't accumulate along the line.
+    pub x: f32,
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Line {
+    pub glyphs: Vec<PlacedGlyph>,
+    pub width: f32,
+}
+
+impl Line {
+    fn push(&mut self, glyph: PlacedGlyph) {
+        self.width = glyph.x + glyph.advance;
+        self.glyphs.push(glyph);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.glyphs.truncate(len);
+        self.width = self
+            .glyphs
+            .last()
+            .map_or(0.0, |glyph| glyph.x + glyph.advance);
+    }
+}
+
+/// Text laid out in window pixels, ready to be painted with `paint`. Keeping it around lets the
+/// same layout be painted again, for instance into a new bitmap.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DisplayList {
+    pub items: Vec<DisplayItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayItem {
+    Text(TextRun),
+    // Filled with an RGBA color, such as an underline or the edge of a missing glyph box
+    Rect(Rect, [u8; 4]),
+}
+
+/// Glyphs drawn at one size. Horizontal text gets one run per line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    // Font size in pixels per em
+    pub size: f32,
+    pub glyphs: Vec<RunGlyph>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunGlyph {
+    pub glyph_id: u16,
+    // Pen position on the baseline
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: usize,
+    pub height: usize,
+}
 
 pub fn render_text(
     bitmap: &mut [u8],
@@ -6,368 +217,2000 @@ pub fn render_text(
     x: usize,
     y: usize,
     window_width: usize,
-    scale: usize, // Add scale parameter
+    font: &Font,
+    options: &TextOptions,
 ) -> Result<(), CapyError> {
-    let mut x_pos = x;
-    for (_, char) in text.chars().enumerate() {
-        let char_width = render_char(bitmap, char, x_pos, y, window_width, scale)?;
-        x_pos += char_width * scale + scale; // Add scale for spacing between characters
-    }
+    let window_height = bitmap.len() / 4 / window_width.max(1);
+    let display_list =
+        layout_display_list(font, text, (x, y), (window_width, window_height), options);
+    paint(bitmap, window_width, font, &display_list, options);
     Ok(())
 }
 
-fn render_char(
+/// Like `render_text`, but also returns the characters the font has no glyph for, with their
+/// byte index in `text`. These are drawn as .notdef, or as hex boxes with `missing_glyph_labels`.
+pub fn render_text_reporting_missing(
     bitmap: &mut [u8],
-    char: char,
+    text: &str,
     x: usize,
     y: usize,
     window_width: usize,
-    scale: usize,
-) -> Result<usize, CapyError> {
-    let glyph = map_char_to_glyph(char)?;
-    let mut char_width = 0;
-    let supersample_scale = 4; // Supersample scale factor
-    let supersample_size = 8 * supersample_scale;
-    let mut supersample_bitmap = vec![0u8; supersample_size * supersample_size];
-
-    // Render the character at a higher resolution
-    for (row_index, row) in glyph.iter().enumerate() {
-        for col_index in 0..8 {
-            let bit = (row >> (7 - col_index)) & 1;
-            if bit == 1 {
-                char_width = char_width.max(col_index + 1); // Update char_width to the rightmost bit set
+    font: &Font,
+    options: &TextOptions,
+) -> Result<Vec<(char, usize)>, CapyError> {
+    render_text(bitmap, text, x, y, window_width, font, options)?;
+    Ok(missing_glyphs(font, text, options))
+}
+
+/// Lays out `text` with its top left corner at `(x, y)`, or with the baseline of its first line
+/// at `y` for `TextOrigin::Baseline`. The window size only matters for
+/// vertical text, whose columns break at the bottom of the window.
+pub fn layout_display_list(
+    font: &Font,
+    text: &str,
+    (x, y): (usize, usize),
+    (window_width, window_height): (usize, usize),
+    options: &TextOptions,
+) -> DisplayList {
+    let mut display_list = DisplayList::default();
+    match options.direction {
+        TextDirection::Horizontal => {
+            layout_horizontal(&mut display_list, font, text, x, y, options)
+        }
+        TextDirection::Vertical => layout_vertical(
+            &mut display_list,
+            font,
+            text,
+            (x, y),
+            (window_width, window_height),
+            options,
+        ),
+    }
+    display_list
+}
+
+fn layout_horizontal(
+    display_list: &mut DisplayList,
+    font: &Font,
+    text: &str,
+    x: usize,
+    y: usize,
+    options: &TextOptions,
+) {
+    let scale = font.scale_factor(options.size);
+    let lines = layout_text(font, text, options);
+    for (line_index, line) in lines.iter().enumerate() {
+        let (line_x, baseline) = line_position(font, line, line_index, (x, y), options);
+        let mut decorations = Vec::new();
+        if options.underline {
+            decorations.push(font.underline_metrics());
+        }
+        if options.strikethrough {
+            decorations.push(font.strikethrough_metrics());
+        }
+        for metrics in decorations {
+            let rect = decoration_rect(
+                line_x.round() as i32,
+                baseline,
+                line.width.round() as usize,
+                metrics,
+                scale,
+            );
+            display_list.items.push(DisplayItem::Rect(rect, BLACK));
+        }
+
+        if options.debug_layout {
+            layout_debug_overlay(display_list, font, line, (line_x, baseline), options);
+        }
+
+        let mut glyphs = Vec::new();
+        for glyph in &line.glyphs {
+            let pen = (line_x + glyph.x, baseline as f32);
+            if glyph.glyph_id == 0 && options.missing_glyph_labels {
+                layout_missing_glyph_box(display_list, font, glyph.char, pen, options.size);
+                continue;
             }
-            for i in 0..supersample_scale {
-                for j in 0..supersample_scale {
-                    let pixel_x = col_index * supersample_scale + i;
-                    let pixel_y = row_index * supersample_scale + j;
-                    supersample_bitmap[pixel_y * supersample_size + pixel_x] = bit;
+            glyphs.push(RunGlyph {
+                glyph_id: glyph.glyph_id,
+                x: pen.0,
+                y: pen.1,
+            });
+        }
+        display_list.items.push(DisplayItem::Text(TextRun {
+            size: options.size,
+            glyphs,
+        }));
+    }
+}
+
+/// Adds the `debug_layout` rules for one line, placed like its text.
+fn layout_debug_overlay(
+    display_list: &mut DisplayList,
+    font: &Font,
+    line: &Line,
+    (line_x, baseline): (f32, usize),
+    options: &TextOptions,
+) {
+    let scale = font.scale_factor(options.size);
+    let to_pixels = |units: i16| (units as f32 * scale).round() as i32;
+    let line_box = Rect {
+        x: line_x.round() as i32,
+        y: baseline as i32 - to_pixels(font.ascent()),
+        width: line.width.round() as usize,
+        height: line_height(font, options.size),
+    };
+    push_debug_box(display_list, line_box, DEBUG_LINE_BOX_COLOR);
+    let baseline_rule = Rect {
+        y: baseline as i32,
+        height: 1,
+        ..line_box
+    };
+    display_list
+        .items
+        .push(DisplayItem::Rect(baseline_rule, DEBUG_BASELINE_COLOR));
+
+    for glyph in &line.glyphs {
+        let pen_x = (line_x + glyph.x).round() as i32;
+        let advance = Rect {
+            x: pen_x,
+            width: 1,
+            ..line_box
+        };
+        display_list
+            .items
+            .push(DisplayItem::Rect(advance, DEBUG_ADVANCE_COLOR));
+        if let Some((x_min, y_min, x_max, y_max)) = font.glyph_bounds(glyph.glyph_id) {
+            let glyph_box = Rect {
+                x: pen_x + to_pixels(x_min),
+                y: baseline as i32 - to_pixels(y_max),
+                width: (to_pixels(x_max) - to_pixels(x_min)).max(1) as usize,
+                height: (to_pixels(y_max) - to_pixels(y_min)).max(1) as usize,
+            };
+            push_debug_box(display_list, glyph_box, DEBUG_GLYPH_BOX_COLOR);
+        }
+    }
+}
+
+/// Outlines `rect` with one pixel wide rules.
+fn push_debug_box(display_list: &mut DisplayList, rect: Rect, color: [u8; 4]) {
+    let edges = [
+        Rect { height: 1, ..rect },
+        Rect {
+            y: rect.y + rect.height as i32 - 1,
+            height: 1,
+            ..rect
+        },
+        Rect { width: 1, ..rect },
+        Rect {
+            x: rect.x + rect.width as i32 - 1,
+            width: 1,
+            ..rect
+        },
+    ];
+    for edge in edges {
+        display_list.items.push(DisplayItem::Rect(edge, color));
+    }
+}
+
+/// Advances the pen downward by each glyph's vertical advance, starting a new column to the
+/// left when the window's bottom is reached. Fonts without vertical metrics advance by the
+/// line height instead. All of the text goes into a single run.
+fn layout_vertical(
+    display_list: &mut DisplayList,
+    font: &Font,
+    text: &str,
+    (x, y): (usize, usize),
+    (window_width, window_height): (usize, usize),
+    options: &TextOptions,
+) {
+    let scale = font.scale_factor(options.size);
+    let to_pixels = |units: i32| (units as f32 * scale).round() as i32;
+    let column_width = line_height(font, options.size);
+
+    // The first column sits against the right edge of the text area
+    let mut column_right = x + options.max_width.min(window_width.saturating_sub(x));
+    let mut pen_y = y;
+    let mut glyphs = Vec::new();
+    for char in normalize_line_breaks(text).chars() {
+        if char == '\n' {
+            column_right = column_right.saturating_sub(column_width);
+            pen_y = y;
+            continue;
+        }
+        let Some(char) = visible_char(char, options.control_characters) else {
+            continue;
+        };
+        let glyph_id = font.glyph_index(char).unwrap_or(0);
+        let advance = font
+            .vertical_advance(glyph_id)
+            .map_or(column_width, |advance| to_pixels(advance as i32) as usize);
+        if pen_y > y && pen_y + advance > window_height {
+            column_right = column_right.saturating_sub(column_width);
+            pen_y = y;
+        }
+        if column_right < x + column_width {
+            break;
+        }
+
+        // Center the glyph in its column, hanging it from the vertical origin
+        let origin = font
+            .vertical_origin_y(glyph_id)
+            .unwrap_or(font.ascent() as i32);
+        let baseline = (pen_y as i32 + to_pixels(origin)).max(0) as usize;
+        let glyph_width = to_pixels(font.advance_width(glyph_id) as i32).max(0) as usize;
+        let pen_x = column_right - column_width + column_width.saturating_sub(glyph_width) / 2;
+        glyphs.push(RunGlyph {
+            glyph_id,
+            x: pen_x as f32,
+            y: baseline as f32,
+        });
+        pen_y += advance;
+    }
+    display_list.items.push(DisplayItem::Text(TextRun {
+        size: options.size,
+        glyphs,
+    }));
+}
+
+/// Paints `display_list` over `bitmap`, using the rasterizer and blending from `options`.
+pub fn paint(
+    bitmap: &mut [u8],
+    window_width: usize,
+    font: &Font,
+    display_list: &DisplayList,
+    options: &TextOptions,
+) {
+    let window_height = bitmap.len() / 4 / window_width.max(1);
+    for item in &display_list.items {
+        match item {
+            DisplayItem::Text(run) => {
+                // No glyph reaches further than two ems from its baseline, so anything past
+                // that is off screen and isn't worth rasterizing
+                let margin = 2.0 * run.size;
+                for glyph in &run.glyphs {
+                    if glyph.y < -margin || glyph.y > window_height as f32 + margin {
+                        continue;
+                    }
+                    render_char(
+                        bitmap,
+                        window_width,
+                        font,
+                        glyph.glyph_id,
+                        (glyph.x, glyph.y),
+                        run.size,
+                        options,
+                    );
                 }
             }
+            DisplayItem::Rect(rect, color) => {
+                fill_rect(bitmap, window_width, rect, *color, options.blending)
+            }
+        }
+    }
+}
+
+/// Renders `text` as a coverage mask, one byte per pixel from 0 (untouched) to 255 (fully
+/// covered), so callers can composite it in any color over any background. Returns the mask
+/// with its width and height, which are the extent `measure_text` reports. The text is laid out
+/// horizontally from the mask's top left corner, whatever `options` says about direction and
+/// origin. Color is dropped: rectangles such as underlines count with their alpha, and color
+/// bitmap glyphs with theirs. `options.blending` is left to the caller too.
+pub fn render_text_mask(font: &Font, text: &str, options: &TextOptions) -> (Vec<u8>, usize, usize) {
+    let options = TextOptions {
+        direction: TextDirection::Horizontal,
+        origin: TextOrigin::TopLeft,
+        ..options.clone()
+    };
+    let extent = measure_text(font, text, &options);
+    let (width, height) = (extent.width, extent.height);
+    let display_list = layout_display_list(font, text, (0, 0), (width, height), &options);
+
+    let mut mask = vec![0; width * height];
+    // Coverage combines like black over white does, so the mask matches what `paint` draws there
+    let mut plot = |pixel_x: i32, pixel_y: i32, _color: [u8; 3], coverage: f32| {
+        if pixel_x < 0 || pixel_y < 0 || pixel_x as usize >= width || pixel_y as usize >= height {
+            return;
+        }
+        let pixel = &mut mask[pixel_y as usize * width + pixel_x as usize];
+        *pixel = 255 - blend(255 - *pixel, 0, coverage, Blending::Srgb);
+    };
+    for item in &display_list.items {
+        match item {
+            DisplayItem::Text(run) => {
+                for glyph in &run.glyphs {
+                    rasterize_char(
+                        font,
+                        glyph.glyph_id,
+                        (glyph.x, glyph.y),
+                        run.size,
+                        &options,
+                        &mut plot,
+                    );
+                }
+            }
+            DisplayItem::Rect(rect, color) => {
+                let coverage = color[3] as f32 / 255.0;
+                // Clip before walking the rectangle, which may be far larger than the mask
+                let right = (rect.x as i64 + rect.width as i64).min(width as i64) as i32;
+                let bottom = (rect.y as i64 + rect.height as i64).min(height as i64) as i32;
+                for pixel_y in rect.y.max(0)..bottom {
+                    for pixel_x in rect.x.max(0)..right {
+                        plot(pixel_x, pixel_y, [0, 0, 0], coverage);
+                    }
+                }
+            }
+        }
+    }
+    (mask, width, height)
+}
+
+/// Size of a block of text as `render_text` would lay it out, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextExtent {
+    // Width of the widest line
+    pub width: usize,
+    pub height: usize,
+    pub line_count: usize,
+}
+
+/// Measures horizontal text without drawing it, using the same layout as `render_text`.
+pub fn measure_text(font: &Font, text: &str, options: &TextOptions) -> TextExtent {
+    let lines = layout_text(font, text, options);
+    TextExtent {
+        width: lines
+            .iter()
+            .map(|line| line.width.ceil() as usize)
+            .max()
+            .unwrap_or(0),
+        height: lines.len() * line_height(font, options.size),
+        line_count: lines.len(),
+    }
+}
+
+/// Distance between the baselines of consecutive lines, in pixels.
+pub fn line_height(font: &Font, size: f32) -> usize {
+    let scale = font.scale_factor(size);
+    let height = font.ascent() as i32 - font.descent() as i32 + font.line_gap() as i32;
+    (height as f32 * scale).round() as usize
+}
+
+/// Breaks `text` into lines no wider than `options.max_width`. Lines end at line breaks
+/// (`\n`, `\r\n` or `\r`) and wrap at spaces and tabs, then at soft hyphens (U+00AD), which
+/// show up as a hyphen only where a line breaks at one. Words that still don't fit on a line of
+/// their own are split. With `TextOverflow::Ellipsis`, lines are truncated instead of wrapped.
+pub fn layout_text(font: &Font, text: &str, options: &TextOptions) -> Vec<Line> {
+    if options.overflow == TextOverflow::Ellipsis {
+        let unwrapped = TextOptions {
+            max_width: usize::MAX,
+            overflow: TextOverflow::Wrap,
+            ..options.clone()
+        };
+        return layout_text(font, text, &unwrapped)
+            .into_iter()
+            .map(|line| truncate_with_ellipsis(font, line, options))
+            .collect();
+    }
+
+    let max_width = options.max_width as f32;
+    let scale = font.scale_factor(options.size);
+    let tab_width = (options.tab_width * options.size).max(1.0);
+
+    // Characters the font doesn't cover, including the U+FFFD left by invalid UTF-8, are drawn
+    // with the .notdef glyph (id 0)
+    let place = |char: char| {
+        let glyph_id = font.glyph_index(char).unwrap_or(0);
+        let advance = match glyph_id == 0 && options.missing_glyph_labels {
+            true => {
+                // Leave a gap so neighbouring boxes don't share an edge
+                let missing = MissingGlyphBox::new(font, char, options.size);
+                missing.width + missing.padding
+            }
+            false => {
+                font.advance_width(glyph_id) as f32 * scale + faux_bold_width(options.size, options)
+            }
+        };
+        PlacedGlyph {
+            char,
+            glyph_id,
+            x: 0.0,
+            advance,
+        }
+    };
+
+    // Drawn at the end of a line broken at a soft hyphen
+    let hyphen = PlacedGlyph {
+        char: SOFT_HYPHEN,
+        ..place('-')
+    };
+
+    let mut lines = Vec::new();
+    for paragraph in normalize_line_breaks(text).split('\n') {
+        let paragraph_start = lines.len();
+        let mut line = Line::default();
+        // The space or tab that came before the current word
+        let mut separator = None;
+        for piece in paragraph.split_inclusive([' ', '\t']) {
+            let (word, next_separator) = match piece.chars().last() {
+                Some(char @ (' ' | '\t')) => (&piece[..piece.len() - 1], Some(char)),
+                _ => (piece, None),
+            };
+
+            let mut glyphs: Vec<PlacedGlyph> = Vec::new();
+            // Indices into `glyphs` the word may break before, where it had a soft hyphen
+            let mut soft_breaks = Vec::new();
+            for char in word.chars() {
+                if char == SOFT_HYPHEN {
+                    soft_breaks.push(glyphs.len());
+                    continue;
+                }
+                let Some(char) = visible_char(char, options.control_characters) else {
+                    continue;
+                };
+                let mut glyph = place(char);
+                if let Some(previous) = glyphs.last() {
+                    let kerning = font.kerning(previous.glyph_id, glyph.glyph_id) as f32 * scale;
+                    glyph.x = (previous.x + previous.advance + kerning).max(0.0);
+                }
+                glyphs.push(glyph);
+            }
+            let word_width = glyphs.last().map_or(0.0, |glyph| glyph.x + glyph.advance);
+            // How much of the line the word needs at least: up to its first soft hyphen, if any
+            let first_piece_width = soft_breaks
+                .iter()
+                .find(|&&index| index > 0 && index < glyphs.len())
+                .map_or(word_width, |&index| {
+                    let last = &glyphs[index - 1];
+                    last.x + last.advance + hyphen.advance
+                });
+
+            match separator {
+                Some('\t') => {
+                    // Tabs advance to the next stop, even at the start of a line
+                    let mut tab = place(' ');
+                    tab.char = '\t';
+                    tab.x = line.width;
+                    tab.advance = ((line.width / tab_width).floor() + 1.0) * tab_width - line.width;
+                    if !line.glyphs.is_empty()
+                        && tab.x + tab.advance + first_piece_width > max_width
+                    {
+                        lines.push(std::mem::take(&mut line));
+                    } else {
+                        line.push(tab);
+                    }
+                }
+                Some(_)
+                    if !line.glyphs.is_empty()
+                        || (options.preserve_spaces && lines.len() == paragraph_start) =>
+                {
+                    let mut space = place(' ');
+                    space.x = line.width;
+                    if !line.glyphs.is_empty()
+                        && space.x + space.advance + first_piece_width > max_width
+                    {
+                        lines.push(std::mem::take(&mut line));
+                    } else {
+                        line.push(space);
+                    }
+                }
+                _ => {}
+            }
+            // Where the start of the word sits on the current line
+            let mut word_origin = line.width;
+            // First glyph of the word on the current line
+            let mut line_start = 0;
+            let mut index = 0;
+            while index < glyphs.len() {
+                let mut glyph = glyphs[index].clone();
+                let offset_in_word = glyph.x;
+                glyph.x = (word_origin + offset_in_word).max(0.0);
+                if !line.glyphs.is_empty() && glyph.x + glyph.advance > max_width {
+                    // Break at the last soft hyphen on this line that leaves room for the hyphen
+                    let soft_break = soft_breaks.iter().rev().copied().find(|&soft_break| {
+                        let last = &glyphs[soft_break.saturating_sub(1)];
+                        soft_break > line_start
+                            && soft_break <= index
+                            && word_origin + last.x + last.advance + hyphen.advance <= max_width
+                    });
+                    if let Some(soft_break) = soft_break {
+                        line.truncate(line.glyphs.len() - (index - soft_break));
+                        line.push(PlacedGlyph {
+                            x: line.width,
+                            ..hyphen.clone()
+                        });
+                        lines.push(std::mem::take(&mut line));
+                        word_origin = -glyphs[soft_break].x;
+                        line_start = soft_break;
+                        index = soft_break;
+                        continue;
+                    }
+                    // No soft hyphen helps, so break it mid-word
+                    lines.push(std::mem::take(&mut line));
+                    word_origin = -offset_in_word;
+                    line_start = index;
+                    glyph.x = 0.0;
+                }
+                line.push(glyph);
+                index += 1;
+            }
+            separator = next_separator;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Drops glyphs from the end of `line` until an ellipsis fits after them within
+/// `options.max_width`, then appends it. Uses "..." if the font has no '…'. Lines that already
+/// fit are returned unchanged, and lines too narrow for even the ellipsis come back empty.
+fn truncate_with_ellipsis(font: &Font, mut line: Line, options: &TextOptions) -> Line {
+    let max_width = options.max_width as f32;
+    if line.width <= max_width {
+        return line;
+    }
+    let scale = font.scale_factor(options.size);
+    let ellipsis: Vec<(char, u16)> = match font.glyph_index(ELLIPSIS) {
+        Some(glyph_id) => vec![(ELLIPSIS, glyph_id)],
+        None => vec![('.', font.glyph_index('.').unwrap_or(0)); 3],
+    };
+    let ellipsis_width: f32 = ellipsis
+        .iter()
+        .map(|&(_, glyph_id)| {
+            font.advance_width(glyph_id) as f32 * scale + faux_bold_width(options.size, options)
+        })
+        .sum();
+
+    // Also drop trailing whitespace so the ellipsis follows the last visible character
+    while line.glyphs.last().is_some_and(|glyph| {
+        glyph.x + glyph.advance + ellipsis_width > max_width || matches!(glyph.char, ' ' | '\t')
+    }) {
+        line.glyphs.pop();
+    }
+    if ellipsis_width > max_width {
+        return Line::default();
+    }
+    line.width = line
+        .glyphs
+        .last()
+        .map_or(0.0, |glyph| glyph.x + glyph.advance);
+    for (char, glyph_id) in ellipsis {
+        line.push(PlacedGlyph {
+            char,
+            glyph_id,
+            x: line.width,
+            advance: font.advance_width(glyph_id) as f32 * scale
+                + faux_bold_width(options.size, options),
+        });
+    }
+    line
+}
+
+/// Turns `\r\n` and bare `\r` line breaks into `\n`.
+pub fn normalize_line_breaks(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Characters of `text` drawn without a glyph of their own. Line breaks, tabs, soft hyphens and
+/// skipped control characters are never drawn, so they're never reported.
+fn missing_glyphs(font: &Font, text: &str, options: &TextOptions) -> Vec<(char, usize)> {
+    text.char_indices()
+        .filter(|&(_, char)| !matches!(char, '\n' | '\r' | '\t' | SOFT_HYPHEN))
+        .filter(|&(_, char)| {
+            visible_char(char, options.control_characters)
+                .is_some_and(|visible| font.glyph_index(visible).is_none())
+        })
+        .map(|(byte_index, char)| (char, byte_index))
+        .collect()
+}
+
+/// The character to draw for `char`, or `None` if it should be skipped.
+fn visible_char(char: char, control_characters: ControlCharacters) -> Option<char> {
+    if !char.is_control() {
+        return Some(char);
+    }
+    match control_characters {
+        ControlCharacters::Skip => None,
+        ControlCharacters::Replace => Some(REPLACEMENT_CHARACTER),
+    }
+}
+
+/// Where horizontal text laid out at `(x, y)` puts the start and baseline of `line`, the
+/// `line_index`th line.
+fn line_position(
+    font: &Font,
+    line: &Line,
+    line_index: usize,
+    (x, y): (usize, usize),
+    options: &TextOptions,
+) -> (f32, usize) {
+    let ascent = (font.ascent() as f32 * font.scale_factor(options.size)).round() as usize;
+    let first_baseline = match options.origin {
+        TextOrigin::TopLeft => y + ascent,
+        TextOrigin::Baseline => y,
+    };
+    // Each wrapped line is aligned independently within max_width
+    let max_width = options.max_width as f32;
+    let offset = match options.align {
+        TextAlign::Left => 0.0,
+        TextAlign::Center => (max_width - line.width).max(0.0) / 2.0,
+        TextAlign::Right => (max_width - line.width).max(0.0),
+    };
+    (
+        x as f32 + offset,
+        first_baseline + line_index * line_height(font, options.size),
+    )
+}
+
+/// A horizontal bar `width` pixels long starting at `x`, placed relative to the baseline by
+/// `metrics`. Bars are at least one pixel thick so they survive small sizes.
+fn decoration_rect(
+    x: i32,
+    baseline: usize,
+    width: usize,
+    metrics: DecorationMetrics,
+    scale: f32,
+) -> Rect {
+    Rect {
+        x,
+        y: baseline as i32 - (metrics.position as f32 * scale).round() as i32,
+        width,
+        height: ((metrics.thickness as f32 * scale).round() as usize).max(1),
+    }
+}
+
+/// Offset from the left edge of `line` of a caret placed before the glyph at `index`, or after
+/// the last glyph when `index` is past the end of the line.
+pub fn caret_x(line: &Line, index: usize) -> f32 {
+    line.glyphs.get(index).map_or(line.width, |glyph| glyph.x)
+}
+
+/// A one pixel wide caret before the glyph at `index` of `line`, spanning the font's ascent and
+/// descent around the baseline. `line_x` is where the line starts, as passed to layout.
+pub fn caret(
+    font: &Font,
+    line: &Line,
+    index: usize,
+    (line_x, baseline): (f32, usize),
+    size: f32,
+) -> DisplayItem {
+    let scale = font.scale_factor(size);
+    let ascent = (font.ascent() as f32 * scale).round() as i32;
+    let descent = (font.descent() as f32 * scale).round() as i32;
+    DisplayItem::Rect(
+        Rect {
+            x: (line_x + caret_x(line, index)).round() as i32,
+            y: baseline as i32 - ascent,
+            width: 1,
+            height: (ascent - descent).max(1) as usize,
+        },
+        BLACK,
+    )
+}
+
+/// The caret position, as a line and glyph index, nearest `point` in horizontal text laid out
+/// at `origin`. Points above or below the text hit its first or last line, and a point over a
+/// glyph goes before or after it depending on which half it's in.
+pub fn hit_test(
+    font: &Font,
+    lines: &[Line],
+    origin: (usize, usize),
+    (x, y): (f32, f32),
+    options: &TextOptions,
+) -> (usize, usize) {
+    let ascent = (font.ascent() as f32 * font.scale_factor(options.size)).round();
+    let line_index = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (index, line_position(font, line, index, origin, options)))
+        .take_while(|&(index, (_, baseline))| index == 0 || y >= baseline as f32 - ascent)
+        .last();
+    let Some((line_index, (line_x, _))) = line_index else {
+        return (0, 0);
+    };
+    let glyph_index = lines[line_index]
+        .glyphs
+        .iter()
+        .take_while(|glyph| line_x + glyph.x + glyph.advance / 2.0 < x)
+        .count();
+    (line_index, glyph_index)
+}
+
+/// A range of horizontal text between two caret positions, each a line and glyph index as
+/// returned by `hit_test`. `start` never comes after `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Selection {
+    /// The text between where a drag from `from` to `to` starts and ends, in either direction.
+    /// Selecting with a rectangle is the same as dragging between its top left and bottom right
+    /// corners.
+    pub fn from_drag(
+        font: &Font,
+        lines: &[Line],
+        origin: (usize, usize),
+        (from, to): ((f32, f32), (f32, f32)),
+        options: &TextOptions,
+    ) -> Self {
+        let from = hit_test(font, lines, origin, from, options);
+        let to = hit_test(font, lines, origin, to, options);
+        Self {
+            start: from.min(to),
+            end: from.max(to),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The selected characters, with a `\n` between lines, for copying. Hyphens added where
+    /// a line broke at a soft hyphen are left out.
+    pub fn text(&self, lines: &[Line]) -> String {
+        let mut text = String::new();
+        for (line_index, glyphs) in self.glyph_ranges(lines) {
+            if line_index > self.start.0 {
+                text.push('\n');
+            }
+            let chars = lines[line_index].glyphs[glyphs]
+                .iter()
+                .map(|glyph| glyph.char);
+            text.extend(chars.filter(|&char| char != SOFT_HYPHEN));
+        }
+        text
+    }
+
+    /// One rectangle per line covering the selected glyphs and the full height of the line,
+    /// in the same coordinates as the display list for text laid out at `origin`.
+    pub fn highlight_rects(
+        &self,
+        font: &Font,
+        lines: &[Line],
+        origin: (usize, usize),
+        options: &TextOptions,
+    ) -> Vec<Rect> {
+        let ascent = (font.ascent() as f32 * font.scale_factor(options.size)).round() as i32;
+        self.glyph_ranges(lines)
+            .filter(|(_, glyphs)| !glyphs.is_empty())
+            .map(|(line_index, glyphs)| {
+                let line = &lines[line_index];
+                let (line_x, baseline) = line_position(font, line, line_index, origin, options);
+                let left = (line_x + caret_x(line, glyphs.start)).round() as i32;
+                let right = (line_x + caret_x(line, glyphs.end)).round() as i32;
+                Rect {
+                    x: left,
+                    y: baseline as i32 - ascent,
+                    width: (right - left).max(0) as usize,
+                    height: line_height(font, options.size),
+                }
+            })
+            .collect()
+    }
+
+    // The selected glyphs of each line the selection touches
+    fn glyph_ranges<'a>(
+        &'a self,
+        lines: &'a [Line],
+    ) -> impl Iterator<Item = (usize, std::ops::Range<usize>)> + 'a {
+        (self.start.0..=self.end.0)
+            .take_while(|&line_index| line_index < lines.len())
+            .map(|line_index| {
+                let len = lines[line_index].glyphs.len();
+                let start = match line_index == self.start.0 {
+                    true => self.start.1.min(len),
+                    false => 0,
+                };
+                let end = match line_index == self.end.0 {
+                    true => self.end.1.min(len),
+                    false => len,
+                };
+                (line_index, start..end.max(start))
+            })
+    }
+}
+
+/// Whether a blinking caret is shown `elapsed` after it last moved. It starts visible so the
+/// caret never disappears right after typing.
+pub fn caret_visible(elapsed: Duration) -> bool {
+    (elapsed.as_millis() / CARET_BLINK_INTERVAL.as_millis()).is_multiple_of(2)
+}
+
+/// Blends `color` over `rect`, using its alpha as coverage like text does. Parts of the
+/// rectangle outside the bitmap are clipped.
+pub fn fill_rect(
+    bitmap: &mut [u8],
+    window_width: usize,
+    rect: &Rect,
+    color: [u8; 4],
+    blending: Blending,
+) {
+    let coverage = color[3] as f32 / 255.0;
+    let window_height = bitmap.len() / 4 / window_width.max(1);
+    // Computed in i64 so huge rectangles can't overflow
+    let clamp = |value: i64, max: usize| value.clamp(0, max as i64) as usize;
+    let (left, right) = (
+        clamp(rect.x as i64, window_width),
+        clamp(rect.x as i64 + rect.width as i64, window_width),
+    );
+    let (top, bottom) = (
+        clamp(rect.y as i64, window_height),
+        clamp(rect.y as i64 + rect.height as i64, window_height),
+    );
+    for row in top..bottom {
+        for col in left..right {
+            let offset = (row * window_width + col) * 4;
+            for (channel, &value) in bitmap[offset..offset + 3].iter_mut().zip(&color) {
+                *channel = blend(*channel, value, coverage, blending);
+            }
+            bitmap[offset + 3] = 255;
+        }
+    }
+}
+
+/// The hex digits labelling a missing glyph box, split over two rows.
+fn missing_glyph_label(char: char) -> (String, String) {
+    let digits = format!("{:04X}", char as u32);
+    let (top, bottom) = digits.split_at(digits.len().div_ceil(2));
+    (top.to_string(), bottom.to_string())
+}
+
+/// Dimensions of the box drawn for a missing character, in pixels.
+struct MissingGlyphBox {
+    width: f32,
+    // The box reaches from the baseline up to the ascent
+    height: f32,
+    padding: f32,
+    // Font size of the two rows of hex digits
+    label_size: f32,
+}
+
+impl MissingGlyphBox {
+    fn new(font: &Font, char: char, size: f32) -> Self {
+        let height = font.ascent() as f32 * font.scale_factor(size);
+        let padding = (size * 0.1).round().max(1.0);
+        let label_size = ((height - 3.0 * padding) / 2.0).max(1.0);
+        let digit_advance = font.glyph_index('0').map_or(label_size / 2.0, |glyph_id| {
+            font.advance_width(glyph_id) as f32 * font.scale_factor(label_size)
+        });
+        let (top, _) = missing_glyph_label(char);
+        Self {
+            width: top.len() as f32 * digit_advance + 2.0 * padding,
+            height,
+            padding,
+            label_size,
         }
     }
+}
+
+/// Adds the outline of a box sitting on the baseline at `(x, baseline)`, with `char`'s hex
+/// codepoint written inside it in two rows.
+fn layout_missing_glyph_box(
+    display_list: &mut DisplayList,
+    font: &Font,
+    char: char,
+    (x, baseline): (f32, f32),
+    size: f32,
+) {
+    let missing = MissingGlyphBox::new(font, char, size);
+    let (left, bottom) = (x.round() as i32, baseline.round() as i32);
+    let (width, height) = (
+        missing.width.round() as usize,
+        missing.height.round() as usize,
+    );
+    let top = bottom - height as i32;
+    for edge in [
+        Rect {
+            x: left,
+            y: top,
+            width,
+            height: 1,
+        },
+        Rect {
+            x: left,
+            y: bottom - 1,
+            width,
+            height: 1,
+        },
+        Rect {
+            x: left,
+            y: top,
+            width: 1,
+            height,
+        },
+        Rect {
+            x: left + width as i32 - 1,
+            y: top,
+            width: 1,
+            height,
+        },
+    ] {
+        display_list.items.push(DisplayItem::Rect(edge, BLACK));
+    }
+
+    let label_scale = font.scale_factor(missing.label_size);
+    let (top_row, bottom_row) = missing_glyph_label(char);
+    let mut glyphs = Vec::new();
+    for (row, digits) in [top_row, bottom_row].iter().enumerate() {
+        // Digits have no descenders, so each row's baseline is the bottom of its cell
+        let row_baseline = top as f32 + (row + 1) as f32 * (missing.label_size + missing.padding);
+        let mut pen_x = x + missing.padding;
+        for digit in digits.chars() {
+            let glyph_id = font.glyph_index(digit).unwrap_or(0);
+            glyphs.push(RunGlyph {
+                glyph_id,
+                x: pen_x,
+                y: row_baseline,
+            });
+            pen_x += font.advance_width(glyph_id) as f32 * label_scale;
+        }
+    }
+    display_list.items.push(DisplayItem::Text(TextRun {
+        size: missing.label_size,
+        glyphs,
+    }));
+}
+
+/// Draws a glyph at `size` pixels per em with its origin at the pen position `(x, baseline)`,
+/// darkening the pixels it covers.
+fn render_char(
+    bitmap: &mut [u8],
+    window_width: usize,
+    font: &Font,
+    glyph_id: u16,
+    (x, baseline): (f32, f32),
+    size: f32,
+    options: &TextOptions,
+) {
+    let mut plot = |pixel_x: i32, pixel_y: i32, color: [u8; 3], coverage: f32| {
+        plot_rgba(
+            bitmap,
+            window_width,
+            (pixel_x, pixel_y),
+            color,
+            coverage,
+            options.blending,
+        );
+    };
+    rasterize_char(font, glyph_id, (x, baseline), size, options, &mut plot);
+}
+
+/// Blends `color` at `coverage` over the pixel at `(pixel_x, pixel_y)` and marks it opaque.
+fn plot_rgba(
+    bitmap: &mut [u8],
+    window_width: usize,
+    (pixel_x, pixel_y): (i32, i32),
+    color: [u8; 3],
+    coverage: f32,
+    blending: Blending,
+) {
+    if pixel_x < 0 || pixel_y < 0 || pixel_x as usize >= window_width {
+        return;
+    }
+    let offset = (pixel_y as usize * window_width + pixel_x as usize) * 4;
+    if offset + 4 > bitmap.len() {
+        return;
+    }
+    // Blend the glyph over whatever is already there
+    for (channel, &color) in bitmap[offset..offset + 3].iter_mut().zip(&color) {
+        *channel = blend(*channel, color, coverage, blending);
+    }
+    bitmap[offset + 3] = 255; // Alpha channel remains the same
+}
+
+/// Rasterizes a glyph, handing `plot` the position, color and coverage of every pixel it
+/// touches. Outlines are black; glyphs without one fall back to their embedded bitmap.
+fn rasterize_char(
+    font: &Font,
+    glyph_id: u16,
+    (x, baseline): (f32, f32),
+    size: f32,
+    options: &TextOptions,
+    plot: &mut dyn FnMut(i32, i32, [u8; 3], f32),
+) {
+    let Some(contours) = font.outline_at_size(glyph_id, size) else {
+        rasterize_bitmap_char(font, glyph_id, (x, baseline), size, plot);
+        return;
+    };
+    // Draw from the whole pixel the pen is in, with the outline shifted by the remainder
+    let (pixel_x, pixel_baseline) = (x.floor(), baseline.floor());
+    let (shift_x, shift_y) = (x - pixel_x, baseline - pixel_baseline);
+    // Outline y grows downward from the baseline, so slanting shifts by the height above it
+    let skew = match options.faux_italic {
+        true => FAUX_ITALIC_SKEW,
+        false => 0.0,
+    };
+    let mut outline = rasterizer::flatten(&contours);
+    if options.simplify_outlines {
+        outline = rasterizer::simplify(&outline, OUTLINE_SIMPLIFY_TOLERANCE);
+    }
+    let outline: Vec<Segment> = outline
+        .into_iter()
+        .map(|segment| Segment {
+            x0: segment.x0 - segment.y0 * skew + shift_x,
+            y0: segment.y0 + shift_y,
+            x1: segment.x1 - segment.y1 * skew + shift_x,
+            y1: segment.y1 + shift_y,
+        })
+        .collect();
+    if outline.is_empty() {
+        rasterize_bitmap_char(font, glyph_id, (x, baseline), size, plot);
+        return;
+    }
+    let coverage = options
+        .rasterizer
+        .fill(&outline, &rasterizer::bounding_box(&outline));
+    // Rasterizers that adjust the outline may grow the box
+    let bbox = coverage.bbox;
+    // Faux bold smears coverage rightward: each pixel takes the darkest of the pixels up to
+    // `bold` to its left, with the farthest one weighted by the fractional part
+    let bold = faux_bold_width(size, options);
+    let spread = bold.ceil() as usize;
+    let smeared = |col: usize, row: usize| {
+        (0..=spread.min(col))
+            .filter(|&distance| col - distance < bbox.width)
+            .map(|distance| {
+                let weight = (bold + 1.0 - distance as f32).clamp(0.0, 1.0);
+                coverage.get(col - distance, row) * weight
+            })
+            .fold(0.0, f32::max)
+    };
+
+    for row in 0..bbox.height {
+        for col in 0..bbox.width + spread {
+            let value = smeared(col, row);
+            if value <= 0.0 {
+                continue;
+            }
+            let pixel_x = pixel_x as i32 + bbox.left + col as i32;
+            let pixel_y = pixel_baseline as i32 + bbox.top + row as i32;
+            plot(pixel_x, pixel_y, [0, 0, 0], value);
+        }
+    }
+}
+
+/// How much faux bold widens glyphs at `size`, or 0 without it.
+fn faux_bold_width(size: f32, options: &TextOptions) -> f32 {
+    match options.faux_bold {
+        true => (size * FAUX_BOLD_STRENGTH).max(1.0),
+        false => 0.0,
+    }
+}
 
-    // Downscale the supersampled bitmap to the target scale
-    for row_index in 0..8 {
-        for col_index in 0..8 {
-            let mut sum = 0;
-            for i in 0..supersample_scale {
-                for j in 0..supersample_scale {
-                    let pixel_x = col_index * supersample_scale + i;
-                    let pixel_y = row_index * supersample_scale + j;
-                    sum += supersample_bitmap[pixel_y * supersample_size + pixel_x] as u32;
+/// Rasterizes a glyph's embedded bitmap, for glyphs without an outline.
+fn rasterize_bitmap_char(
+    font: &Font,
+    glyph_id: u16,
+    (x, baseline): (f32, f32),
+    size: f32,
+    plot: &mut dyn FnMut(i32, i32, [u8; 3], f32),
+) {
+    if let Some(glyph) = font.bitmap_glyph(glyph_id, size.round() as u16) {
+        rasterize_bitmap(&glyph, (x, baseline), size, plot);
+    }
+}
+
+/// Hands `plot` every pixel of `glyph`'s bitmap, transparent ones included, so its whole
+/// rectangle comes out opaque in RGBA output. Bitmaps from another size are scaled to `size`
+/// with nearest-neighbour sampling.
+fn rasterize_bitmap(
+    glyph: &BitmapGlyph,
+    (x, baseline): (f32, f32),
+    size: f32,
+    plot: &mut dyn FnMut(i32, i32, [u8; 3], f32),
+) {
+    let scale = size / glyph.ppem as f32;
+    let left = (x + glyph.left as f32 * scale).round() as i32;
+    let top = (baseline - glyph.top as f32 * scale).round() as i32;
+    let width = (glyph.width as f32 * scale).round() as usize;
+    let height = (glyph.height as f32 * scale).round() as usize;
+
+    for row in 0..height {
+        for col in 0..width {
+            let source_col = ((col as f32 / scale) as usize).min(glyph.width - 1);
+            let source_row = ((row as f32 / scale) as usize).min(glyph.height - 1);
+            let source = source_row * glyph.width + source_col;
+            let (color, value) = match &glyph.pixels {
+                // Black, as with outlines
+                BitmapPixels::Coverage(coverage) => ([0, 0, 0], coverage[source]),
+                BitmapPixels::Rgba(rgba) => {
+                    let pixel = &rgba[source * 4..source * 4 + 4];
+                    ([pixel[0], pixel[1], pixel[2]], pixel[3])
                 }
+            };
+            plot(
+                left + col as i32,
+                top + row as i32,
+                color,
+                value as f32 / 255.0,
+            );
+        }
+    }
+}
+
+/// Covers an sRGB channel value with `color` at the given coverage.
+fn blend(channel: u8, color: u8, coverage: f32, blending: Blending) -> u8 {
+    let (background, foreground) = (channel as f32 / 255.0, color as f32 / 255.0);
+    let blended = match blending {
+        Blending::Srgb => background + (foreground - background) * coverage,
+        Blending::Linear => {
+            let background = srgb_to_linear(background);
+            let foreground = srgb_to_linear(foreground);
+            linear_to_srgb(background + (foreground - background) * coverage)
+        }
+    };
+    (blended * 255.0).round() as u8
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::{parse_from_bytes, ParseOptions};
+    use std::path::{Path, PathBuf};
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    /// A `width` by `height` bitmap filled with `color`.
+    fn bitmap(width: usize, height: usize, color: [u8; 4]) -> Vec<u8> {
+        color.repeat(width * height)
+    }
+
+    fn pixel(bitmap: &[u8], window_width: usize, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * window_width + x) * 4;
+        bitmap[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn fill_rect_fills_the_interior_and_clips_to_the_bitmap() {
+        let mut pixels = bitmap(4, 3, WHITE);
+        let rect = Rect {
+            x: -2,
+            y: 1,
+            width: 4,
+            height: 10,
+        };
+        fill_rect(&mut pixels, 4, &rect, [255, 0, 0, 255], Blending::Srgb);
+        for y in 0..3 {
+            for x in 0..4 {
+                let expected = match (x < 2, y >= 1) {
+                    (true, true) => [255, 0, 0, 255],
+                    _ => WHITE,
+                };
+                assert_eq!(pixel(&pixels, 4, x, y), expected, "pixel {x}, {y}");
             }
-            let avg = sum / (supersample_scale * supersample_scale) as u32;
-            let color = 255 * (1 - avg);
-            for i in 0..scale {
-                for j in 0..scale {
-                    let pixel_x = x + col_index * scale + i;
-                    let pixel_y = y + row_index * scale + j;
-                    let offset = (pixel_y * window_width + pixel_x) * 4;
-                    bitmap[offset] = color as u8;
-                    bitmap[offset + 1] = color as u8;
-                    bitmap[offset + 2] = color as u8;
-                    bitmap[offset + 3] = 255; // Alpha channel remains the same
+        }
+    }
+
+    #[test]
+    fn fill_rect_blends_by_alpha() {
+        let mut pixels = bitmap(1, 1, WHITE);
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        fill_rect(&mut pixels, 1, &rect, [0, 0, 0, 128], Blending::Srgb);
+        assert_eq!(pixels, [127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn fill_rect_far_outside_the_bitmap_draws_nothing() {
+        let mut pixels = bitmap(2, 2, WHITE);
+        let rect = Rect {
+            x: i32::MAX,
+            y: i32::MIN,
+            width: usize::MAX,
+            height: usize::MAX,
+        };
+        fill_rect(&mut pixels, 2, &rect, [0, 0, 0, 255], Blending::Srgb);
+        assert_eq!(pixels, bitmap(2, 2, WHITE));
+    }
+
+    fn arial() -> Font {
+        let bytes = include_bytes!("../assets/fonts/arial.ttf");
+        parse_from_bytes(bytes.to_vec(), ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn text_mask_matches_black_text_drawn_on_white() {
+        let font = arial();
+        let options = TextOptions {
+            underline: true,
+            ..TextOptions::default()
+        };
+        let (mask, width, height) = render_text_mask(&font, "Hag", &options);
+        let mut pixels = bitmap(width, height, WHITE);
+        render_text(&mut pixels, "Hag", 0, 0, width, &font, &options).unwrap();
+        assert!(mask.contains(&255));
+        for y in 0..height {
+            for x in 0..width {
+                let red = pixel(&pixels, width, x, y)[0];
+                assert_eq!(mask[y * width + x], 255 - red, "pixel {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn text_mask_is_covered_wherever_the_rgba_output_is_opaque() {
+        let font = arial();
+        let options = TextOptions::default();
+        let (mask, width, height) = render_text_mask(&font, "Hag", &options);
+        let mut pixels = bitmap(width, height, [0; 4]);
+        render_text(&mut pixels, "Hag", 0, 0, width, &font, &options).unwrap();
+        for y in 0..height {
+            for x in 0..width {
+                let opaque = pixel(&pixels, width, x, y)[3] == 255;
+                assert_eq!(mask[y * width + x] > 0, opaque, "pixel {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_bitmap_pixels_still_come_out_opaque() {
+        // An opaque red pixel beside a fully transparent one
+        let glyph = BitmapGlyph {
+            ppem: 16,
+            width: 2,
+            height: 1,
+            left: 0,
+            top: 1,
+            pixels: BitmapPixels::Rgba(vec![255, 0, 0, 255, 0, 0, 0, 0]),
+        };
+        let mut pixels = bitmap(2, 1, [0; 4]);
+        let mut mask = [0; 2];
+        rasterize_bitmap(&glyph, (0.0, 1.0), 16.0, &mut |x, y, color, coverage| {
+            plot_rgba(&mut pixels, 2, (x, y), color, coverage, Blending::Srgb);
+            mask[x as usize] = (coverage * 255.0) as u8;
+        });
+        assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 0, 255]);
+        assert_eq!(mask, [255, 0]);
+    }
+
+    // Glyphs and sizes locked down by the images in assets/reference
+    const REFERENCE_GLYPHS: [char; 5] = ['a', 'g', 'Q', '&', '@'];
+    const REFERENCE_SIZES: [f32; 3] = [12.0, 24.0, 48.0];
+    // Largest per-pixel coverage difference that still counts as a match
+    const REFERENCE_TOLERANCE: u8 = 2;
+
+    fn reference_path(char: char, size: f32) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets/reference")
+            .join(format!("U+{:04X}-{}px.png", char as u32, size))
+    }
+
+    /// Reads an 8-bit grayscale PNG as its pixels, width and height.
+    fn read_gray_png(path: &Path) -> (Vec<u8>, usize, usize) {
+        let file = std::fs::File::open(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(
+            (info.color_type, info.bit_depth),
+            (png::ColorType::Grayscale, png::BitDepth::Eight)
+        );
+        pixels.truncate(info.buffer_size());
+        (pixels, info.width as usize, info.height as usize)
+    }
+
+    fn write_gray_png(path: &Path, pixels: &[u8], width: usize, height: usize) {
+        let file =
+            std::fs::File::create(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(pixels)
+            .unwrap();
+    }
+
+    /// Renders each reference glyph as a coverage mask and compares it with its image in
+    /// assets/reference. Set `CAPYNET_UPDATE_REFERENCES` to rewrite the images from the current
+    /// output after an intended change, and `CAPYNET_REFERENCE_DIFFS` to a directory to get an
+    /// image of the per-pixel differences for each glyph that doesn't match.
+    #[test]
+    fn glyphs_match_reference_images() {
+        let font = arial();
+        let update = std::env::var_os("CAPYNET_UPDATE_REFERENCES").is_some();
+        let diff_dir = std::env::var_os("CAPYNET_REFERENCE_DIFFS").map(PathBuf::from);
+        let mut failures = Vec::new();
+        for char in REFERENCE_GLYPHS {
+            for size in REFERENCE_SIZES {
+                let options = TextOptions {
+                    size,
+                    ..TextOptions::default()
+                };
+                let (mask, width, height) = render_text_mask(&font, &char.to_string(), &options);
+                let path = reference_path(char, size);
+                if update {
+                    write_gray_png(&path, &mask, width, height);
+                    continue;
+                }
+                let (reference, reference_width, reference_height) = read_gray_png(&path);
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                if (reference_width, reference_height) != (width, height) {
+                    failures.push(format!(
+                        "{name}: rendered {width}x{height}, expected \
+                         {reference_width}x{reference_height}"
+                    ));
+                    continue;
+                }
+                let diff: Vec<u8> = mask
+                    .iter()
+                    .zip(&reference)
+                    .map(|(&pixel, &expected)| pixel.abs_diff(expected))
+                    .collect();
+                let worst = diff.iter().copied().max().unwrap_or(0);
+                if worst > REFERENCE_TOLERANCE {
+                    let differing = diff.iter().filter(|&&d| d > REFERENCE_TOLERANCE).count();
+                    failures.push(format!(
+                        "{name}: {differing} pixels differ, by up to {worst}"
+                    ));
+                    if let Some(diff_dir) = &diff_dir {
+                        write_gray_png(&diff_dir.join(&name), &diff, width, height);
+                    }
                 }
             }
         }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Where each text run's first glyph starts, one per line.
+    fn line_starts(display_list: &DisplayList) -> Vec<f32> {
+        display_list
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text(run) => run.glyphs.first().map(|glyph| glyph.x),
+                DisplayItem::Rect(..) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wrapped_lines_are_aligned_independently() {
+        let font = arial();
+        let text = "A centered heading that wraps";
+        for align in [TextAlign::Left, TextAlign::Center, TextAlign::Right] {
+            let options = TextOptions {
+                size: 20.0,
+                max_width: 160,
+                align,
+                ..TextOptions::default()
+            };
+            let lines = layout_text(&font, text, &options);
+            assert!(lines.len() > 1);
+            let display_list = layout_display_list(&font, text, (10, 0), (400, 400), &options);
+            let starts = line_starts(&display_list);
+            assert_eq!(starts.len(), lines.len());
+            for (start, line) in starts.iter().zip(&lines) {
+                let offset = match align {
+                    TextAlign::Left => 0.0,
+                    TextAlign::Center => (160.0 - line.width) / 2.0,
+                    TextAlign::Right => 160.0 - line.width,
+                };
+                assert!((start - (10.0 + offset)).abs() < 1e-3, "{align:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn vertical_text_advances_down_columns_from_the_right() {
+        let font = arial();
+        let options = TextOptions {
+            direction: TextDirection::Vertical,
+            max_width: 200,
+            ..TextOptions::default()
+        };
+        let display_list = layout_display_list(&font, "ab\ncd", (0, 0), (400, 400), &options);
+        let [DisplayItem::Text(run)] = display_list.items.as_slice() else {
+            panic!("expected a single text run");
+        };
+        let [a, b, c, d] = run.glyphs.as_slice() else {
+            panic!("expected four glyphs");
+        };
+        // Without vertical metrics each glyph takes a line height, hanging from the ascent
+        let advance = line_height(&font, options.size) as f32;
+        assert_eq!(b.y - a.y, advance);
+        assert_eq!(d.y - c.y, advance);
+        assert_eq!(a.y, c.y);
+        assert!(c.x < a.x && a.x < 200.0);
+    }
+
+    #[test]
+    fn tabs_advance_to_the_next_tab_stop() {
+        let font = arial();
+        let options = TextOptions {
+            size: 20.0,
+            tab_width: 2.0,
+            ..TextOptions::default()
+        };
+        let stop = 2.0 * 20.0;
+        for (before, column) in [("", 1.0), ("a", 1.0), ("abcdefghij", 3.0)] {
+            let width = layout_text(&font, before, &options)[0].width;
+            assert!(width < column * stop && width >= (column - 1.0) * stop);
+            let lines = layout_text(&font, &format!("{before}\tb"), &options);
+            let b = lines[0].glyphs.last().unwrap();
+            assert_eq!(b.char, 'b');
+            assert!(
+                (b.x - column * stop).abs() < 1e-3,
+                "{before:?} then b at {}",
+                b.x
+            );
+        }
+    }
+
+    #[test]
+    fn control_characters_are_skipped_or_replaced() {
+        let font = arial();
+        let chars = |options: &TextOptions| -> Vec<Vec<char>> {
+            layout_text(&font, "a\0b\r\nc\rd", options)
+                .iter()
+                .map(|line| line.glyphs.iter().map(|glyph| glyph.char).collect())
+                .collect()
+        };
+        assert_eq!(
+            chars(&TextOptions::default()),
+            [vec!['a', 'b'], vec!['c'], vec!['d']]
+        );
+        let options = TextOptions {
+            control_characters: ControlCharacters::Replace,
+            ..TextOptions::default()
+        };
+        assert_eq!(
+            chars(&options),
+            [vec!['a', REPLACEMENT_CHARACTER, 'b'], vec!['c'], vec!['d']]
+        );
+    }
+
+    #[test]
+    fn underline_spans_the_run_at_the_post_table_position() {
+        let font = arial();
+        let options = TextOptions {
+            size: 32.0,
+            underline: true,
+            ..TextOptions::default()
+        };
+        let text = "a   b";
+        let (width, height) = (200, 60);
+        let mut pixels = bitmap(width, height, WHITE);
+        render_text(&mut pixels, text, 0, 0, width, &font, &options).unwrap();
+
+        let scale = font.scale_factor(options.size);
+        let metrics = font.underline_metrics();
+        assert!(metrics.position < 0);
+        let baseline = (font.ascent() as f32 * scale).round() as i32;
+        let top = (baseline - (metrics.position as f32 * scale).round() as i32) as usize;
+        let thickness = ((metrics.thickness as f32 * scale).round() as usize).max(1);
+        let line_width = layout_text(&font, text, &options)[0].width.round() as usize;
+        for x in 0..line_width {
+            for y in top..top + thickness {
+                assert_eq!(
+                    pixel(&pixels, width, x, y),
+                    [0, 0, 0, 255],
+                    "pixel {x}, {y}"
+                );
+            }
+        }
+        // Nothing is drawn past the end of the run or below the line, even between words
+        assert_eq!(pixel(&pixels, width, line_width + 1, top), WHITE);
+        let gap = line_width / 2;
+        assert_eq!(pixel(&pixels, width, gap, top + thickness + 1), WHITE);
+        assert_eq!(pixel(&pixels, width, gap, top - 1), WHITE);
+    }
+
+    #[test]
+    fn half_coverage_differs_between_blending_modes() {
+        let mut srgb = bitmap(1, 1, WHITE);
+        plot_rgba(&mut srgb, 1, (0, 0), [0, 0, 0], 0.5, Blending::Srgb);
+        let mut linear = bitmap(1, 1, WHITE);
+        plot_rgba(&mut linear, 1, (0, 0), [0, 0, 0], 0.5, Blending::Linear);
+        assert_eq!(pixel(&srgb, 1, 0, 0), [128, 128, 128, 255]);
+        // Half the light of white is sRGB 188, noticeably lighter than the naive midpoint
+        assert_eq!(pixel(&linear, 1, 0, 0), [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn blending_modes_agree_on_full_and_no_coverage() {
+        for blending in [Blending::Srgb, Blending::Linear] {
+            for channel in [0, 37, 128, 255] {
+                assert_eq!(blend(channel, 200, 0.0, blending), channel);
+                assert_eq!(blend(channel, 200, 1.0, blending), 200);
+            }
+        }
+    }
+
+    #[test]
+    fn monochrome_text_is_pure_black_and_white() {
+        let font = arial();
+        let text = "Sphinx of black quartz";
+        let mut pixels = bitmap(300, 40, WHITE);
+        let options = TextOptions {
+            size: 13.0,
+            rasterizer: Arc::new(rasterizer::MonochromeRasterizer),
+            ..TextOptions::default()
+        };
+        render_text(&mut pixels, text, 0, 0, 300, &font, &options).unwrap();
+        assert!(pixels.contains(&0));
+        assert!(pixels.iter().all(|&channel| channel == 0 || channel == 255));
+
+        // Antialiased text at the same size has intermediate shades
+        let mut pixels = bitmap(300, 40, WHITE);
+        let options = TextOptions {
+            size: 13.0,
+            ..TextOptions::default()
+        };
+        render_text(&mut pixels, text, 0, 0, 300, &font, &options).unwrap();
+        assert!(pixels.iter().any(|&channel| channel != 0 && channel != 255));
+    }
+
+    /// One past the rightmost column with any ink.
+    fn drawn_width(pixels: &[u8], window_width: usize) -> usize {
+        let rows = pixels.len() / 4 / window_width;
+        (0..window_width)
+            .rev()
+            .find(|&x| (0..rows).any(|y| pixel(pixels, window_width, x, y) != WHITE))
+            .map_or(0, |x| x + 1)
+    }
+
+    #[test]
+    fn measured_width_matches_the_drawn_width() {
+        let font = arial();
+        let options = TextOptions {
+            size: 24.0,
+            underline: true,
+            ..TextOptions::default()
+        };
+        let text = "Measure me, Will";
+        let extent = measure_text(&font, text, &options);
+        let mut pixels = bitmap(600, 60, WHITE);
+        render_text(&mut pixels, text, 0, 0, 600, &font, &options).unwrap();
+        // The underline runs the full width of the line, rounded to whole pixels
+        let drawn = drawn_width(&pixels, 600);
+        assert!(drawn <= extent.width && extent.width <= drawn + 1);
+        assert_eq!(extent.line_count, 1);
+        assert_eq!(extent.height, line_height(&font, options.size));
+
+        let wrapped = TextOptions {
+            max_width: drawn / 2,
+            ..options
+        };
+        let extent = measure_text(&font, text, &wrapped);
+        assert!(extent.line_count >= 2);
+        assert!(extent.width <= drawn / 2);
+        assert_eq!(
+            extent.height,
+            extent.line_count * line_height(&font, options.size)
+        );
+    }
+
+    /// Coverage of each pixel a glyph drawn with its pen at `pen` touches.
+    fn glyph_coverage(font: &Font, char: char, pen: (f32, f32)) -> Vec<((i32, i32), u32)> {
+        let mut coverage = Vec::new();
+        let glyph_id = font.glyph_index(char).unwrap();
+        let options = TextOptions::default();
+        rasterize_char(
+            font,
+            glyph_id,
+            pen,
+            24.0,
+            &options,
+            &mut |x, y, _, value| {
+                if value > 0.0 {
+                    coverage.push(((x, y), (value * 1000.0).round() as u32));
+                }
+            },
+        );
+        coverage.sort();
+        coverage
+    }
+
+    #[test]
+    fn glyphs_are_positioned_to_a_fraction_of_a_pixel() {
+        let font = arial();
+        let whole = glyph_coverage(&font, 'l', (10.0, 30.0));
+        let half = glyph_coverage(&font, 'l', (10.5, 30.0));
+        assert_ne!(whole, half);
+        // A whole pixel further along is the same coverage, moved over by one
+        let next = glyph_coverage(&font, 'l', (11.0, 30.0));
+        let moved: Vec<_> = whole
+            .iter()
+            .map(|&((x, y), value)| ((x + 1, y), value))
+            .collect();
+        assert_eq!(next, moved);
+    }
+
+    #[test]
+    fn plain_text_keeps_its_spaces_and_blank_lines() {
+        let font = arial();
+        let text = "a  b\n\n  indented\n";
+        let line_text = |options: &TextOptions| -> Vec<String> {
+            layout_text(&font, text, options)
+                .iter()
+                .map(|line| line.glyphs.iter().map(|glyph| glyph.char).collect())
+                .collect()
+        };
+        let plain = TextOptions {
+            preserve_spaces: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(line_text(&plain), ["a  b", "", "  indented", ""]);
+        // Without it, spaces at the start of a paragraph go, as for HTML
+        assert_eq!(
+            line_text(&TextOptions::default()),
+            ["a  b", "", "indented", ""]
+        );
+
+        let lines = layout_text(&font, text, &plain);
+        let space = font.advance_width(font.glyph_index(' ').unwrap()) as f32
+            * font.scale_factor(plain.size);
+        assert!((lines[2].glyphs[2].x - 2.0 * space).abs() < 1e-3);
+    }
+
+    #[test]
+    fn unsupported_emoji_is_drawn_as_a_box_of_hex_digits() {
+        let font = arial();
+        let options = TextOptions {
+            size: 48.0,
+            missing_glyph_labels: true,
+            ..TextOptions::default()
+        };
+        let (width, height) = (200, 100);
+        let display_list =
+            layout_display_list(&font, "\u{1F600}", (0, 0), (width, height), &options);
+
+        // The box edges, and a run of the digits 1F6 over 00
+        let edges: Vec<Rect> = display_list
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Rect(rect, _) => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edges.len(), 4);
+        let label = display_list
+            .items
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text(run) if !run.glyphs.is_empty() => Some(run),
+                _ => None,
+            })
+            .unwrap();
+        let digits: Vec<u16> = "1F600"
+            .chars()
+            .map(|digit| font.glyph_index(digit).unwrap())
+            .collect();
+        let glyph_ids: Vec<u16> = label.glyphs.iter().map(|glyph| glyph.glyph_id).collect();
+        assert_eq!(glyph_ids, digits);
+
+        // Some pixels strictly inside the box are inked by the digits
+        let left = edges.iter().map(|rect| rect.x).min().unwrap() as usize;
+        let top = edges.iter().map(|rect| rect.y).min().unwrap() as usize;
+        let right = edges
+            .iter()
+            .map(|rect| rect.x as usize + rect.width)
+            .max()
+            .unwrap();
+        let bottom = edges
+            .iter()
+            .map(|rect| rect.y as usize + rect.height)
+            .max()
+            .unwrap();
+        let mut pixels = bitmap(width, height, WHITE);
+        render_text(&mut pixels, "\u{1F600}", 0, 0, width, &font, &options).unwrap();
+        let inked = (top + 1..bottom - 1)
+            .flat_map(|y| (left + 1..right - 1).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&pixels, width, x, y)[0] < 128)
+            .count();
+        assert!(inked > 0);
+    }
+
+    #[test]
+    fn overflowing_label_ends_with_an_ellipsis_within_the_width() {
+        let font = arial();
+        let options = TextOptions {
+            max_width: 120,
+            overflow: TextOverflow::Ellipsis,
+            ..TextOptions::default()
+        };
+        let url = "https://example.com/a/very/long/path/that/cannot/fit";
+        let lines = layout_text(&font, url, &options);
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert!(line.width <= 120.0);
+        assert_eq!(line.glyphs.last().unwrap().char, ELLIPSIS);
+        let kept: String = line.glyphs.iter().map(|glyph| glyph.char).collect();
+        assert!(url.starts_with(kept.trim_end_matches(ELLIPSIS)));
+
+        // Text that fits is left alone
+        let lines = layout_text(&font, "short", &options);
+        assert!(lines[0].glyphs.iter().all(|glyph| glyph.char != ELLIPSIS));
+    }
+
+    #[test]
+    fn paragraph_is_laid_out_as_one_run_per_line() {
+        let font = arial();
+        let options = TextOptions {
+            max_width: 150,
+            ..TextOptions::default()
+        };
+        let text = "First line\nA second paragraph long enough to wrap\nLast";
+        let (width, height) = (200, 200);
+        let display_list = layout_display_list(&font, text, (0, 0), (width, height), &options);
+        let runs: Vec<&TextRun> = display_list
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text(run) => Some(run),
+                DisplayItem::Rect(..) => None,
+            })
+            .collect();
+        assert_eq!(runs.len(), layout_text(&font, text, &options).len());
+        assert!(runs.len() > 3);
+        let baselines: Vec<f32> = runs.iter().map(|run| run.glyphs[0].y).collect();
+        let line_height = line_height(&font, options.size) as f32;
+        for pair in baselines.windows(2) {
+            assert!((pair[1] - pair[0] - line_height).abs() < 1.0);
+        }
+
+        // Painting the display list draws what render_text does
+        let mut painted = bitmap(width, height, WHITE);
+        paint(&mut painted, width, &font, &display_list, &options);
+        let mut rendered = bitmap(width, height, WHITE);
+        render_text(&mut rendered, text, 0, 0, width, &font, &options).unwrap();
+        assert!(painted == rendered);
+    }
+
+    #[test]
+    fn caret_sits_after_the_advances_before_it() {
+        let font = arial();
+        let options = TextOptions::default();
+        let line = &layout_text(&font, "Hello", &options)[0];
+        let advances: f32 = line.glyphs[..3].iter().map(|glyph| glyph.advance).sum();
+        assert!((caret_x(line, 3) - advances).abs() < 1e-3);
+        assert_eq!(caret_x(line, 0), 0.0);
+        assert_eq!(caret_x(line, 99), line.width);
+
+        let scale = font.scale_factor(options.size);
+        let ascent = (font.ascent() as f32 * scale).round() as i32;
+        let descent = (font.descent() as f32 * scale).round() as i32;
+        let DisplayItem::Rect(rect, _) = caret(&font, line, 3, (10.0, 40), options.size) else {
+            panic!("caret is not a rule");
+        };
+        assert_eq!(rect.x, (10.0 + advances).round() as i32);
+        assert_eq!((rect.y, rect.width), (40 - ascent, 1));
+        assert_eq!(rect.height as i32, ascent - descent);
     }
 
-    Ok(char_width)
-}
-
-fn map_char_to_glyph(char: char) -> Result<[u8; 8], CapyError> {
-    match char {
-        #[rustfmt::skip]
-        'a' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b00000100,
-            0b00111100,
-            0b01000100,
-            0b00111100,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'b' => Ok([
-            0b00000000,
-            0b01000000,
-            0b01000000,
-            0b01111000,
-            0b01000100,
-            0b01000100,
-            0b01111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'c' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b01000000,
-            0b01000000,
-            0b01000000,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'd' => Ok([
-            0b00000000,
-            0b00001000,
-            0b00001000,
-            0b00111000,
-            0b01001000,
-            0b01001000,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'e' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00110000,
-            0b01001000,
-            0b01111000,
-            0b01000000,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'f' => Ok([
-            0b00000000,
-            0b00011000,
-            0b00100000,
-            0b01110000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'g' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b01001000,
-            0b00111000,
-            0b00001000,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'h' => Ok([
-            0b00000000,
-            0b01000000,
-            0b01000000,
-            0b01110000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'i' => Ok([
-            0b00000000,
-            0b00100000,
-            0b00000000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'j' => Ok([
-            0b00000000,
-            0b00010000,
-            0b00000000,
-            0b00010000,
-            0b00010000,
-            0b00010000,
-            0b01100000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'k' => Ok([
-            0b00000000,
-            0b01000000,
-            0b01000000,
-            0b01001000,
-            0b01110000,
-            0b01001000,
-            0b01001000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'l' => Ok([
-            0b00000000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'm' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01101000,
-            0b01010100,
-            0b01010100,
-            0b01010100,
-            0b01010100,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'n' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01110000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'o' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b01000100,
-            0b01000100,
-            0b01000100,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'p' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01110000,
-            0b01001000,
-            0b01110000,
-            0b01000000,
-            0b01000000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'q' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b01001000,
-            0b00111000,
-            0b00001000,
-            0b00001000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'r' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01110000,
-            0b01001000,
-            0b01000000,
-            0b01000000,
-            0b01000000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        's' => Ok([
-            0b00000000,
-            0b00000000,
-            0b00111000,
-            0b01000000,
-            0b00111000,
-            0b00001000,
-            0b01110000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        't' => Ok([
-            0b00000000,
-            0b00100000,
-            0b01110000,
-            0b00100000,
-            0b00100000,
-            0b00100000,
-            0b00011000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'u' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b00111000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'v' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01001000,
-            0b01001000,
-            0b01001000,
-            0b00110000,
-            0b00110000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'w' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01000100,
-            0b01000100,
-            0b01010100,
-            0b01010100,
-            0b00101000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'x' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01001000,
-            0b00110000,
-            0b00110000,
-            0b01001000,
-            0b01001000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'y' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01001000,
-            0b01001000,
-            0b00111000,
-            0b00001000,
-            0b01110000,
-            0b00000000,
-        ]),
-        #[rustfmt::skip]
-        'z' => Ok([
-            0b00000000,
-            0b00000000,
-            0b01111000,
-            0b00010000,
-            0b00100000,
-            0b01000000,
-            0b01111000,
-            0b00000000,
-        ]),
-        _ => Err(CapyError::new(
-            crate::error::ErrorCode::Unknown,
-            "Unknown character",
-        )),
+    #[test]
+    fn top_left_and_equivalent_baseline_origins_draw_the_same_pixels() {
+        let font = arial();
+        let top_left = TextOptions {
+            origin: TextOrigin::TopLeft,
+            ..TextOptions::default()
+        };
+        let baseline = TextOptions {
+            origin: TextOrigin::Baseline,
+            ..TextOptions::default()
+        };
+        let ascent = (font.ascent() as f32 * font.scale_factor(top_left.size)).round() as usize;
+        let (width, height) = (120, 80);
+        let text = "Origin\ntest";
+        let mut from_top_left = bitmap(width, height, WHITE);
+        render_text(&mut from_top_left, text, 5, 10, width, &font, &top_left).unwrap();
+        let mut from_baseline = bitmap(width, height, WHITE);
+        render_text(
+            &mut from_baseline,
+            text,
+            5,
+            10 + ascent,
+            width,
+            &font,
+            &baseline,
+        )
+        .unwrap();
+        assert!(from_top_left.iter().any(|&channel| channel < 255));
+        assert!(from_top_left == from_baseline);
+    }
+
+    #[test]
+    fn word_breaks_at_its_soft_hyphen_with_a_visible_hyphen() {
+        let font = arial();
+        let text = "hyphen\u{AD}ated";
+        let chars = |line: &Line| {
+            line.glyphs
+                .iter()
+                .map(|glyph| glyph.char)
+                .collect::<String>()
+        };
+        // Wide enough for the whole word, the soft hyphen isn't drawn
+        let lines = layout_text(&font, text, &TextOptions::default());
+        assert_eq!(lines.len(), 1);
+        assert_eq!(chars(&lines[0]), "hyphenated");
+
+        let first_part = layout_text(&font, "hyphen-", &TextOptions::default())[0].width;
+        let options = TextOptions {
+            max_width: first_part.ceil() as usize + 1,
+            ..TextOptions::default()
+        };
+        let lines = layout_text(&font, text, &options);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(chars(&lines[0]), "hyphen\u{AD}");
+        let hyphen = lines[0].glyphs.last().unwrap();
+        assert_eq!(Some(hyphen.glyph_id), font.glyph_index('-'));
+        assert!(lines[0].width <= options.max_width as f32);
+        assert_eq!(chars(&lines[1]), "ated");
+        assert_eq!(lines[1].glyphs[0].x, 0.0);
+    }
+
+    /// Renders `text` at 40px on white and returns the bitmap and its width.
+    fn render_styled(text: &str, options: &TextOptions) -> (Vec<u8>, usize) {
+        let font = arial();
+        let (width, height) = (120, 60);
+        let mut pixels = bitmap(width, height, WHITE);
+        render_text(&mut pixels, text, 10, 5, width, &font, options).unwrap();
+        (pixels, width)
+    }
+
+    /// Mean x of the inked pixels in rows `rows`.
+    fn ink_center(pixels: &[u8], width: usize, rows: std::ops::Range<usize>) -> f32 {
+        let inked: Vec<usize> = rows
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(pixels, width, x, y)[0] < 128)
+            .map(|(x, _)| x)
+            .collect();
+        inked.iter().sum::<usize>() as f32 / inked.len() as f32
+    }
+
+    #[test]
+    fn faux_italic_leans_the_top_of_a_glyph_right() {
+        let upright = TextOptions {
+            size: 40.0,
+            ..TextOptions::default()
+        };
+        let italic = TextOptions {
+            faux_italic: true,
+            ..upright.clone()
+        };
+        // The stem of an 'l' runs from about row 10 to the baseline near row 42
+        let lean = |options: &TextOptions| {
+            let (pixels, width) = render_styled("l", options);
+            ink_center(&pixels, width, 10..16) - ink_center(&pixels, width, 36..42)
+        };
+        assert!(lean(&upright).abs() < 1.0);
+        assert!(lean(&italic) > 3.0);
+    }
+
+    #[test]
+    fn faux_bold_covers_more_pixels() {
+        let regular = TextOptions {
+            size: 40.0,
+            ..TextOptions::default()
+        };
+        let bold = TextOptions {
+            faux_bold: true,
+            ..regular.clone()
+        };
+        let inked = |options: &TextOptions| {
+            let (pixels, _) = render_styled("Hi", options);
+            pixels.chunks(4).filter(|pixel| pixel[0] < 128).count()
+        };
+        assert!(inked(&bold) > inked(&regular) * 11 / 10);
+    }
+
+    #[test]
+    fn uncovered_character_is_reported_with_its_byte_index() {
+        let font = arial();
+        let (width, height) = (200, 40);
+        let mut pixels = bitmap(width, height, WHITE);
+        let text = "caf\u{E9} \u{1F600}!\tend\n";
+        let missing = render_text_reporting_missing(
+            &mut pixels,
+            text,
+            0,
+            0,
+            width,
+            &font,
+            &TextOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(missing, [('\u{1F600}', 6)]);
+
+        let mut pixels = bitmap(width, height, WHITE);
+        let missing = render_text_reporting_missing(
+            &mut pixels,
+            "all covered",
+            0,
+            0,
+            width,
+            &font,
+            &TextOptions::default(),
+        )
+        .unwrap();
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn debug_layout_adds_rules_without_moving_the_text() {
+        let font = arial();
+        let plain = TextOptions::default();
+        let debug = TextOptions {
+            debug_layout: true,
+            ..TextOptions::default()
+        };
+        let (width, height) = (160, 40);
+        let text_runs = |display_list: DisplayList| -> Vec<DisplayItem> {
+            display_list
+                .items
+                .into_iter()
+                .filter(|item| matches!(item, DisplayItem::Text(_)))
+                .collect()
+        };
+        let plain_list = layout_display_list(&font, "Debug", (0, 0), (width, height), &plain);
+        let debug_list = layout_display_list(&font, "Debug", (0, 0), (width, height), &debug);
+        assert!(debug_list.items.len() > plain_list.items.len());
+        assert_eq!(text_runs(plain_list), text_runs(debug_list));
+
+        // Text is drawn in gray levels; only the overlay adds color, e.g. the red baseline
+        let baseline = (font.ascent() as f32 * font.scale_factor(plain.size)).round() as usize;
+        let is_gray = |pixel: [u8; 4]| pixel[0] == pixel[1] && pixel[1] == pixel[2];
+        let mut plain_pixels = bitmap(width, height, WHITE);
+        render_text(&mut plain_pixels, "Debug", 0, 0, width, &font, &plain).unwrap();
+        assert!(plain_pixels
+            .chunks(4)
+            .all(|pixel| is_gray(pixel.try_into().unwrap())));
+        let mut debug_pixels = bitmap(width, height, WHITE);
+        render_text(&mut debug_pixels, "Debug", 0, 0, width, &font, &debug).unwrap();
+        let red = pixel(&debug_pixels, width, 20, baseline);
+        assert!(red[0] > red[1] && red[0] > red[2]);
+    }
+
+    #[test]
+    fn drag_across_three_glyphs_selects_them() {
+        let font = arial();
+        let options = TextOptions::default();
+        let lines = layout_text(&font, "abcdefg", &options);
+        let origin = (10, 20);
+        let glyphs = &lines[0].glyphs;
+        let y = 20.0 + line_height(&font, options.size) as f32 / 2.0;
+        // From the left part of 'c' to the right part of 'e'
+        let from = (10.0 + glyphs[2].x + glyphs[2].advance * 0.25, y);
+        let to = (10.0 + glyphs[4].x + glyphs[4].advance * 0.75, y);
+        for drag in [(from, to), (to, from)] {
+            let selection = Selection::from_drag(&font, &lines, origin, drag, &options);
+            assert_eq!((selection.start, selection.end), ((0, 2), (0, 5)));
+            assert_eq!(selection.text(&lines), "cde");
+            let rects = selection.highlight_rects(&font, &lines, origin, &options);
+            assert_eq!(rects.len(), 1);
+            assert_eq!(rects[0].x, (10.0 + glyphs[2].x).round() as i32);
+            let right = (10.0 + glyphs[4].x + glyphs[4].advance).round() as i32;
+            assert_eq!(rects[0].x + rects[0].width as i32, right);
+        }
+
+        // A click without a drag selects nothing
+        let selection = Selection::from_drag(&font, &lines, origin, (from, from), &options);
+        assert!(selection.is_empty());
+        assert_eq!(selection.text(&lines), "");
     }
 }
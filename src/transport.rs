@@ -0,0 +1,575 @@
+use crate::error::{CapyError, ErrorCode};
+use crate::http::RequestTimings;
+use socket2::SockRef;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Cursor, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RESOLVE_TIMEOUT: Duration = Duration::from_secs(10);
+// Addresses beyond these are never tried, however many the resolver returns
+const MAX_RESOLVED_ADDRESSES: usize = 16;
+
+// Canned responses keyed by host and port, served in order
+type ResponseQueues = HashMap<(String, u16), VecDeque<Vec<u8>>>;
+
+/// A byte stream a request is written to and its response read from.
+pub trait Connection: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Connection for T {}
+
+/// Opens the connections `HttpClient` sends requests over. Reads on a connection should give
+/// up with `WouldBlock` or `TimedOut` every so often, so the client can check for cancellation.
+pub trait Transport: Send + Sync {
+    /// Connects to `host:port`, recording the time spent resolving and connecting in `timings`.
+    /// Resolving, connecting and each read should give up by `deadline`, if there is one.
+    fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        deadline: Option<Instant>,
+        timings: &mut RequestTimings,
+    ) -> Result<Box<dyn Connection>, CapyError>;
+}
+
+/// Looks up the addresses of a host.
+pub trait Resolver: Send + Sync {
+    fn resolve(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>>;
+}
+
+/// Resolves through the operating system with `ToSocketAddrs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+        Ok((host, port)
+            .to_socket_addrs()?
+            .take(MAX_RESOLVED_ADDRESSES)
+            .collect())
+    }
+}
+
+/// Options set on each socket `TcpTransport` opens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SocketOptions {
+    // Disable Nagle's algorithm, so small requests go out without waiting for more data
+    pub nodelay: bool,
+    // SO_SNDBUF and SO_RCVBUF; None keeps the operating system's default
+    pub send_buffer_size: Option<usize>,
+    pub recv_buffer_size: Option<usize>,
+    // SO_KEEPALIVE
+    pub keepalive: bool,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            keepalive: false,
+        }
+    }
+}
+
+impl SocketOptions {
+    fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        let socket = SockRef::from(stream);
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        socket.set_keepalive(self.keepalive)
+    }
+}
+
+/// Plain TCP connections.
+#[derive(Clone)]
+pub struct TcpTransport {
+    // How long a blocked read waits before returning control to the client
+    read_timeout: Duration,
+    resolver: Arc<dyn Resolver>,
+    resolve_timeout: Duration,
+    socket_options: SocketOptions,
+}
+
+impl TcpTransport {
+    pub fn new(read_timeout: Duration) -> Self {
+        Self {
+            read_timeout,
+            resolver: Arc::new(SystemResolver),
+            resolve_timeout: DEFAULT_RESOLVE_TIMEOUT,
+            socket_options: SocketOptions::default(),
+        }
+    }
+
+    pub fn with_resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Resolution that takes longer than this fails with `ErrorCode::DeadlineExceeded`.
+    pub fn with_resolve_timeout(mut self, resolve_timeout: Duration) -> Self {
+        self.resolve_timeout = resolve_timeout;
+        self
+    }
+
+    pub fn with_socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
+    }
+
+    /// Resolves on a helper thread so a resolver that hangs can be abandoned after
+    /// `resolve_timeout`. The thread is left to finish on its own. Errors carry `host` as
+    /// context.
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        resolve_timeout: Duration,
+    ) -> Result<Vec<SocketAddr>, CapyError> {
+        let (sender, receiver) = mpsc::channel();
+        let resolver = self.resolver.clone();
+        let owned_host = host.to_string();
+        thread::spawn(move || {
+            // The receiver is gone if resolution timed out
+            let _ = sender.send(resolver.resolve(&owned_host, port));
+        });
+        let mut addrs = match receiver.recv_timeout(resolve_timeout) {
+            Ok(result) => result.map_err(|e| {
+                CapyError::with_source(ErrorCode::Unavailable, "resolver failed", Box::new(e))
+                    .with_context(host)
+            })?,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                return Err(
+                    CapyError::new(ErrorCode::DeadlineExceeded, "DNS resolution timed out")
+                        .with_context(host),
+                )
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(CapyError::new(
+                    ErrorCode::Internal,
+                    "resolver thread panicked",
+                ))
+            }
+        };
+        if addrs.is_empty() {
+            return Err(
+                CapyError::new(ErrorCode::NotFound, "host resolved to no addresses")
+                    .with_context(host),
+            );
+        }
+        addrs.truncate(MAX_RESOLVED_ADDRESSES);
+        Ok(addrs)
+    }
+}
+
+impl Transport for TcpTransport {
+    fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        deadline: Option<Instant>,
+        timings: &mut RequestTimings,
+    ) -> Result<Box<dyn Connection>, CapyError> {
+        // Resolve DNS
+        let dns_start = Instant::now();
+        let resolve_timeout = match deadline {
+            Some(deadline) => self.resolve_timeout.min(until(deadline)),
+            None => self.resolve_timeout,
+        };
+        let addrs = self.resolve(host, port, resolve_timeout)?;
+        timings.dns = dns_start.elapsed();
+
+        // Connect to the server, trying each address in turn
+        let connect_start = Instant::now();
+        let stream = match deadline {
+            Some(deadline) => connect_before(&addrs, deadline)?,
+            None => TcpStream::connect(&addrs[..])?,
+        };
+        stream.set_read_timeout(Some(self.read_timeout))?;
+        self.socket_options.apply(&stream)?;
+        timings.connect = connect_start.elapsed();
+        Ok(Box::new(TcpConnection {
+            stream,
+            read_timeout: self.read_timeout,
+            deadline,
+        }))
+    }
+}
+
+/// Time left until `deadline`, or zero once it has passed.
+fn until(deadline: Instant) -> Duration {
+    deadline.saturating_duration_since(Instant::now())
+}
+
+/// Like `TcpStream::connect`, but gives up on each address once `deadline` has passed.
+fn connect_before(addrs: &[SocketAddr], deadline: Instant) -> Result<TcpStream, CapyError> {
+    let mut last_error = None;
+    for addr in addrs {
+        let remaining = until(deadline);
+        if remaining.is_zero() {
+            break;
+        }
+        match TcpStream::connect_timeout(addr, remaining) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) if until(deadline) > Duration::ZERO => e.into(),
+        _ => CapyError::new(
+            ErrorCode::DeadlineExceeded,
+            "connecting took longer than the total timeout",
+        ),
+    })
+}
+
+/// A TCP stream whose reads wait no longer than its read timeout or the time left until its
+/// deadline, whichever is shorter.
+struct TcpConnection {
+    stream: TcpStream,
+    read_timeout: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Read for TcpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            // A zero timeout means none at all, so wait at least a millisecond
+            let remaining = until(deadline).max(Duration::from_millis(1));
+            self.stream
+                .set_read_timeout(Some(self.read_timeout.min(remaining)))?;
+        }
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Serves canned responses instead of touching the network, so fetches can be tested
+/// deterministically. Each connection to a host gets the next response queued for it.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Mutex<ResponseQueues>,
+    // Everything written to each connection, in the order they were opened
+    requests: Arc<Mutex<Vec<Vec<u8>>>>,
+    // Set to trickle responses out a byte at a time, this long apart
+    read_delay: Option<Duration>,
+    open_connections: Arc<Mutex<OpenConnections>>,
+}
+
+#[derive(Debug, Default)]
+struct OpenConnections {
+    all: ConnectionCount,
+    per_host: HashMap<(String, u16), ConnectionCount>,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionCount {
+    current: usize,
+    // The most that were ever open at once
+    peak: usize,
+}
+
+impl ConnectionCount {
+    fn open(&mut self) {
+        self.current += 1;
+        self.peak = self.peak.max(self.current);
+    }
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a raw HTTP response for the next connection to `host:port`.
+    pub fn with_response(self, host: &str, port: u16, response: &[u8]) -> Self {
+        lock(&self.responses)
+            .entry((host.to_string(), port))
+            .or_default()
+            .push_back(response.to_vec());
+        self
+    }
+
+    /// Makes every read wait `read_delay` and return a single byte, like a slow server
+    /// trickling its response.
+    pub fn with_read_delay(mut self, read_delay: Duration) -> Self {
+        self.read_delay = Some(read_delay);
+        self
+    }
+
+    /// The raw requests sent so far, one per connection.
+    pub fn requests(&self) -> Vec<String> {
+        lock(&self.requests)
+            .iter()
+            .map(|request| String::from_utf8_lossy(request).into_owned())
+            .collect()
+    }
+
+    /// The most connections that were open at the same time so far.
+    pub fn peak_connections(&self) -> usize {
+        lock(&self.open_connections).all.peak
+    }
+
+    /// The most connections to `host:port` that were open at the same time so far.
+    pub fn peak_connections_to(&self, host: &str, port: u16) -> usize {
+        lock(&self.open_connections)
+            .per_host
+            .get(&(host.to_string(), port))
+            .map_or(0, |count| count.peak)
+    }
+}
+
+impl Transport for MockTransport {
+    fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        _deadline: Option<Instant>,
+        _timings: &mut RequestTimings,
+    ) -> Result<Box<dyn Connection>, CapyError> {
+        let response = lock(&self.responses)
+            .get_mut(&(host.to_string(), port))
+            .and_then(VecDeque::pop_front)
+            .ok_or(CapyError::new(
+                ErrorCode::Unavailable,
+                "no canned response left for this host",
+            ))?;
+        let host = (host.to_string(), port);
+        let mut open_connections = lock(&self.open_connections);
+        open_connections.all.open();
+        open_connections
+            .per_host
+            .entry(host.clone())
+            .or_default()
+            .open();
+        drop(open_connections);
+        let mut requests = lock(&self.requests);
+        requests.push(Vec::new());
+        Ok(Box::new(MockConnection {
+            response: Cursor::new(response),
+            requests: Arc::clone(&self.requests),
+            index: requests.len() - 1,
+            read_delay: self.read_delay,
+            host,
+            open_connections: Arc::clone(&self.open_connections),
+        }))
+    }
+}
+
+struct MockConnection {
+    response: Cursor<Vec<u8>>,
+    requests: Arc<Mutex<Vec<Vec<u8>>>>,
+    // Which entry of `requests` this connection writes to
+    index: usize,
+    read_delay: Option<Duration>,
+    host: (String, u16),
+    open_connections: Arc<Mutex<OpenConnections>>,
+}
+
+impl Drop for MockConnection {
+    fn drop(&mut self) {
+        let mut open_connections = lock(&self.open_connections);
+        open_connections.all.current -= 1;
+        if let Some(count) = open_connections.per_host.get_mut(&self.host) {
+            count.current -= 1;
+        }
+    }
+}
+
+impl Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.read_delay {
+            Some(read_delay) => {
+                thread::sleep(read_delay);
+                let len = buf.len().min(1);
+                self.response.read(&mut buf[..len])
+            }
+            None => self.response.read(buf),
+        }
+    }
+}
+
+impl Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        lock(&self.requests)[self.index].extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // Nothing here can be left half-updated by a panic, so keep using poisoned data
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn read_gives_up_at_the_deadline_before_the_read_timeout() {
+        // A server that accepts the connection and then never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || listener.accept().unwrap());
+
+        let transport = TcpTransport::new(Duration::from_secs(30));
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(200);
+        let mut connection = transport
+            .connect(
+                "127.0.0.1",
+                port,
+                Some(deadline),
+                &mut RequestTimings::default(),
+            )
+            .unwrap();
+        let err = connection.read(&mut [0; 16]).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        drop(server.join());
+    }
+
+    #[test]
+    fn resolving_waits_no_longer_than_the_deadline() {
+        struct HangingResolver;
+        impl Resolver for HangingResolver {
+            fn resolve(&self, _host: &str, _port: u16) -> std::io::Result<Vec<SocketAddr>> {
+                thread::sleep(Duration::from_secs(30));
+                Ok(Vec::new())
+            }
+        }
+        let transport =
+            TcpTransport::new(Duration::from_secs(30)).with_resolver(Arc::new(HangingResolver));
+        let start = Instant::now();
+        let err = transport
+            .connect(
+                "example.com",
+                80,
+                Some(start + Duration::from_millis(100)),
+                &mut RequestTimings::default(),
+            )
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Hands back `count` loopback addresses, or never answers if `count` is `None`.
+    struct StubResolver(Option<usize>);
+
+    impl Resolver for StubResolver {
+        fn resolve(&self, _host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+            let Some(count) = self.0 else {
+                thread::sleep(Duration::from_secs(30));
+                return Ok(Vec::new());
+            };
+            Ok(vec![SocketAddr::from(([127, 0, 0, 1], port)); count])
+        }
+    }
+
+    #[test]
+    fn resolver_that_never_returns_times_out() {
+        let transport = TcpTransport::new(Duration::from_secs(30))
+            .with_resolver(Arc::new(StubResolver(None)))
+            .with_resolve_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let err = transport
+            .connect("example.com", 80, None, &mut RequestTimings::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn resolved_addresses_are_capped() {
+        let transport = TcpTransport::new(Duration::from_secs(30))
+            .with_resolver(Arc::new(StubResolver(Some(10_000))));
+        let addrs = transport
+            .resolve("example.com", 80, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(addrs.len(), MAX_RESOLVED_ADDRESSES);
+
+        let transport = transport.with_resolver(Arc::new(StubResolver(Some(0))));
+        let err = transport
+            .resolve("example.com", 80, Duration::from_secs(5))
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn socket_options_are_applied_to_the_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        SocketOptions::default().apply(&stream).unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+
+        let options = SocketOptions {
+            nodelay: false,
+            send_buffer_size: Some(64 * 1024),
+            recv_buffer_size: Some(64 * 1024),
+            keepalive: true,
+        };
+        options.apply(&stream).unwrap();
+        let socket = SockRef::from(&stream);
+        assert!(!stream.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        // The kernel may round buffer sizes up, e.g. Linux doubles them for bookkeeping
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    }
+
+    #[test]
+    fn resolution_errors_name_the_host() {
+        struct FailingResolver;
+        impl Resolver for FailingResolver {
+            fn resolve(&self, _host: &str, _port: u16) -> std::io::Result<Vec<SocketAddr>> {
+                Err(std::io::Error::other("no such host is known"))
+            }
+        }
+        let transport =
+            TcpTransport::new(Duration::from_secs(30)).with_resolver(Arc::new(FailingResolver));
+        let err = transport
+            .resolve("bad-host.invalid", 80, Duration::from_secs(5))
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Unavailable);
+        assert_eq!(err.context_chain(), ["bad-host.invalid"]);
+        assert!(err.to_string().contains("no such host is known"));
+
+        let transport = transport.with_resolver(Arc::new(StubResolver(Some(0))));
+        let err = transport
+            .resolve("empty.invalid", 80, Duration::from_secs(5))
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::NotFound);
+        assert!(err.to_string().contains("empty.invalid"));
+    }
+}
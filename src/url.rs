@@ -0,0 +1,249 @@
+use crate::error::{CapyError, ErrorCode};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Url {
+    scheme: String,
+    host: String,
+    port: u16,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Url, CapyError> {
+        let (scheme, rest) = url.split_once("://").ok_or(CapyError::new(
+            ErrorCode::InvalidArgument,
+            "URL is missing a scheme",
+        ))?;
+        let scheme = scheme.to_ascii_lowercase();
+        let default_port = match scheme.as_str() {
+            "http" => 80,
+            "https" => 443,
+            _ => {
+                return Err(CapyError::new(
+                    ErrorCode::InvalidArgument,
+                    "Only HTTP URLs are supported",
+                ))
+            }
+        };
+
+        // Split off the fragment first, then the query, so a '?' inside the fragment is kept
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+            None => (rest, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query.to_string())),
+            None => (rest, None),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], rest[index..].to_string()),
+            None => (rest, String::from("/")),
+        };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse::<u16>().map_err(|_| {
+                    CapyError::new(ErrorCode::InvalidArgument, "URL has an invalid port")
+                })?;
+                (host, port)
+            }
+            None => (authority, default_port),
+        };
+        if host.is_empty() {
+            return Err(CapyError::new(
+                ErrorCode::InvalidArgument,
+                "URL is missing a host",
+            ));
+        }
+
+        Ok(Url {
+            scheme,
+            host: host.to_ascii_lowercase(),
+            port,
+            path,
+            query,
+            fragment,
+        })
+    }
+
+    /// Resolves a (possibly relative) reference, such as a `Location` header, against this URL,
+    /// following RFC 3986 section 5.2.
+    pub fn join(&self, reference: &str) -> Result<Url, CapyError> {
+        if has_scheme(reference) {
+            return Url::parse(reference);
+        }
+        // A network-path reference keeps only the scheme
+        if reference.starts_with("//") {
+            return Url::parse(&format!("{}:{}", self.scheme, reference));
+        }
+
+        let mut url = self.clone();
+        url.fragment = None;
+        let (reference, fragment) = match reference.split_once('#') {
+            Some((reference, fragment)) => (reference, Some(fragment.to_string())),
+            None => (reference, None),
+        };
+        let (path, query) = match reference.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (reference, None),
+        };
+
+        if path.starts_with('/') {
+            url.path = remove_dot_segments(path);
+            url.query = query;
+        } else if !path.is_empty() {
+            let base = &self.path[..self.path.rfind('/').map_or(0, |index| index + 1)];
+            url.path = remove_dot_segments(&format!("{}{}", base, path));
+            url.query = query;
+        } else if query.is_some() {
+            url.query = query;
+        }
+        url.fragment = fragment;
+        Ok(url)
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+
+    /// The origin-form target sent in the request line, e.g. `/index.html?q=1`.
+    pub fn request_target(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{}?{}", self.path, query),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// Whether a reference starts with a scheme, like `https:` or `mailto:`, making it absolute.
+fn has_scheme(reference: &str) -> bool {
+    let Some((scheme, _)) = reference.split_once(':') else {
+        return false;
+    };
+    scheme.starts_with(|char: char| char.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '+' | '-' | '.'))
+}
+
+/// Resolves `.` and `..` segments in an absolute path, as in RFC 3986 section 5.2.4. A `..`
+/// at the root stays at the root.
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    for (index, &segment) in segments.iter().enumerate() {
+        let is_last = index + 1 == segments.len();
+        match segment {
+            "." | ".." => {
+                if segment == ".." {
+                    output.pop();
+                }
+                // A trailing dot segment still refers to a directory
+                if is_last {
+                    output.push("");
+                }
+            }
+            _ => output.push(segment),
+        }
+    }
+    format!("/{}", output.join("/"))
+}
+
+impl Display for Url {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        let default_port = if self.scheme == "https" { 443 } else { 80 };
+        if self.port != default_port {
+            write!(f, ":{}", self.port)?;
+        }
+        write!(f, "{}", self.request_target())?;
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_resolve_like_the_rfc_3986_examples() {
+        // Section 5.4.1, except "g:h", whose scheme we can't fetch
+        let base = Url::parse("http://a/b/c/d;p?q").unwrap();
+        let examples = [
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("g;x", "http://a/b/c/g;x"),
+            ("g;x?y#s", "http://a/b/c/g;x?y#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../", "http://a/"),
+            ("../../g", "http://a/g"),
+            // Some of the abnormal examples from section 5.4.2
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            ("./../g", "http://a/b/g"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+        ];
+        for (reference, expected) in examples {
+            assert_eq!(
+                base.join(reference).unwrap(),
+                Url::parse(expected).unwrap(),
+                "{reference:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn absolute_references_replace_the_base() {
+        let base = Url::parse("http://a/b/c/d;p?q").unwrap();
+        let url = base.join("HTTPS://Example.com:8443/x").unwrap();
+        assert_eq!(url.to_string(), "https://example.com:8443/x");
+        assert!(base.join("mailto:someone@example.com").is_err());
+    }
+}